@@ -0,0 +1,12 @@
+CREATE TABLE newsletter_deliveries (
+    newsletter_delivery_id uuid NOT NULL,
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL,
+    status_code SMALLINT NULL,
+    provider_message_id TEXT NULL,
+    error TEXT NULL,
+    PRIMARY KEY(newsletter_delivery_id)
+);
+
+CREATE INDEX newsletter_deliveries_issue_idx ON newsletter_deliveries (newsletter_issue_id);
@@ -0,0 +1,11 @@
+ALTER TABLE newsletter_issues
+    ADD COLUMN status TEXT NULL,
+    ADD COLUMN scheduled_for timestamptz NULL,
+    ALTER COLUMN published_at DROP NOT NULL;
+
+UPDATE newsletter_issues
+    SET status = 'queued', scheduled_for = published_at;
+
+ALTER TABLE newsletter_issues
+    ALTER COLUMN status SET NOT NULL,
+    ALTER COLUMN scheduled_for SET NOT NULL;
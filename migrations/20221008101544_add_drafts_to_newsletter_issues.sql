@@ -0,0 +1,10 @@
+-- Drafts have not been given a send time yet
+ALTER TABLE newsletter_issues
+    ALTER COLUMN scheduled_for DROP NOT NULL;
+
+UPDATE newsletter_issues i
+    SET status = 'sent'
+    WHERE status = 'queued' AND NOT EXISTS (
+        SELECT 1 FROM issue_delivery_queue q
+        WHERE q.newsletter_issue_id = i.newsletter_issue_id
+    );
@@ -0,0 +1,31 @@
+CREATE TABLE lists (
+    list_id uuid NOT NULL,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(list_id)
+);
+
+CREATE TABLE list_subscriptions (
+    list_id uuid NOT NULL REFERENCES lists (list_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    subscribed_at timestamptz NOT NULL,
+    PRIMARY KEY(list_id, subscriber_id)
+);
+
+-- Everyone who subscribed so far did so to the one and only newsletter
+INSERT INTO lists (list_id, slug, name, created_at)
+VALUES ('8c1b2a66-6c86-4c6e-9d1c-5f1ac4f2a3e0', 'newsletter', 'Newsletter', now());
+
+INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+SELECT '8c1b2a66-6c86-4c6e-9d1c-5f1ac4f2a3e0', id, subscribed_at
+FROM subscriptions;
+
+ALTER TABLE newsletter_issues
+    ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+
+UPDATE newsletter_issues
+    SET list_id = '8c1b2a66-6c86-4c6e-9d1c-5f1ac4f2a3e0';
+
+ALTER TABLE newsletter_issues
+    ALTER COLUMN list_id SET NOT NULL;
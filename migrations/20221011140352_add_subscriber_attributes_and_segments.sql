@@ -0,0 +1,12 @@
+ALTER TABLE subscriptions
+    ADD COLUMN locale TEXT NULL;
+
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY(subscriber_id, tag)
+);
+
+-- The filter expression an issue is targeted with, NULL means everyone on the list
+ALTER TABLE newsletter_issues
+    ADD COLUMN segment TEXT NULL;
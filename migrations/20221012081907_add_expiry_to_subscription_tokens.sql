@@ -0,0 +1,13 @@
+ALTER TABLE subscription_tokens
+    ADD COLUMN issued_at timestamptz NULL,
+    ADD COLUMN expires_at timestamptz NULL;
+
+-- Outstanding links get a fresh lease rather than expiring on deploy
+UPDATE subscription_tokens
+    SET issued_at = now(), expires_at = now() + interval '48 hours';
+
+ALTER TABLE subscription_tokens
+    ALTER COLUMN issued_at SET NOT NULL,
+    ALTER COLUMN expires_at SET NOT NULL;
+
+CREATE INDEX subscription_tokens_subscriber_idx ON subscription_tokens (subscriber_id);
@@ -0,0 +1,2 @@
+-- Subscribers confirmed before this migration have no known confirmation time
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
@@ -0,0 +1,2 @@
+-- Keyset pagination of the admin subscriber browser, newest first
+CREATE INDEX subscriptions_subscribed_at_idx ON subscriptions (subscribed_at DESC, id DESC);
@@ -0,0 +1,6 @@
+-- Only a hash of the address is kept, enough to recognise it without storing it
+CREATE TABLE erased_subscribers (
+    email_hash TEXT NOT NULL,
+    erased_at timestamptz NOT NULL,
+    PRIMARY KEY(email_hash)
+);
@@ -0,0 +1,7 @@
+-- Addresses we must never mail again, stored lowercased
+CREATE TABLE suppressions (
+    email TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    suppressed_at timestamptz NOT NULL,
+    PRIMARY KEY(email)
+);
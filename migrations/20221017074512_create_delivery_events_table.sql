@@ -0,0 +1,13 @@
+-- What the email provider told us happened to a delivery after we sent it
+CREATE TABLE delivery_events (
+    delivery_event_id uuid NOT NULL,
+    newsletter_delivery_id uuid NULL REFERENCES newsletter_deliveries (newsletter_delivery_id),
+    subscriber_email TEXT NOT NULL,
+    event TEXT NOT NULL,
+    description TEXT NULL,
+    received_at timestamptz NOT NULL,
+    PRIMARY KEY(delivery_event_id)
+);
+
+CREATE INDEX delivery_events_subscriber_email_idx ON delivery_events (subscriber_email);
+CREATE INDEX newsletter_deliveries_provider_message_id_idx ON newsletter_deliveries (provider_message_id);
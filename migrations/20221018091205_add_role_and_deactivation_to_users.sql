@@ -0,0 +1,9 @@
+ALTER TABLE users
+    ADD COLUMN role TEXT NULL,
+    ADD COLUMN deactivated_at timestamptz NULL;
+
+-- Whoever could log in so far could do everything
+UPDATE users SET role = 'owner';
+
+ALTER TABLE users
+    ALTER COLUMN role SET NOT NULL;
@@ -0,0 +1,12 @@
+CREATE TABLE user_invitations (
+    invitation_token TEXT NOT NULL,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL,
+    invited_by uuid NOT NULL REFERENCES users (user_id),
+    issued_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    accepted_at timestamptz NULL,
+    PRIMARY KEY (invitation_token)
+);
+
+CREATE INDEX user_invitations_email_idx ON user_invitations (lower(email));
@@ -0,0 +1,4 @@
+ALTER TABLE users
+    ADD COLUMN email TEXT NULL,
+    -- Bumped to log the user out everywhere
+    ADD COLUMN session_epoch INTEGER NOT NULL DEFAULT 0;
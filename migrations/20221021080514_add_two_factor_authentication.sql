@@ -0,0 +1,12 @@
+ALTER TABLE users
+    -- Encrypted with the TOTP encryption key, NULL when not enrolled
+    ADD COLUMN totp_secret BYTEA NULL,
+    -- The time step of the last accepted code, which cannot be replayed
+    ADD COLUMN totp_last_step BIGINT NULL;
+
+CREATE TABLE user_recovery_codes (
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
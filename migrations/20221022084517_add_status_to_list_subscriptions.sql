@@ -0,0 +1,17 @@
+-- Each list is confirmed and left on its own
+ALTER TABLE list_subscriptions
+    ADD COLUMN status TEXT NULL,
+    ADD COLUMN confirmed_at timestamptz NULL;
+
+UPDATE list_subscriptions m
+    SET status = s.status, confirmed_at = s.confirmed_at
+    FROM subscriptions s
+    WHERE s.id = m.subscriber_id;
+
+ALTER TABLE list_subscriptions
+    ALTER COLUMN status SET NOT NULL;
+
+-- The list a confirmation link is for. Links without one, such as those sent
+-- before this migration, confirm every list the subscriber is waiting on.
+ALTER TABLE subscription_tokens
+    ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
@@ -0,0 +1,10 @@
+-- Emailed to subscribers asking for their data, each lets them export or
+-- erase it once
+CREATE TABLE data_access_tokens (
+    data_access_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    issued_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (data_access_token)
+);
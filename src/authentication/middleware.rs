@@ -4,11 +4,15 @@ use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
-    FromRequest, HttpMessage,
+    http::Method,
+    web, FromRequest, HttpMessage, HttpResponse,
 };
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::Role;
 use crate::{
     session_state::TypedSession,
     utils::{e500, see_other},
@@ -31,6 +35,7 @@ impl Deref for UserId {
     }
 }
 
+/// Lets logged in, active users through, along with their `UserId` and `Role`.
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
@@ -39,16 +44,94 @@ pub async fn reject_anonymous_users(
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The connection pool is registered as app data.")
+        .clone();
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
+        None => {
+            let e = anyhow::anyhow!("User is not logged in.");
+            return Err(InternalError::from_response(e, see_other("/login")).into());
+        }
+    };
+    let session_epoch = session.get_session_epoch().map_err(e500)?.unwrap_or(0);
+    match get_active_role(&pool, user_id, session_epoch)
+        .await
+        .map_err(e500)?
+    {
+        Some(role) => {
             req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(role);
             next.call(req).await
-        },
+        }
         None => {
-            let response = see_other("/login");
-            let e = anyhow::anyhow!("User is not logged in.");
-
-            Err(InternalError::from_response(e, response).into())
+            // Deactivated, or logged out everywhere, while logged in.
+            session.logout();
+            let e = anyhow::anyhow!("User is deactivated or the session was revoked.");
+            Err(InternalError::from_response(e, see_other("/login")).into())
         }
     }
 }
+
+/// Viewers can only look around, and manage their own credentials.
+pub async fn reject_read_only_users(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let read_only = matches!(req.method(), &Method::GET | &Method::HEAD)
+        || matches!(
+            req.path(),
+            "/admin/logout" | "/admin/password" | "/admin/two-factor" | "/admin/two-factor/disable"
+        );
+    if !read_only && role(&req) < Role::Editor {
+        return Err(forbidden());
+    }
+    next.call(req).await
+}
+
+pub async fn reject_non_owners(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if role(&req) < Role::Owner {
+        return Err(forbidden());
+    }
+    next.call(req).await
+}
+
+/// Users without a known role get the least privileges.
+fn role(req: &ServiceRequest) -> Role {
+    req.extensions()
+        .get::<Role>()
+        .copied()
+        .unwrap_or(Role::Viewer)
+}
+
+fn forbidden() -> actix_web::Error {
+    let response = HttpResponse::Forbidden().body("You are not allowed to do this.");
+    let e = anyhow::anyhow!("User is not allowed to do this.");
+    InternalError::from_response(e, response).into()
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_active_role(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_epoch: i32,
+) -> anyhow::Result<Option<Role>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT role
+        FROM users
+        WHERE user_id = $1 AND deactivated_at IS NULL AND session_epoch = $2
+        "#,
+        user_id,
+        session_epoch
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the user's role.")?;
+    row.map(|row| Role::parse(&row.role).map_err(anyhow::Error::msg))
+        .transpose()
+}
@@ -1,5 +1,16 @@
 mod middleware;
 mod password;
+mod role;
+mod sessions;
+mod totp;
+mod two_factor;
 
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
-pub use middleware::{reject_anonymous_users, UserId};
+pub use password::{add_user, change_password, validate_credentials, AuthError, Credentials};
+pub use middleware::{reject_anonymous_users, reject_non_owners, reject_read_only_users, UserId};
+pub use role::Role;
+pub use sessions::{get_session_epoch, revoke_sessions};
+pub use totp::{TotpCipher, TotpSecret};
+pub use two_factor::{
+    count_recovery_codes, disable_two_factor, enable_two_factor, get_totp_secret, has_two_factor,
+    verify_second_factor,
+};
@@ -4,8 +4,9 @@ use argon2::{
     PasswordVerifier, Version,
 };
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
+use super::Role;
 use crate::telemetry::spawn_blocking_with_tracing;
 
 pub struct Credentials {
@@ -76,7 +77,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id,password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND deactivated_at IS NULL
         "#,
         username,
     )
@@ -117,6 +118,40 @@ pub async fn change_password<'a>(
     Ok(())
 }
 
+/// Returns `None` if the username is already taken.
+#[tracing::instrument(skip(password, executor))]
+pub async fn add_user<'e>(
+    username: &str,
+    email: Option<&str>,
+    password: &Secret<String>,
+    role: Role,
+    executor: impl PgExecutor<'e>,
+) -> Result<Option<uuid::Uuid>, anyhow::Error> {
+    let password = password.clone();
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(&password))
+        .await?
+        .context("Failed to hash password.")?;
+    let user_id = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, password_hash, role)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING user_id
+        "#,
+        uuid::Uuid::new_v4(),
+        username,
+        email,
+        password_hash.expose_secret(),
+        role.as_str()
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to insert the user in the database.")?
+    .map(|row| row.user_id);
+
+    Ok(user_id)
+}
+
 fn compute_password_hash(password: &Secret<String>) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
 
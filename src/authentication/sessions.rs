@@ -0,0 +1,35 @@
+use anyhow::Context;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// Sessions remember the epoch of their user as of logging in: bumping it
+/// logs the user out everywhere.
+#[tracing::instrument(skip(executor))]
+pub async fn get_session_epoch<'e>(
+    executor: impl PgExecutor<'e>,
+    user_id: Uuid,
+) -> Result<i32, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT session_epoch FROM users WHERE user_id = $1",
+        user_id
+    )
+    .fetch_one(executor)
+    .await
+    .context("Failed to fetch the session epoch.")?;
+    Ok(row.session_epoch)
+}
+
+#[tracing::instrument(skip(executor))]
+pub async fn revoke_sessions<'e>(
+    executor: impl PgExecutor<'e>,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "UPDATE users SET session_epoch = session_epoch + 1 WHERE user_id = $1",
+        user_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke the sessions.")?;
+    Ok(())
+}
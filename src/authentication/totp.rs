@@ -0,0 +1,239 @@
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::Context;
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng, RngCore};
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// RFC 6238 defaults, which is what authenticator apps expect.
+const DIGITS: u32 = 6;
+const STEP_SECONDS: i64 = 30;
+const SECRET_SIZE: usize = 20;
+const NONCE_SIZE: usize = 12;
+const BASE32: Alphabet = Alphabet::RFC4648 { padding: false };
+
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_SIZE: usize = 10;
+
+/// The key shared with the user's authenticator app.
+pub struct TotpSecret(Vec<u8>);
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret([REDACTED])")
+    }
+}
+
+impl TotpSecret {
+    pub fn generate() -> TotpSecret {
+        let mut secret = vec![0; SECRET_SIZE];
+        thread_rng().fill_bytes(&mut secret);
+        TotpSecret(secret)
+    }
+
+    pub fn parse_base32(s: &str) -> Option<TotpSecret> {
+        base32::decode(BASE32, s).map(TotpSecret)
+    }
+
+    /// What users type in when they can't scan the otpauth URI.
+    pub fn to_base32(&self) -> String {
+        base32::encode(BASE32, &self.0)
+    }
+
+    /// What authenticator apps scan, usually from a QR code.
+    pub fn otpauth_uri(&self, issuer: &str, account: &str) -> String {
+        let mut uri = Url::parse("otpauth://totp").expect("The base URI is valid");
+        uri.path_segments_mut()
+            .expect("The base URI has a path")
+            .push(&format!("{}:{}", issuer, account));
+        uri.query_pairs_mut()
+            .append_pair("secret", &self.to_base32())
+            .append_pair("issuer", issuer)
+            .append_pair("digits", &DIGITS.to_string())
+            .append_pair("period", &STEP_SECONDS.to_string());
+        uri.to_string()
+    }
+
+    /// The code an authenticator app shows at `unix_time`.
+    pub fn generate_code(&self, unix_time: i64) -> String {
+        let code = self.code_at(unix_time.div_euclid(STEP_SECONDS));
+        format!("{:0width$}", code, width = DIGITS as usize)
+    }
+
+    /// Returns the time step `code` is valid for, tolerating a step of clock
+    /// drift either way.
+    pub fn verify(&self, code: &str, unix_time: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let code: u32 = code.parse().ok()?;
+        let step = unix_time.div_euclid(STEP_SECONDS);
+        (step - 1..=step + 1).find(|&s| self.code_at(s) == code)
+    }
+
+    /// RFC 4226 HOTP with the time step as the counter.
+    fn code_at(&self, step: i64) -> u32 {
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(&self.0).expect("HMAC can take a key of any size");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        let offset = (hash[hash.len() - 1] & 0xf) as usize;
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+        binary % 10u32.pow(DIGITS)
+    }
+}
+
+/// Encrypts the secrets at rest, so that a database dump is not enough to
+/// get past the second factor.
+pub struct TotpCipher(Aes256Gcm);
+
+impl TotpCipher {
+    pub fn new(key: &Secret<String>) -> TotpCipher {
+        let mut hasher = Sha256::new();
+        hasher.update(b"totp-encryption:");
+        hasher.update(key.expose_secret().as_bytes());
+        TotpCipher(Aes256Gcm::new(Key::from_slice(&hasher.finalize())))
+    }
+
+    /// The nonce followed by the ciphertext.
+    pub fn encrypt(&self, secret: &TotpSecret) -> Vec<u8> {
+        let mut nonce = [0; NONCE_SIZE];
+        thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .0
+            .encrypt(Nonce::from_slice(&nonce), secret.0.as_ref())
+            .expect("Encrypting into a Vec cannot fail");
+        [&nonce[..], &ciphertext].concat()
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<TotpSecret, anyhow::Error> {
+        if encrypted.len() < NONCE_SIZE {
+            anyhow::bail!("The encrypted secret is too short.");
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_SIZE);
+        self.0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(TotpSecret)
+            .map_err(|_| anyhow::anyhow!("Cannot decrypt the secret."))
+            .context("The TOTP encryption key does not match the one the secret was stored with.")
+    }
+}
+
+/// Single-use codes to log in when the authenticator app is lost.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = thread_rng();
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let code: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .take(RECOVERY_CODE_SIZE)
+                .collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are stored hashed, ignoring case and dashes.
+pub fn hash_recovery_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::{generate_recovery_codes, hash_recovery_code, TotpCipher, TotpSecret};
+
+    /// The SHA-1 secret of the RFC 6238 test vectors.
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret(b"12345678901234567890".to_vec())
+    }
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        // The RFC uses 8 digits, these are their last 6.
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(rfc_secret().generate_code(time), code);
+            assert_eq!(rfc_secret().verify(code, time), Some(time / 30));
+        }
+    }
+
+    #[test]
+    fn codes_from_neighbouring_steps_are_accepted() {
+        assert_eq!(
+            rfc_secret().verify("081804", 1111111109 + 30),
+            Some(37037036)
+        );
+        assert_eq!(
+            rfc_secret().verify("081804", 1111111109 - 30),
+            Some(37037036)
+        );
+        assert_eq!(rfc_secret().verify("081804", 1111111109 + 90), None);
+    }
+
+    #[test]
+    fn malformed_codes_are_rejected() {
+        for code in ["", "28708", "2870822", "28708a", "-87082"] {
+            assert_eq!(rfc_secret().verify(code, 59), None);
+        }
+    }
+
+    #[test]
+    fn secrets_round_trip_through_base32() {
+        let secret = TotpSecret::generate();
+        let parsed = TotpSecret::parse_base32(&secret.to_base32()).unwrap();
+        assert_eq!(parsed.0, secret.0);
+        assert_eq!(rfc_secret().to_base32(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn the_otpauth_uri_carries_the_secret_and_the_account() {
+        let uri = rfc_secret().otpauth_uri("zero2prod", "ursula le guin");
+        assert_eq!(
+            uri,
+            "otpauth://totp/zero2prod:ursula%20le%20guin\
+            ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=zero2prod&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn secrets_are_encrypted_and_decrypted() {
+        let cipher = TotpCipher::new(&Secret::new("a-very-secret-key".to_string()));
+        let secret = TotpSecret::generate();
+        let encrypted = cipher.encrypt(&secret);
+        assert!(!encrypted.windows(secret.0.len()).any(|w| w == secret.0));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap().0, secret.0);
+
+        let other_cipher = TotpCipher::new(&Secret::new("another-key".to_string()));
+        assert!(other_cipher.decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_hashed_loosely() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), 10);
+        assert!(codes.iter().all(|c| c.len() == 11));
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&codes[0].to_uppercase().replace('-', " "))
+        );
+        assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+    }
+}
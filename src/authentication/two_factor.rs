@@ -0,0 +1,174 @@
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::totp::{generate_recovery_codes, hash_recovery_code, TotpCipher, TotpSecret};
+
+#[tracing::instrument(skip(pool))]
+pub async fn has_two_factor(pool: &PgPool, user_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret IS NOT NULL AS "enabled!" FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check for a second factor.")?;
+    Ok(row.enabled)
+}
+
+#[tracing::instrument(skip(pool, cipher))]
+pub async fn get_totp_secret(
+    pool: &PgPool,
+    cipher: &TotpCipher,
+    user_id: Uuid,
+) -> Result<Option<TotpSecret>, anyhow::Error> {
+    let row = sqlx::query!("SELECT totp_secret FROM users WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to fetch the TOTP secret.")?;
+    row.totp_secret
+        .map(|encrypted| cipher.decrypt(&encrypted))
+        .transpose()
+}
+
+/// Stores the secret, once `verified_step` proved the user set it up, and
+/// returns a fresh set of recovery codes replacing any previous ones.
+#[tracing::instrument(skip(pool, cipher, secret))]
+pub async fn enable_two_factor(
+    pool: &PgPool,
+    cipher: &TotpCipher,
+    user_id: Uuid,
+    secret: &TotpSecret,
+    verified_step: i64,
+) -> Result<Vec<String>, anyhow::Error> {
+    let recovery_codes = generate_recovery_codes();
+    let code_hashes: Vec<_> = recovery_codes
+        .iter()
+        .map(|c| hash_recovery_code(c))
+        .collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    sqlx::query!(
+        "UPDATE users SET totp_secret = $1, totp_last_step = $2 WHERE user_id = $3",
+        cipher.encrypt(secret),
+        verified_step,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the TOTP secret.")?;
+    sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the previous recovery codes.")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO user_recovery_codes (user_id, code_hash)
+        SELECT $1, * FROM UNNEST($2::text[])
+        "#,
+        user_id,
+        &code_hashes[..]
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+    Ok(recovery_codes)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn disable_two_factor(pool: &PgPool, user_id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    sqlx::query!(
+        "UPDATE users SET totp_secret = NULL, totp_last_step = NULL WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the TOTP secret.")?;
+    sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+    Ok(())
+}
+
+/// Accepts either a code from the authenticator app or an unused recovery
+/// code. Neither can be used twice.
+#[tracing::instrument(skip(pool, secret, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    secret: &TotpSecret,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    if let Some(step) = secret.verify(code, Utc::now().timestamp()) {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_step = $1
+            WHERE user_id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+            "#,
+            step,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the TOTP code.")?;
+        return Ok(result.rows_affected() > 0);
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE user_recovery_codes
+        SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_recovery_code(code)
+    )
+    .execute(pool)
+    .await
+    .context("Failed to use the recovery code.")?;
+    if result.rows_affected() > 0 {
+        tracing::warn!("A recovery code was used.");
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn count_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM user_recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count the recovery codes.")?;
+    Ok(row.count)
+}
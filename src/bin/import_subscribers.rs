@@ -0,0 +1,54 @@
+//! Imports subscribers from a CSV file, for migrations too big for the admin form.
+//!
+//! Usage: `import_subscribers <file.csv> [list]`
+use anyhow::Context;
+use secrecy::ExposeSecret;
+use std::fs::File;
+use std::io::stdout;
+use zero2prod::{
+    configuration::get_configuration,
+    domain::ListSlug,
+    routes::get_list_id,
+    startup::get_connection_pool,
+    subscriber_import::import_csv,
+    telemetry::{get_subscriber, init_subscriber},
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .context("Usage: import_subscribers <file.csv> [list]")?;
+    let list = ListSlug::parse(args.next().unwrap_or_else(|| ListSlug::DEFAULT.to_string()))
+        .map_err(anyhow::Error::msg)?;
+
+    let configuration = get_configuration().unwrap();
+
+    let subscriber = get_subscriber("import_subscribers".into(), "warn".into(), stdout);
+    init_subscriber(subscriber);
+
+    let pool = get_connection_pool(configuration.database_url.expose_secret());
+    let list_id = get_list_id(&pool, &list)
+        .await
+        .context("Failed to look up the mailing list.")?
+        .with_context(|| format!("{} is not a known list", list.as_ref()))?;
+    let file = File::open(&path).with_context(|| format!("Cannot open {}", path))?;
+
+    let report = import_csv(&pool, list_id, file).await?;
+    for row in &report.skipped {
+        println!("line {}: {} skipped, {}", row.line, row.email, row.reason);
+    }
+    println!(
+        "Imported {} subscribers into {}, skipped {} rows.",
+        report.n_imported,
+        list.as_ref(),
+        report.skipped.len()
+    );
+    println!(
+        "Added {} subscribers we already knew to {}.",
+        report.n_added_to_list,
+        list.as_ref()
+    );
+    Ok(())
+}
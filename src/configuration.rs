@@ -1,15 +1,79 @@
 use dotenv::{dotenv, Error};
 use secrecy::Secret;
-use std::env::var;
+use std::{env::var, path::PathBuf};
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::{EmailDomainBlocklist, SubscriberEmail},
+    email_client::{
+        EmailClient, EmailTransport, FileTransport, HttpApiTransport, SmtpTls, SmtpTransport,
+    },
+};
+
+#[derive(Clone, Debug)]
+pub enum EmailTransportSettings {
+    Http {
+        base_url: String,
+        authorization_token: Secret<String>,
+    },
+    Smtp {
+        host: String,
+        port: u16,
+        tls: SmtpTls,
+        username: Option<String>,
+        password: Option<Secret<String>>,
+    },
+    File {
+        directory: PathBuf,
+    },
+}
 
 #[derive(Clone, Debug)]
 pub struct EmailClientSettings {
-    pub base_url: String,
-    pub authorization_token: Secret<String>,
+    pub transport: EmailTransportSettings,
     pub sender_email: String,
     pub timeout_milliseconds: u64,
+    /// Signs the provider's bounce and complaint notifications, the webhook
+    /// rejects all of them when unset.
+    pub webhook_secret: Option<Secret<String>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IssueDeliverySettings {
+    pub max_retries: u16,
+    pub retry_base_delay_seconds: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct SubscriptionSettings {
+    pub confirmation_token_ttl_hours: u32,
+    pub max_confirmation_emails_per_day: u32,
+    pub blocked_email_domains: EmailDomainBlocklist,
+    /// Overrides for the pages shown after clicking a confirmation link.
+    pub confirmation_templates_directory: Option<PathBuf>,
+    /// Send subscribers here after they confirm, instead of showing a page.
+    pub confirmation_redirect_url: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitSettings {
+    /// Prefixes every redis key, so several deployments can share one redis.
+    pub key_prefix: String,
+    /// Take the client address from the `Forwarded`/`X-Forwarded-For` headers,
+    /// only enable it behind a reverse proxy which sets them.
+    pub trust_forwarded_for: bool,
+    pub subscriptions_per_ip_per_hour: u32,
+    pub subscriptions_per_email_per_hour: u32,
+    pub password_resets_per_ip_per_hour: u32,
+    /// Reset emails sent to one address, further requests are dropped quietly.
+    pub password_resets_per_email_per_hour: u32,
+    /// Failed logins for a username before every further attempt gets slower.
+    pub login_failures_before_delay: u32,
+    /// Failed logins locking a username out.
+    pub login_failures_per_username: u32,
+    /// Failed logins locking an address out, whatever the username.
+    pub login_failures_per_ip: u32,
+    /// How long a lockout lasts after the latest failed login.
+    pub login_lockout_minutes: u64,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -17,6 +81,9 @@ pub struct ApplicationSettings {
     pub address: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Encrypts the two-factor secrets, nobody can enable two-factor
+    /// authentication when unset.
+    pub totp_encryption_key: Option<Secret<String>>,
 }
 
 #[derive(Clone, Debug)]
@@ -24,6 +91,9 @@ pub struct Settings {
     pub database_url: Secret<String>,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub issue_delivery: IssueDeliverySettings,
+    pub subscriptions: SubscriptionSettings,
+    pub rate_limit: RateLimitSettings,
     pub redis_uri: Secret<String>,
 }
 
@@ -31,12 +101,30 @@ impl EmailClientSettings {
     pub fn client(self) -> EmailClient {
         let sender_email = self.sender().expect("Invalid sender email address.");
         let timeout = self.timeout();
-        EmailClient::new(
-            self.base_url,
-            sender_email,
-            self.authorization_token,
-            timeout,
-        )
+        let transport: Box<dyn EmailTransport> = match self.transport {
+            EmailTransportSettings::Http {
+                base_url,
+                authorization_token,
+            } => Box::new(HttpApiTransport::new(
+                base_url,
+                authorization_token,
+                timeout,
+            )),
+            EmailTransportSettings::Smtp {
+                host,
+                port,
+                tls,
+                username,
+                password,
+            } => Box::new(
+                SmtpTransport::new(&host, port, tls, username.zip(password), timeout)
+                    .expect("Invalid SMTP settings."),
+            ),
+            EmailTransportSettings::File { directory } => {
+                Box::new(FileTransport::new(directory).expect("Invalid email directory."))
+            }
+        };
+        EmailClient::new(sender_email, transport)
     }
 
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
@@ -48,6 +136,24 @@ impl EmailClientSettings {
     }
 }
 
+impl SubscriptionSettings {
+    pub fn confirmation_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.confirmation_token_ttl_hours.into())
+    }
+}
+
+impl IssueDeliverySettings {
+    /// Exponential backoff: the delay doubles with every failed attempt.
+    pub fn retry_delay(&self, n_retries: u16) -> std::time::Duration {
+        let factor = 2u64.saturating_pow(n_retries.into());
+        std::time::Duration::from_secs(self.retry_base_delay_seconds.saturating_mul(factor))
+    }
+}
+
+const DISPOSABLE_EMAIL_DOMAINS: &str = "10minutemail.com,discard.email,dispostable.com,\
+    getnada.com,guerrillamail.com,maildrop.cc,mailinator.com,mintemail.com,sharklasers.com,\
+    temp-mail.org,tempmail.com,throwawaymail.com,trashmail.com,yopmail.com";
+
 pub fn get_configuration() -> Result<Settings, Error> {
     dotenv().ok();
 
@@ -67,19 +173,155 @@ pub fn get_configuration() -> Result<Settings, Error> {
             address,
             base_url,
             hmac_secret: Secret::new(var("HMAC_SECRET").expect("HMAC_SECRET is missing")),
+            totp_encryption_key: var("TOTP_ENCRYPTION_KEY")
+                .ok()
+                .filter(|k| !k.is_empty())
+                .map(Secret::new),
         },
         email_client: EmailClientSettings {
-            authorization_token: Secret::new(
-                var("EMAIL_CLIENT_AUTHORIZATION_TOKEN")
-                    .expect("EMAIL_CLIENT_AUTHORIZATION_TOKEN missing"),
-            ),
-            base_url: var("EMAIL_CLIENT_BASE_URL").expect("EMAIL_CLIENT_BASE_URL missing"),
+            transport: get_email_transport_settings(),
             sender_email: var("EMAIL_CLIENT_SENDER_EMAIL")
                 .expect("EMAIL_CLIENT_SENDER_EMAIL missing"),
             timeout_milliseconds: var("EMAIL_CLIENT_TIMEOUT_MILLISECONDS").map_or(5000, |v| {
                 v.parse::<u64>()
                     .expect("EMAIL_CLIENT_TIMEOUT_MILLISECONDS cannot be parsed as u64")
             }),
+            webhook_secret: var("EMAIL_WEBHOOK_SECRET").ok().map(Secret::new),
+        },
+        issue_delivery: IssueDeliverySettings {
+            max_retries: var("ISSUE_DELIVERY_MAX_RETRIES").map_or(5, |v| {
+                let max_retries = v
+                    .parse::<u16>()
+                    .expect("ISSUE_DELIVERY_MAX_RETRIES cannot be parsed as u16");
+                // Retries are counted in a SMALLINT column.
+                assert!(
+                    i16::try_from(max_retries).is_ok(),
+                    "ISSUE_DELIVERY_MAX_RETRIES cannot be above {}",
+                    i16::MAX
+                );
+                max_retries
+            }),
+            retry_base_delay_seconds: var("ISSUE_DELIVERY_RETRY_BASE_DELAY_SECONDS").map_or(
+                60,
+                |v| {
+                    v.parse::<u64>()
+                        .expect("ISSUE_DELIVERY_RETRY_BASE_DELAY_SECONDS cannot be parsed as u64")
+                },
+            ),
+        },
+        subscriptions: SubscriptionSettings {
+            confirmation_token_ttl_hours: var("SUBSCRIPTION_CONFIRMATION_TOKEN_TTL_HOURS").map_or(
+                48,
+                |v| {
+                    v.parse::<u32>()
+                        .expect("SUBSCRIPTION_CONFIRMATION_TOKEN_TTL_HOURS cannot be parsed as u32")
+                },
+            ),
+            max_confirmation_emails_per_day: var("SUBSCRIPTION_MAX_CONFIRMATION_EMAILS_PER_DAY")
+                .map_or(3, |v| {
+                    v.parse::<u32>().expect(
+                        "SUBSCRIPTION_MAX_CONFIRMATION_EMAILS_PER_DAY cannot be parsed as u32",
+                    )
+                }),
+            blocked_email_domains: EmailDomainBlocklist::parse_list(
+                &var("SUBSCRIPTION_BLOCKED_EMAIL_DOMAINS")
+                    .unwrap_or_else(|_| DISPOSABLE_EMAIL_DOMAINS.to_string()),
+            ),
+            confirmation_templates_directory: var("SUBSCRIPTION_CONFIRMATION_TEMPLATES_DIRECTORY")
+                .ok()
+                .map(PathBuf::from),
+            confirmation_redirect_url: var("SUBSCRIPTION_CONFIRMATION_REDIRECT_URL").ok(),
+        },
+        rate_limit: RateLimitSettings {
+            key_prefix: var("RATE_LIMIT_KEY_PREFIX").unwrap_or_else(|_| "rate_limit".to_string()),
+            trust_forwarded_for: var("RATE_LIMIT_TRUST_FORWARDED_FOR").is_ok_and(|v| {
+                v.parse::<bool>()
+                    .expect("RATE_LIMIT_TRUST_FORWARDED_FOR cannot be parsed as bool")
+            }),
+            subscriptions_per_ip_per_hour: var("RATE_LIMIT_SUBSCRIPTIONS_PER_IP_PER_HOUR").map_or(
+                20,
+                |v| {
+                    v.parse::<u32>()
+                        .expect("RATE_LIMIT_SUBSCRIPTIONS_PER_IP_PER_HOUR cannot be parsed as u32")
+                },
+            ),
+            subscriptions_per_email_per_hour: var("RATE_LIMIT_SUBSCRIPTIONS_PER_EMAIL_PER_HOUR")
+                .map_or(5, |v| {
+                    v.parse::<u32>().expect(
+                        "RATE_LIMIT_SUBSCRIPTIONS_PER_EMAIL_PER_HOUR cannot be parsed as u32",
+                    )
+                }),
+            password_resets_per_ip_per_hour: var("RATE_LIMIT_PASSWORD_RESETS_PER_IP_PER_HOUR")
+                .map_or(20, |v| {
+                    v.parse::<u32>().expect(
+                        "RATE_LIMIT_PASSWORD_RESETS_PER_IP_PER_HOUR cannot be parsed as u32",
+                    )
+                }),
+            password_resets_per_email_per_hour: var(
+                "RATE_LIMIT_PASSWORD_RESETS_PER_EMAIL_PER_HOUR",
+            )
+            .map_or(3, |v| {
+                v.parse::<u32>()
+                    .expect("RATE_LIMIT_PASSWORD_RESETS_PER_EMAIL_PER_HOUR cannot be parsed as u32")
+            }),
+            login_failures_before_delay: var("RATE_LIMIT_LOGIN_FAILURES_BEFORE_DELAY").map_or(
+                3,
+                |v| {
+                    v.parse::<u32>()
+                        .expect("RATE_LIMIT_LOGIN_FAILURES_BEFORE_DELAY cannot be parsed as u32")
+                },
+            ),
+            login_failures_per_username: var("RATE_LIMIT_LOGIN_FAILURES_PER_USERNAME").map_or(
+                10,
+                |v| {
+                    v.parse::<u32>()
+                        .expect("RATE_LIMIT_LOGIN_FAILURES_PER_USERNAME cannot be parsed as u32")
+                },
+            ),
+            login_failures_per_ip: var("RATE_LIMIT_LOGIN_FAILURES_PER_IP").map_or(100, |v| {
+                v.parse::<u32>()
+                    .expect("RATE_LIMIT_LOGIN_FAILURES_PER_IP cannot be parsed as u32")
+            }),
+            login_lockout_minutes: var("RATE_LIMIT_LOGIN_LOCKOUT_MINUTES").map_or(15, |v| {
+                v.parse::<u64>()
+                    .expect("RATE_LIMIT_LOGIN_LOCKOUT_MINUTES cannot be parsed as u64")
+            }),
         },
     })
 }
+
+fn get_email_transport_settings() -> EmailTransportSettings {
+    match var("EMAIL_CLIENT_TRANSPORT")
+        .unwrap_or_else(|_| "http".to_string())
+        .as_str()
+    {
+        "http" => EmailTransportSettings::Http {
+            authorization_token: Secret::new(
+                var("EMAIL_CLIENT_AUTHORIZATION_TOKEN")
+                    .expect("EMAIL_CLIENT_AUTHORIZATION_TOKEN missing"),
+            ),
+            base_url: var("EMAIL_CLIENT_BASE_URL").expect("EMAIL_CLIENT_BASE_URL missing"),
+        },
+        "smtp" => EmailTransportSettings::Smtp {
+            host: var("EMAIL_CLIENT_SMTP_HOST").expect("EMAIL_CLIENT_SMTP_HOST missing"),
+            port: var("EMAIL_CLIENT_SMTP_PORT").map_or(587, |v| {
+                v.parse::<u16>()
+                    .expect("EMAIL_CLIENT_SMTP_PORT cannot be parsed as u16")
+            }),
+            tls: var("EMAIL_CLIENT_SMTP_TLS").map_or(SmtpTls::StartTls, |v| {
+                SmtpTls::try_from(v).expect("EMAIL_CLIENT_SMTP_TLS is invalid")
+            }),
+            username: var("EMAIL_CLIENT_SMTP_USERNAME").ok(),
+            password: var("EMAIL_CLIENT_SMTP_PASSWORD").ok().map(Secret::new),
+        },
+        "file" => EmailTransportSettings::File {
+            directory: var("EMAIL_CLIENT_FILE_DIRECTORY")
+                .expect("EMAIL_CLIENT_FILE_DIRECTORY missing")
+                .into(),
+        },
+        other => panic!(
+            "EMAIL_CLIENT_TRANSPORT {} is not supported. Use either `http`, `smtp` or `file`.",
+            other
+        ),
+    }
+}
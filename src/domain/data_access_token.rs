@@ -0,0 +1,62 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+/// Lets whoever holds it read or erase a subscriber's data, hence longer than
+/// a `SubscriptionToken`.
+#[derive(Debug)]
+pub struct DataAccessToken(String);
+
+const TOKEN_SIZE: usize = 40;
+
+impl DataAccessToken {
+    pub fn parse(s: String) -> Result<DataAccessToken, String> {
+        if s.len() != TOKEN_SIZE || !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("This link is not valid.".to_string());
+        }
+        Ok(DataAccessToken(s))
+    }
+
+    pub fn generate() -> DataAccessToken {
+        let mut rng = thread_rng();
+
+        DataAccessToken(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(char::from)
+                .take(TOKEN_SIZE)
+                .collect(),
+        )
+    }
+}
+
+impl AsRef<str> for DataAccessToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::{DataAccessToken, TOKEN_SIZE};
+
+    #[test]
+    fn generated_tokens_parse() {
+        let token = DataAccessToken::generate();
+        assert_eq!(token.as_ref().len(), TOKEN_SIZE);
+        assert_ok!(DataAccessToken::parse(token.as_ref().to_string()));
+    }
+
+    #[test]
+    fn parsing_fails_on_the_wrong_length() {
+        assert_err!(DataAccessToken::parse("a".repeat(TOKEN_SIZE - 1)));
+        assert_err!(DataAccessToken::parse("a".repeat(TOKEN_SIZE + 1)));
+    }
+
+    #[test]
+    fn parsing_fails_on_non_alphanumeric_characters() {
+        assert_err!(DataAccessToken::parse(format!(
+            "{}!",
+            "a".repeat(TOKEN_SIZE - 1)
+        )));
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+
+use super::SubscriberEmail;
+
+/// Email domains we refuse to subscribe, usually disposable inbox providers.
+/// Subdomains of a blocked domain are blocked as well.
+#[derive(Clone, Debug, Default)]
+pub struct EmailDomainBlocklist(HashSet<String>);
+
+impl EmailDomainBlocklist {
+    pub fn new<I, S>(domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self(
+            domains
+                .into_iter()
+                .map(|d| d.as_ref().trim().trim_start_matches('.').to_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Parses a comma separated list of domains.
+    pub fn parse_list(s: &str) -> Self {
+        Self::new(s.split(','))
+    }
+
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        let domain = email
+            .as_ref()
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_lowercase())
+            .unwrap_or_default();
+        let mut candidate = domain.as_str();
+        loop {
+            if self.0.contains(candidate) {
+                return Err(format!("Email addresses at {} are not accepted", domain));
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailDomainBlocklist;
+    use crate::domain::SubscriberEmail;
+    use claim::{assert_err, assert_ok};
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn blocked_domain_is_rejected() {
+        let blocklist = EmailDomainBlocklist::parse_list("mailinator.com, yopmail.com");
+        assert_err!(blocklist.check(&email("bot@mailinator.com")));
+        assert_err!(blocklist.check(&email("bot@yopmail.com")));
+    }
+
+    #[test]
+    fn domains_are_matched_case_insensitively() {
+        let blocklist = EmailDomainBlocklist::parse_list("Mailinator.com");
+        assert_err!(blocklist.check(&email("bot@MAILINATOR.com")));
+    }
+
+    #[test]
+    fn subdomains_of_a_blocked_domain_are_rejected() {
+        let blocklist = EmailDomainBlocklist::parse_list("mailinator.com");
+        assert_err!(blocklist.check(&email("bot@eu.mailinator.com")));
+    }
+
+    #[test]
+    fn other_domains_are_accepted() {
+        let blocklist = EmailDomainBlocklist::parse_list("mailinator.com");
+        assert_ok!(blocklist.check(&email("ursula@gmail.com")));
+        assert_ok!(blocklist.check(&email("ursula@notmailinator.com")));
+    }
+
+    #[test]
+    fn empty_list_accepts_everything() {
+        let blocklist = EmailDomainBlocklist::parse_list("");
+        assert_ok!(blocklist.check(&email("bot@mailinator.com")));
+    }
+}
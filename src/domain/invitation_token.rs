@@ -0,0 +1,66 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+/// Lets whoever holds it create an admin account, hence longer than a
+/// `SubscriptionToken`.
+#[derive(Debug)]
+pub struct InvitationToken(String);
+
+const TOKEN_SIZE: usize = 40;
+
+impl InvitationToken {
+    pub fn parse(s: String) -> Result<InvitationToken, String> {
+        if s.len() != TOKEN_SIZE || !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("This invitation link is not valid.".to_string());
+        }
+        Ok(InvitationToken(s))
+    }
+
+    pub fn generate() -> InvitationToken {
+        let mut rng = thread_rng();
+
+        InvitationToken(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(char::from)
+                .take(TOKEN_SIZE)
+                .collect(),
+        )
+    }
+}
+
+impl AsRef<str> for InvitationToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::{InvitationToken, TOKEN_SIZE};
+
+    #[test]
+    fn generated_tokens_parse() {
+        let token = InvitationToken::generate();
+        assert_eq!(token.as_ref().len(), TOKEN_SIZE);
+        assert_ok!(InvitationToken::parse(token.as_ref().to_string()));
+    }
+
+    #[test]
+    fn parsing_fails_on_the_wrong_length() {
+        assert_err!(InvitationToken::parse("a".repeat(TOKEN_SIZE - 1)));
+        assert_err!(InvitationToken::parse("a".repeat(TOKEN_SIZE + 1)));
+    }
+
+    #[test]
+    fn parsing_fails_on_non_alphanumeric_characters() {
+        assert_err!(InvitationToken::parse(format!(
+            "{}!",
+            "a".repeat(TOKEN_SIZE - 1)
+        )));
+        assert_err!(InvitationToken::parse(format!(
+            "{}é",
+            "a".repeat(TOKEN_SIZE - 2)
+        )));
+    }
+}
@@ -0,0 +1,72 @@
+/// The URL-safe identifier of a mailing list, as used by subscription forms.
+#[derive(Debug)]
+pub struct ListSlug(String);
+
+const MAX_LENGTH: usize = 64;
+
+impl ListSlug {
+    /// The list every subscriber belonged to before lists were introduced.
+    pub const DEFAULT: &'static str = "newsletter";
+
+    pub fn parse(s: String) -> Result<ListSlug, String> {
+        if s.is_empty() {
+            return Err("List cannot be empty".to_string());
+        }
+
+        if s.len() > MAX_LENGTH {
+            return Err(format!(
+                "List cannot be longer than {} characters",
+                MAX_LENGTH
+            ));
+        }
+
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err("List can only contain lowercase letters, digits and dashes".to_string());
+        }
+
+        Ok(ListSlug(s))
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::ListSlug;
+
+    #[test]
+    fn the_default_list_is_valid() {
+        assert_ok!(ListSlug::parse(ListSlug::DEFAULT.to_string()));
+    }
+
+    #[test]
+    fn lowercase_letters_digits_and_dashes_are_valid() {
+        assert_ok!(ListSlug::parse("rust-weekly-2022".to_string()));
+    }
+
+    #[test]
+    fn empty_slug_is_rejected() {
+        assert_err!(ListSlug::parse("".to_string()));
+    }
+
+    #[test]
+    fn too_long_slug_is_rejected() {
+        assert_err!(ListSlug::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn other_characters_are_rejected() {
+        for slug in ["Weekly", "rust weekly", "rust_weekly", "ünïcode"] {
+            assert_err!(ListSlug::parse(slug.to_string()));
+        }
+    }
+}
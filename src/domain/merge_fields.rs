@@ -0,0 +1,151 @@
+use crate::utils::escape_html;
+
+/// Per-recipient values substituted for `{{ name }}`, `{{ email }}` and
+/// `{{ unsubscribe_url }}` in newsletter issue content.
+pub struct MergeFields<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub unsubscribe_url: &'a str,
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Field { name: &'a str, raw: &'a str },
+}
+
+impl<'a> MergeFields<'a> {
+    /// Checks that `template` only uses known merge fields.
+    pub fn validate(template: &str) -> Result<(), String> {
+        for segment in segments(template)? {
+            if let Segment::Field { name, .. } = segment {
+                if !Self::is_known(name) {
+                    return Err(format!("{{{{ {} }}}} is not a known merge field", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn render_text(&self, template: &str) -> String {
+        self.render(template, |value| value.to_string())
+    }
+
+    /// Like [`render_text`](Self::render_text), but escapes the substituted values.
+    pub fn render_html(&self, template: &str) -> String {
+        self.render(template, escape_html)
+    }
+
+    fn is_known(name: &str) -> bool {
+        matches!(name, "name" | "email" | "unsubscribe_url")
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        match name {
+            "name" => Some(self.name),
+            "email" => Some(self.email),
+            "unsubscribe_url" => Some(self.unsubscribe_url),
+            _ => None,
+        }
+    }
+
+    // Content is validated when it is published, anything that still does
+    // not parse is sent as it was written.
+    fn render<F>(&self, template: &str, encode: F) -> String
+    where
+        F: Fn(&str) -> String,
+    {
+        let segments = match segments(template) {
+            Ok(segments) => segments,
+            Err(_) => return template.to_string(),
+        };
+        segments
+            .into_iter()
+            .fold(String::with_capacity(template.len()), |mut a, s| {
+                match s {
+                    Segment::Text(text) => a.push_str(text),
+                    Segment::Field { name, raw } => match self.value(name) {
+                        Some(value) => a.push_str(&encode(value)),
+                        None => a.push_str(raw),
+                    },
+                }
+                a
+            })
+    }
+}
+
+fn segments(template: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        segments.push(Segment::Text(&rest[..start]));
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end + 2)
+            .ok_or_else(|| "A {{ merge field is never closed".to_string())?;
+        let raw = &rest[start..end];
+        segments.push(Segment::Field {
+            name: raw[2..raw.len() - 2].trim(),
+            raw,
+        });
+        rest = &rest[end..];
+    }
+    segments.push(Segment::Text(rest));
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::MergeFields;
+
+    fn fields() -> MergeFields<'static> {
+        MergeFields {
+            name: "Ursula <Le Guin>",
+            email: "ursula@example.com",
+            unsubscribe_url: "https://example.com/unsubscribe",
+        }
+    }
+
+    #[test]
+    fn known_merge_fields_are_valid() {
+        assert_ok!(MergeFields::validate(
+            "Hi {{ name }} ({{email}}), {{  unsubscribe_url }}"
+        ));
+        assert_ok!(MergeFields::validate("No merge fields at all"));
+    }
+
+    #[test]
+    fn unknown_merge_fields_are_rejected() {
+        assert_err!(MergeFields::validate("Hi {{ first_name }}"));
+    }
+
+    #[test]
+    fn unclosed_merge_fields_are_rejected() {
+        assert_err!(MergeFields::validate("Hi {{ name"));
+    }
+
+    #[test]
+    fn text_rendering_substitutes_values() {
+        assert_eq!(
+            fields().render_text("Hi {{ name }}, you are {{email}}."),
+            "Hi Ursula <Le Guin>, you are ursula@example.com."
+        );
+    }
+
+    #[test]
+    fn html_rendering_escapes_values() {
+        assert_eq!(
+            fields().render_html("<p>Hi {{ name }}</p>"),
+            "<p>Hi Ursula &lt;Le Guin&gt;</p>"
+        );
+    }
+
+    #[test]
+    fn unknown_merge_fields_are_left_untouched() {
+        assert_eq!(
+            fields().render_text("Hi {{ nickname }}"),
+            "Hi {{ nickname }}"
+        );
+    }
+}
@@ -2,8 +2,28 @@ mod subscriber_name;
 mod subscriber_email;
 mod new_subscriber;
 mod subscriber_token;
+mod invitation_token;
+mod unsubscribe_token;
+mod password_reset_token;
+mod merge_fields;
+mod list_slug;
+mod subscriber_attributes;
+mod segment;
+mod email_domain_blocklist;
+mod data_access_token;
+mod username;
 
 pub use subscriber_name::SubscriberName;
 pub use subscriber_email::SubscriberEmail;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_token::SubscriptionToken;
+pub use invitation_token::InvitationToken;
+pub use unsubscribe_token::UnsubscribeToken;
+pub use password_reset_token::PasswordResetToken;
+pub use merge_fields::MergeFields;
+pub use list_slug::ListSlug;
+pub use subscriber_attributes::{SubscriberLocale, SubscriberTag};
+pub use segment::Segment;
+pub use email_domain_blocklist::EmailDomainBlocklist;
+pub use data_access_token::DataAccessToken;
+pub use username::Username;
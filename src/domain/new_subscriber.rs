@@ -1,7 +1,10 @@
-use super::{SubscriberEmail, SubscriberName};
+use super::{ListSlug, SubscriberEmail, SubscriberLocale, SubscriberName, SubscriberTag};
 
 #[derive(Debug)]
 pub struct NewSubscriber {
     pub name: SubscriberName,
     pub email: SubscriberEmail,
+    pub list: ListSlug,
+    pub locale: Option<SubscriberLocale>,
+    pub tags: Vec<SubscriberTag>,
 }
@@ -0,0 +1,124 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Signs the user, the expiry and the current password hash: no need to store
+/// it, and it stops working as soon as the password changes.
+#[derive(Debug)]
+pub struct PasswordResetToken(String);
+
+const TOKEN_SIZE: usize = 64;
+
+impl PasswordResetToken {
+    pub fn parse(s: String) -> Result<PasswordResetToken, String> {
+        if s.len() != TOKEN_SIZE {
+            return Err(format!("String token must be of {} length", TOKEN_SIZE));
+        }
+
+        if s.chars().any(|c| !c.is_ascii_hexdigit()) {
+            return Err("Token contains forbidden characters".to_string());
+        }
+
+        Ok(PasswordResetToken(s))
+    }
+
+    pub fn generate(
+        user_id: &Uuid,
+        expires_at: i64,
+        password_hash: &Secret<String>,
+        hmac_secret: &Secret<String>,
+    ) -> PasswordResetToken {
+        let tag = signer(user_id, expires_at, password_hash, hmac_secret)
+            .finalize()
+            .into_bytes();
+
+        PasswordResetToken(hex::encode(tag))
+    }
+
+    pub fn verify(
+        &self,
+        user_id: &Uuid,
+        expires_at: i64,
+        password_hash: &Secret<String>,
+        hmac_secret: &Secret<String>,
+    ) -> bool {
+        match hex::decode(&self.0) {
+            Ok(tag) => signer(user_id, expires_at, password_hash, hmac_secret)
+                .verify_slice(&tag)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+fn signer(
+    user_id: &Uuid,
+    expires_at: i64,
+    password_hash: &Secret<String>,
+    hmac_secret: &Secret<String>,
+) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(b"password-reset:");
+    mac.update(user_id.as_bytes());
+    mac.update(&expires_at.to_be_bytes());
+    mac.update(password_hash.expose_secret().as_bytes());
+    mac
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::PasswordResetToken;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-very-secret-key".to_string())
+    }
+
+    fn hash(s: &str) -> Secret<String> {
+        Secret::new(s.to_string())
+    }
+
+    #[test]
+    fn generated_token_can_be_parsed() {
+        let token = PasswordResetToken::generate(&Uuid::new_v4(), 42, &hash("a"), &secret());
+        assert_ok!(PasswordResetToken::parse(token.as_ref().to_string()));
+    }
+
+    #[test]
+    fn generated_token_is_verified_with_the_same_inputs() {
+        let user_id = Uuid::new_v4();
+        let token = PasswordResetToken::generate(&user_id, 42, &hash("a"), &secret());
+        assert!(token.verify(&user_id, 42, &hash("a"), &secret()));
+    }
+
+    #[test]
+    fn generated_token_is_rejected_if_anything_changes() {
+        let user_id = Uuid::new_v4();
+        let token = PasswordResetToken::generate(&user_id, 42, &hash("a"), &secret());
+        assert!(!token.verify(&Uuid::new_v4(), 42, &hash("a"), &secret()));
+        assert!(!token.verify(&user_id, 43, &hash("a"), &secret()));
+        assert!(!token.verify(&user_id, 42, &hash("b"), &secret()));
+        assert!(!token.verify(
+            &user_id,
+            42,
+            &hash("a"),
+            &Secret::new("another-key".to_string())
+        ));
+    }
+
+    #[test]
+    fn parsing_token_fails_if_non_hex() {
+        assert_err!(PasswordResetToken::parse("z".repeat(64)));
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use super::{SubscriberLocale, SubscriberTag};
+
+/// Which subscribers of a list an issue is sent to.
+///
+/// Written as whitespace separated `key:value` terms that must all match:
+/// `subscribed_before:2022-10-01 subscribed_after:2022-01-01 tag:vip locale:en`.
+/// `tag` can be repeated, an empty segment matches everybody.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Segment {
+    pub subscribed_before: Option<DateTime<Utc>>,
+    pub subscribed_after: Option<DateTime<Utc>>,
+    pub locale: Option<SubscriberLocale>,
+    pub tags: Vec<SubscriberTag>,
+}
+
+impl Segment {
+    pub fn parse(s: &str) -> Result<Segment, String> {
+        let mut segment = Segment::default();
+        for term in s.split_whitespace() {
+            let (key, value) = term
+                .split_once(':')
+                .ok_or_else(|| format!("{} is not a key:value filter", term))?;
+            match key {
+                "subscribed_before" => {
+                    set_once(&mut segment.subscribed_before, key, parse_date(value)?)?
+                }
+                "subscribed_after" => {
+                    set_once(&mut segment.subscribed_after, key, parse_date(value)?)?
+                }
+                "locale" => set_once(
+                    &mut segment.locale,
+                    key,
+                    SubscriberLocale::parse(value.to_string())?,
+                )?,
+                "tag" => segment.tags.push(SubscriberTag::parse(value.to_string())?),
+                _ => return Err(format!("{} is not a known filter", key)),
+            }
+        }
+        Ok(segment)
+    }
+
+    pub fn is_everyone(&self) -> bool {
+        *self == Segment::default()
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.iter().map(|t| t.as_ref().to_string()).collect()
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, key: &str, value: T) -> Result<(), String> {
+    if slot.replace(value).is_some() {
+        return Err(format!("{} can only be used once", key));
+    }
+    Ok(())
+}
+
+fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| format!("{} is not a YYYY-MM-DD date", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+    use claim::{assert_err, assert_ok};
+
+    use super::Segment;
+
+    fn midnight(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn an_empty_segment_matches_everyone() {
+        assert!(Segment::parse("  ").unwrap().is_everyone());
+    }
+
+    #[test]
+    fn all_filters_are_parsed() {
+        let segment = Segment::parse(
+            "subscribed_before:2022-10-01 subscribed_after:2022-01-01 tag:vip tag:beta locale:en",
+        )
+        .unwrap();
+
+        assert_eq!(segment.subscribed_before, Some(midnight(2022, 10, 1)));
+        assert_eq!(segment.subscribed_after, Some(midnight(2022, 1, 1)));
+        assert_eq!(segment.locale.unwrap().as_ref(), "en");
+        assert_eq!(segment.tags.len(), 2);
+    }
+
+    #[test]
+    fn unknown_filters_are_rejected() {
+        assert_err!(Segment::parse("country:it"));
+        assert_err!(Segment::parse("vip"));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert_err!(Segment::parse("subscribed_before:yesterday"));
+        assert_err!(Segment::parse("locale:English"));
+        assert_err!(Segment::parse("tag:'; DROP TABLE subscriptions; --"));
+    }
+
+    #[test]
+    fn single_valued_filters_cannot_be_repeated() {
+        assert_err!(Segment::parse("locale:en locale:it"));
+        assert_ok!(Segment::parse("tag:a tag:b"));
+    }
+}
@@ -0,0 +1,112 @@
+/// A language tag such as `en` or `pt-BR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriberLocale(String);
+
+impl SubscriberLocale {
+    pub fn parse(s: String) -> Result<SubscriberLocale, String> {
+        let (language, region) = match s.split_once('-') {
+            Some((language, region)) => (language, Some(region)),
+            None => (s.as_str(), None),
+        };
+        let valid_language =
+            (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_lowercase());
+        let valid_region = region
+            .map(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_uppercase()))
+            .unwrap_or(true);
+
+        if valid_language && valid_region {
+            Ok(SubscriberLocale(s))
+        } else {
+            Err(format!("{} is not a valid locale", s))
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberLocale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A free-form label attached to a subscriber, e.g. where they signed up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriberTag(String);
+
+const MAX_TAG_LENGTH: usize = 64;
+
+impl SubscriberTag {
+    pub fn parse(s: String) -> Result<SubscriberTag, String> {
+        if s.is_empty() || s.len() > MAX_TAG_LENGTH {
+            return Err(format!(
+                "A tag must be between 1 and {} characters long",
+                MAX_TAG_LENGTH
+            ));
+        }
+
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "{} is not a valid tag, use lowercase letters, digits, dashes and underscores",
+                s
+            ));
+        }
+
+        Ok(SubscriberTag(s))
+    }
+
+    /// Parses a comma separated list of tags, ignoring blanks.
+    pub fn parse_list(s: &str) -> Result<Vec<SubscriberTag>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(|t| SubscriberTag::parse(t.to_string()))
+            .collect()
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::{SubscriberLocale, SubscriberTag};
+
+    #[test]
+    fn languages_with_an_optional_region_are_valid_locales() {
+        for locale in ["en", "fil", "pt-BR"] {
+            assert_ok!(SubscriberLocale::parse(locale.to_string()));
+        }
+    }
+
+    #[test]
+    fn malformed_locales_are_rejected() {
+        for locale in ["", "EN", "english", "pt-br", "pt_BR", "pt-"] {
+            assert_err!(SubscriberLocale::parse(locale.to_string()));
+        }
+    }
+
+    #[test]
+    fn tags_are_parsed_from_a_comma_separated_list() {
+        let tags = SubscriberTag::parse_list(" vip, early_adopter ,,").unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                SubscriberTag::parse("vip".into()).unwrap(),
+                SubscriberTag::parse("early_adopter".into()).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn tags_with_forbidden_characters_are_rejected() {
+        assert_err!(SubscriberTag::parse_list("vip,Big Spender"));
+        assert_err!(SubscriberTag::parse("a".repeat(65)));
+    }
+}
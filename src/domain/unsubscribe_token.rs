@@ -0,0 +1,139 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct UnsubscribeToken(String);
+
+const TOKEN_SIZE: usize = 64;
+
+impl UnsubscribeToken {
+    pub fn parse(s: String) -> Result<UnsubscribeToken, String> {
+        if s.trim().is_empty() {
+            return Err("Token cannot be empty".to_string());
+        }
+
+        if s.len() != TOKEN_SIZE {
+            return Err(format!("String token must be of {} length", TOKEN_SIZE));
+        }
+
+        if s.chars().any(|c| !c.is_ascii_hexdigit()) {
+            return Err("Token contains forbidden characters".to_string());
+        }
+
+        Ok(UnsubscribeToken(s))
+    }
+
+    /// Without a `list_id`, the token stands for every list of the subscriber.
+    pub fn generate(
+        subscriber_id: &Uuid,
+        list_id: Option<&Uuid>,
+        hmac_secret: &Secret<String>,
+    ) -> UnsubscribeToken {
+        let tag = signer(subscriber_id, list_id, hmac_secret)
+            .finalize()
+            .into_bytes();
+
+        UnsubscribeToken(hex::encode(tag))
+    }
+
+    pub fn verify(
+        &self,
+        subscriber_id: &Uuid,
+        list_id: Option<&Uuid>,
+        hmac_secret: &Secret<String>,
+    ) -> bool {
+        match hex::decode(&self.0) {
+            Ok(tag) => signer(subscriber_id, list_id, hmac_secret)
+                .verify_slice(&tag)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+fn signer(
+    subscriber_id: &Uuid,
+    list_id: Option<&Uuid>,
+    hmac_secret: &Secret<String>,
+) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(b"unsubscribe:");
+    mac.update(subscriber_id.as_bytes());
+    if let Some(list_id) = list_id {
+        mac.update(b":list:");
+        mac.update(list_id.as_bytes());
+    }
+    mac
+}
+
+impl AsRef<str> for UnsubscribeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::UnsubscribeToken;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-very-secret-key".to_string())
+    }
+
+    #[test]
+    fn generated_token_can_be_parsed() {
+        let token = UnsubscribeToken::generate(&Uuid::new_v4(), None, &secret());
+        assert_ok!(UnsubscribeToken::parse(token.as_ref().to_string()));
+    }
+
+    #[test]
+    fn generated_token_is_verified_for_the_same_subscriber() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(&subscriber_id, None, &secret());
+        assert!(token.verify(&subscriber_id, None, &secret()));
+    }
+
+    #[test]
+    fn generated_token_is_rejected_for_another_subscriber() {
+        let token = UnsubscribeToken::generate(&Uuid::new_v4(), None, &secret());
+        assert!(!token.verify(&Uuid::new_v4(), None, &secret()));
+    }
+
+    #[test]
+    fn generated_token_is_rejected_with_another_secret() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(&subscriber_id, None, &secret());
+        assert!(!token.verify(
+            &subscriber_id,
+            None,
+            &Secret::new("another-key".to_string())
+        ));
+    }
+
+    #[test]
+    fn generated_token_is_only_verified_for_its_list() {
+        let subscriber_id = Uuid::new_v4();
+        let list_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(&subscriber_id, Some(&list_id), &secret());
+        assert!(token.verify(&subscriber_id, Some(&list_id), &secret()));
+        assert!(!token.verify(&subscriber_id, Some(&Uuid::new_v4()), &secret()));
+        assert!(!token.verify(&subscriber_id, None, &secret()));
+    }
+
+    #[test]
+    fn parsing_token_fails_if_non_hex() {
+        assert_err!(UnsubscribeToken::parse("z".repeat(64)));
+    }
+
+    #[test]
+    fn parsing_token_fails_if_too_short() {
+        assert_err!(UnsubscribeToken::parse("abcdef".to_string()));
+    }
+}
@@ -0,0 +1,82 @@
+/// The name an admin user logs in with, shown on admin pages.
+#[derive(Debug)]
+pub struct Username(String);
+
+const MAX_LENGTH: usize = 64;
+
+impl Username {
+    pub fn parse(s: String) -> Result<Username, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("A username cannot be empty.".to_string());
+        }
+
+        if s.len() > MAX_LENGTH {
+            return Err(format!(
+                "A username cannot be longer than {} characters.",
+                MAX_LENGTH
+            ));
+        }
+
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ['.', '_', '-', '@', '+'].contains(&c))
+        {
+            return Err(
+                "A username can only contain letters, digits and the characters . _ - @ +"
+                    .to_string(),
+            );
+        }
+
+        Ok(Username(s.to_string()))
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::Username;
+
+    #[test]
+    fn names_and_email_addresses_are_valid() {
+        for username in ["ursula", "le_guin-1929", "ursula.le+guin@gmail.com"] {
+            assert_ok!(Username::parse(username.to_string()));
+        }
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        let username = Username::parse("  ursula ".to_string()).unwrap();
+        assert_eq!(username.as_ref(), "ursula");
+    }
+
+    #[test]
+    fn empty_username_is_rejected() {
+        assert_err!(Username::parse(" ".to_string()));
+    }
+
+    #[test]
+    fn too_long_username_is_rejected() {
+        assert_ok!(Username::parse("a".repeat(64)));
+        assert_err!(Username::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn markup_and_other_characters_are_rejected() {
+        for username in [
+            "<script>alert(1)</script>",
+            "ursula le guin",
+            "\"ursula\"",
+            "ürsula",
+        ] {
+            assert_err!(Username::parse(username.to_string()));
+        }
+    }
+}
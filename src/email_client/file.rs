@@ -0,0 +1,81 @@
+use anyhow::Context;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+
+use super::{mime::to_mime, Email, EmailError, EmailTransport, SentEmail};
+
+/// Writes every email as an `.eml` file in a directory, for development.
+#[derive(Debug)]
+pub struct FileTransport {
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileTransport {
+    pub fn new(directory: PathBuf) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(&directory)
+            .with_context(|| format!("Failed to create {}.", directory.display()))?;
+
+        Ok(Self {
+            transport: AsyncFileTransport::new(directory),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, email: Email<'_>) -> Result<SentEmail, EmailError> {
+        let mime = to_mime(&email)?;
+
+        self.transport
+            .send_raw(&mime.envelope, &mime.formatted)
+            .await
+            .context("Failed to write the email to disk.")?;
+
+        Ok(SentEmail {
+            status_code: None,
+            message_id: Some(mime.message_id),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::domain::SubscriberEmail;
+
+    #[tokio::test]
+    async fn send_writes_an_eml_file_in_the_directory() {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let transport = FileTransport::new(directory.clone()).unwrap();
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+        let headers = HashMap::new();
+
+        transport
+            .send(Email {
+                sender: &sender,
+                recipient: &recipient,
+                recipient_name: Some("Ursula"),
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter body as HTML.</p>",
+                text_content: "Newsletter body as plain text.",
+                headers: &headers,
+            })
+            .await
+            .unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("Subject: Newsletter title"));
+        assert!(content.contains("To: Ursula <recipient@example.com>"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
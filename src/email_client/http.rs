@@ -0,0 +1,372 @@
+use anyhow::Context;
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+
+use super::{Email, EmailError, EmailTransport, SentEmail};
+
+/// Sends emails through the provider's HTTP API.
+#[derive(Debug)]
+pub struct HttpApiTransport {
+    http_client: Client,
+    base_url: String,
+    authorization_token: Secret<String>,
+}
+
+impl HttpApiTransport {
+    pub fn new(
+        base_url: String,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+    ) -> Self {
+        Self {
+            http_client: Client::builder().timeout(timeout).build().unwrap(),
+            base_url,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for HttpApiTransport {
+    async fn send(&self, email: Email<'_>) -> Result<SentEmail, EmailError> {
+        let url = format!("{}/email", self.base_url);
+
+        let request_body = SendEmailMessage {
+            to: [SendEmailAddress {
+                email: email.recipient.as_ref(),
+                name: email
+                    .recipient_name
+                    .unwrap_or_else(|| email.recipient.as_ref()),
+            }],
+            sender: SendEmailAddress {
+                email: email.sender.as_ref(),
+                name: email.sender.as_ref(),
+            },
+            subject: email.subject,
+            html_content: email.html_content,
+            text_content: email.text_content,
+            headers: email.headers,
+        };
+
+        let response = self
+            .http_client
+            .post(&url)
+            .header("api-key", self.authorization_token.expose_secret())
+            .header("accept", "application/json")
+            .header("content-type", "application/json")
+            .json(&request_body)
+            .send()
+            .await
+            .context("Failed to reach the email API.")?;
+
+        let status_code = response.status().as_u16();
+        if let Err(e) = response.error_for_status_ref() {
+            // Client errors other than timeouts and rate limits won't go away.
+            return match status_code {
+                408 | 429 => Err(EmailError::Rejected(status_code, e.into())),
+                400..=499 => Err(EmailError::PermanentlyRejected(status_code, e.into())),
+                _ => Err(EmailError::Rejected(status_code, e.into())),
+            };
+        }
+
+        // The provider's response body is informative only, a send is not failed over it.
+        let message_id = response
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .and_then(|r| r.message_id);
+
+        Ok(SentEmail {
+            status_code: Some(status_code),
+            message_id,
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendEmailResponse {
+    message_id: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct SendEmailAddress<'a> {
+    email: &'a str,
+    name: &'a str,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendEmailMessage<'a> {
+    sender: SendEmailAddress<'a>,
+    to: [SendEmailAddress<'a>; 1],
+    subject: &'a str,
+    html_content: &'a str,
+    text_content: &'a str,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: &'a HashMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_err;
+    use fake::{
+        faker::{
+            internet::en::SafeEmail,
+            lorem::en::{Paragraph, Sentence},
+        },
+        Fake, Faker,
+    };
+    use wiremock::{
+        matchers::{any, body_partial_json, header, method, path},
+        Mock, MockServer, Request, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{domain::SubscriberEmail, email_client::EmailClient};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("sender").is_some()
+                    && body.get("to").is_some()
+                    && body.get("subject").is_some()
+                    && body.get("htmlContent").is_some()
+                    && body.get("textContent").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = fake_email_client(mock_server.uri());
+
+        Mock::given(method("POST"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1) // Then
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let _ = email_client
+            .send_email(
+                &fake_email(),
+                &fake_subject(),
+                &fake_content(),
+                &fake_content(),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_sends_the_headers_in_the_body() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = fake_email_client(mock_server.uri());
+        let mut headers = HashMap::new();
+        headers.insert("X-Custom".to_string(), "value".to_string());
+
+        Mock::given(method("POST"))
+            .and(path("/email"))
+            .and(SendEmailBodyMatcher)
+            .and(body_partial_json(
+                serde_json::json!({ "headers": { "X-Custom": "value" } }),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1) // Then
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let _ = email_client
+            .send_email_with_headers(
+                &fake_email(),
+                None,
+                &fake_subject(),
+                &fake_content(),
+                &fake_content(),
+                &headers,
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_returns_the_provider_message_id() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = fake_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_json(serde_json::json!({ "messageId": "<message@id>" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let sent = email_client
+            .send_email_with_headers(
+                &fake_email(),
+                None,
+                &fake_subject(),
+                &fake_content(),
+                &fake_content(),
+                &HashMap::new(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(sent.status_code, Some(201));
+        assert_eq!(sent.message_id.as_deref(), Some("<message@id>"));
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = fake_email_client(mock_server.uri());
+        let response = ResponseTemplate::new(200)
+            // 3 minutes
+            .set_delay(std::time::Duration::from_secs(180));
+
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1) // When
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                &fake_email(),
+                &fake_subject(),
+                &fake_content(),
+                &fake_content(),
+            )
+            .await;
+
+        // Then
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = fake_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1) // When
+            .mount(&mock_server)
+            .await;
+        let outcome = email_client
+            .send_email(
+                &fake_email(),
+                &fake_subject(),
+                &fake_content(),
+                &fake_content(),
+            )
+            .await;
+
+        // Then
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn only_client_errors_other_than_timeouts_and_rate_limits_are_permanent() {
+        for (status_code, permanent) in [
+            (400, true),
+            (422, true),
+            (408, false),
+            (429, false),
+            (503, false),
+        ] {
+            // Given
+            let mock_server = MockServer::start().await;
+            let email_client = fake_email_client(mock_server.uri());
+            Mock::given(any())
+                .respond_with(ResponseTemplate::new(status_code))
+                .mount(&mock_server)
+                .await;
+
+            // When
+            let e = email_client
+                .send_email(
+                    &fake_email(),
+                    &fake_subject(),
+                    &fake_content(),
+                    &fake_content(),
+                )
+                .await
+                .unwrap_err();
+
+            // Then
+            assert_eq!(e.status_code(), Some(status_code));
+            assert_eq!(e.is_permanent(), permanent, "status code {}", status_code);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_uses_the_recipient_name() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = fake_email_client(mock_server.uri());
+
+        Mock::given(body_partial_json(serde_json::json!({
+            "to": [{ "name": "Ursula" }]
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1) // Then
+        .mount(&mock_server)
+        .await;
+
+        // When
+        let _ = email_client
+            .send_email_with_headers(
+                &fake_email(),
+                Some("Ursula"),
+                &fake_subject(),
+                &fake_content(),
+                &fake_content(),
+                &HashMap::new(),
+            )
+            .await;
+    }
+
+    fn fake_subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn fake_content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn fake_email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn fake_email_client(base_url: String) -> EmailClient {
+        EmailClient::new(
+            fake_email(),
+            Box::new(HttpApiTransport::new(
+                base_url,
+                Secret::new(Faker.fake()),
+                std::time::Duration::from_millis(200),
+            )),
+        )
+    }
+}
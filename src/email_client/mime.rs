@@ -0,0 +1,65 @@
+use anyhow::Context;
+use lettre::{
+    address::Envelope,
+    message::{
+        header::{HeaderName, HeaderValue, Headers},
+        Mailbox, MultiPart,
+    },
+    Message,
+};
+use uuid::Uuid;
+
+use super::Email;
+
+/// An [`Email`] rendered as a MIME message, ready to be handed to an SMTP
+/// server or written to disk.
+pub(super) struct MimeEmail {
+    pub envelope: Envelope,
+    pub formatted: Vec<u8>,
+    pub message_id: String,
+}
+
+pub(super) fn to_mime(email: &Email<'_>) -> Result<MimeEmail, anyhow::Error> {
+    let sender = email.sender.as_ref();
+    let domain = sender.rsplit('@').next().unwrap_or("localhost");
+    let message_id = format!("<{}@{}>", Uuid::new_v4(), domain);
+
+    let message = Message::builder()
+        .from(
+            sender
+                .parse::<Mailbox>()
+                .context("Invalid sender address.")?,
+        )
+        .to(Mailbox::new(
+            email.recipient_name.map(str::to_string),
+            email
+                .recipient
+                .as_ref()
+                .parse()
+                .context("Invalid recipient address.")?,
+        ))
+        .subject(email.subject)
+        .message_id(Some(message_id.clone()))
+        .multipart(MultiPart::alternative_plain_html(
+            email.text_content.to_string(),
+            email.html_content.to_string(),
+        ))
+        .context("Failed to build the email message.")?;
+
+    // The builder only knows about typed headers, so custom ones are
+    // encoded separately and prepended to the formatted message.
+    let mut headers = Headers::new();
+    for (name, value) in email.headers {
+        let name = HeaderName::new_from_ascii(name.clone())
+            .with_context(|| format!("Invalid email header name: {}", name))?;
+        headers.insert_raw(HeaderValue::new(name, value.clone()));
+    }
+    let mut formatted = headers.to_string().into_bytes();
+    formatted.extend(message.formatted());
+
+    Ok(MimeEmail {
+        envelope: message.envelope().clone(),
+        formatted,
+        message_id,
+    })
+}
@@ -0,0 +1,119 @@
+mod file;
+mod http;
+mod mime;
+mod smtp;
+
+use std::collections::HashMap;
+
+use crate::{domain::SubscriberEmail, routes::error_chain_fmt};
+
+pub use file::FileTransport;
+pub use http::HttpApiTransport;
+pub use smtp::{SmtpTls, SmtpTransport};
+
+/// A single outgoing email, as handed over to an [`EmailTransport`].
+pub struct Email<'a> {
+    pub sender: &'a SubscriberEmail,
+    pub recipient: &'a SubscriberEmail,
+    pub recipient_name: Option<&'a str>,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct SentEmail {
+    pub status_code: Option<u16>,
+    pub message_id: Option<String>,
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailError {
+    #[error("The email was rejected with status code {0}")]
+    Rejected(u16, #[source] anyhow::Error),
+    /// Sending the same email again will fail the same way.
+    #[error("The email was permanently rejected with status code {0}")]
+    PermanentlyRejected(u16, #[source] anyhow::Error),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl EmailError {
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            Self::Rejected(status_code, _) | Self::PermanentlyRejected(status_code, _) => {
+                Some(*status_code)
+            }
+            Self::Unexpected(_) => None,
+        }
+    }
+
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, Self::PermanentlyRejected(..))
+    }
+}
+
+impl std::fmt::Debug for EmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[async_trait::async_trait]
+pub trait EmailTransport: std::fmt::Debug + Send + Sync {
+    async fn send(&self, email: Email<'_>) -> Result<SentEmail, EmailError>;
+}
+
+#[derive(Debug)]
+pub struct EmailClient {
+    pub sender: SubscriberEmail,
+    transport: Box<dyn EmailTransport>,
+}
+
+impl EmailClient {
+    pub fn new(sender: SubscriberEmail, transport: Box<dyn EmailTransport>) -> Self {
+        Self { sender, transport }
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), EmailError> {
+        self.send_email_with_headers(
+            recipient,
+            None,
+            subject,
+            html_content,
+            text_content,
+            &HashMap::new(),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        recipient_name: Option<&str>,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<SentEmail, EmailError> {
+        self.transport
+            .send(Email {
+                sender: &self.sender,
+                recipient,
+                recipient_name,
+                subject,
+                html_content,
+                text_content,
+                headers,
+            })
+            .await
+    }
+}
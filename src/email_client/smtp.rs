@@ -0,0 +1,237 @@
+use anyhow::Context;
+use lettre::{
+    transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport,
+    Tokio1Executor,
+};
+use secrecy::{ExposeSecret, Secret};
+
+use super::{mime::to_mime, Email, EmailError, EmailTransport, SentEmail};
+
+#[derive(Clone, Copy, Debug)]
+pub enum SmtpTls {
+    None,
+    StartTls,
+    Tls,
+}
+
+impl TryFrom<String> for SmtpTls {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            other => Err(format!(
+                "{} is not a supported SMTP TLS mode. Use either `none`, `starttls` or `tls`.",
+                other
+            )),
+        }
+    }
+}
+
+/// Sends emails to an SMTP relay.
+#[derive(Debug)]
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: SmtpTls,
+        credentials: Option<(String, Secret<String>)>,
+        timeout: std::time::Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let builder = match tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .context("Failed to set up a STARTTLS SMTP relay.")?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .context("Failed to set up a TLS SMTP relay.")?,
+        }
+        .port(port)
+        .timeout(Some(timeout));
+
+        let builder = match credentials {
+            Some((username, password)) => builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            )),
+            None => builder,
+        };
+
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: Email<'_>) -> Result<SentEmail, EmailError> {
+        let mime = to_mime(&email)?;
+
+        match self.mailer.send_raw(&mime.envelope, &mime.formatted).await {
+            Ok(response) => Ok(SentEmail {
+                status_code: response.code().to_string().parse().ok(),
+                message_id: Some(mime.message_id),
+            }),
+            Err(e) => match e.status().and_then(|code| code.to_string().parse().ok()) {
+                // 5xx replies, as opposed to 4xx ones which are worth retrying.
+                Some(status_code) if e.is_permanent() => {
+                    Err(EmailError::PermanentlyRejected(status_code, e.into()))
+                }
+                Some(status_code) => Err(EmailError::Rejected(status_code, e.into())),
+                None => Err(EmailError::Unexpected(
+                    anyhow::Error::new(e).context("Failed to talk to the SMTP server."),
+                )),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_err;
+    use std::collections::HashMap;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use super::*;
+    use crate::domain::SubscriberEmail;
+
+    /// A minimal SMTP server, capturing the DATA of a single message.
+    async fn smtp_sink(rcpt_reply: &'static str) -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut data = String::new();
+            let mut in_data = false;
+
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        writer.write_all(b"250 Ok: queued\r\n").await.unwrap();
+                    } else {
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    continue;
+                }
+                let command = line.to_ascii_uppercase();
+                if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else if command.starts_with("RCPT") {
+                    writer.write_all(rcpt_reply.as_bytes()).await.unwrap();
+                } else if command.starts_with("DATA") {
+                    in_data = true;
+                    writer
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .await
+                        .unwrap();
+                } else {
+                    writer.write_all(b"250 Ok\r\n").await.unwrap();
+                }
+            }
+            data
+        });
+
+        (port, handle)
+    }
+
+    fn transport(port: u16) -> SmtpTransport {
+        SmtpTransport::new(
+            "127.0.0.1",
+            port,
+            SmtpTls::None,
+            None,
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap()
+    }
+
+    fn email<'a>(
+        sender: &'a SubscriberEmail,
+        recipient: &'a SubscriberEmail,
+        headers: &'a HashMap<String, String>,
+    ) -> Email<'a> {
+        Email {
+            sender,
+            recipient,
+            recipient_name: None,
+            subject: "Newsletter title",
+            html_content: "<p>Newsletter body as HTML.</p>",
+            text_content: "Newsletter body as plain text.",
+            headers,
+        }
+    }
+
+    #[tokio::test]
+    async fn send_delivers_the_message_to_the_smtp_server() {
+        let (port, sink) = smtp_sink("250 Ok\r\n").await;
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+        let headers = HashMap::from([(
+            "List-Unsubscribe".to_string(),
+            "<https://example.com/unsubscribe>".to_string(),
+        )]);
+
+        let sent = transport(port)
+            .send(email(&sender, &recipient, &headers))
+            .await
+            .unwrap();
+        let data = sink.await.unwrap();
+
+        assert_eq!(sent.status_code, Some(250));
+        assert!(data.contains(&format!("Message-ID: {}", sent.message_id.unwrap())));
+        assert!(data.contains("Subject: Newsletter title"));
+        assert!(data.contains("To: recipient@example.com"));
+        assert!(data.contains("List-Unsubscribe: <https://example.com/unsubscribe>"));
+        assert!(data.contains("Newsletter body as plain text."));
+    }
+
+    #[tokio::test]
+    async fn send_fails_with_the_status_code_if_the_recipient_is_rejected() {
+        let (port, _sink) = smtp_sink("550 No such user\r\n").await;
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+        let headers = HashMap::new();
+
+        let outcome = transport(port)
+            .send(email(&sender, &recipient, &headers))
+            .await;
+
+        assert_err!(&outcome);
+        let e = outcome.unwrap_err();
+        assert_eq!(e.status_code(), Some(550));
+        assert!(e.is_permanent());
+    }
+
+    #[tokio::test]
+    async fn a_temporary_rejection_is_not_permanent() {
+        let (port, _sink) = smtp_sink("451 Try again later\r\n").await;
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+        let headers = HashMap::new();
+
+        let e = transport(port)
+            .send(email(&sender, &recipient, &headers))
+            .await
+            .unwrap_err();
+
+        assert_eq!(e.status_code(), Some(451));
+        assert!(!e.is_permanent());
+    }
+}
@@ -55,8 +55,14 @@ pub async fn try_processing(
         idempotency_key.as_ref()
     )
     .execute(&mut transaction)
-    .await?
-    .rows_affected();
+    .await;
+    // A concurrent request inserted the same key after our snapshot was taken:
+    // it is (or was) being processed, so we fall back to its saved response.
+    let n_inserted_rows = match n_inserted_rows {
+        Ok(result) => result.rows_affected(),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("40001") => 0,
+        Err(e) => return Err(e.into()),
+    };
     if n_inserted_rows > 0 {
         Ok(NextAction::StartProcessing(transaction))
     } else {
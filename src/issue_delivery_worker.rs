@@ -1,10 +1,15 @@
 use anyhow::Context;
-use secrecy::ExposeSecret;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
+    configuration::{IssueDeliverySettings, Settings},
+    domain::{MergeFields, Segment, SubscriberEmail},
+    email_client::EmailClient,
+    routes::unsubscribe_link,
     startup::get_connection_pool,
 };
 
@@ -18,7 +23,19 @@ pub enum ExecutionOutcome {
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let pool = get_connection_pool(configuration.database_url.expose_secret());
     let email_client = configuration.email_client.client();
-    worker_loop(&pool, email_client).await
+    worker_loop(
+        &pool,
+        email_client,
+        &configuration.application.base_url,
+        &configuration.application.hmac_secret,
+        &configuration.issue_delivery,
+    )
+    .await
+}
+
+enum DeliveryFailure {
+    Retryable(String),
+    Permanent(String),
 }
 
 #[tracing::instrument(
@@ -32,10 +49,13 @@ pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), any
 pub async fn deliver_queued_tasks(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+    settings: &IssueDeliverySettings,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     let mut transaction = pool.begin().await?;
 
-    let (newsletter_issue_id, subscriber_email) = match dequeue_task(&mut transaction)
+    let task = match dequeue_task(&mut transaction)
         .await
         .context("Failed to dequeue newsletter issue.")?
     {
@@ -43,46 +63,147 @@ pub async fn deliver_queued_tasks(
         None => return Ok(ExecutionOutcome::EmptyQueue),
     };
 
-    match SubscriberEmail::parse(subscriber_email.clone()) {
-        Ok(email) => {
-            let newsletter_issue = get_issue(pool, &newsletter_issue_id)
-                .await
-                .context("Failed to fetch issue.")?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &newsletter_issue.title,
-                    &newsletter_issue.html_content,
-                    &newsletter_issue.text_content,
-                )
-                .await
-            {
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. Skipping.",
-                );
+    tracing::Span::current()
+        .record(
+            "newsletter_issue_id",
+            tracing::field::display(&task.newsletter_issue_id),
+        )
+        .record(
+            "subscriber_email",
+            tracing::field::display(&task.subscriber_email),
+        );
+
+    let failure = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+        Ok(email) => match get_confirmed_subscriber(pool, &email, &task.newsletter_issue_id)
+            .await
+            .context("Failed to fetch subscriber.")?
+        {
+            Some(subscriber) => {
+                let newsletter_issue = get_issue(pool, &task.newsletter_issue_id)
+                    .await
+                    .context("Failed to fetch issue.")?;
+                let unsubscribe_link =
+                    unsubscribe_link(base_url, &subscriber.id, &subscriber.list_id, hmac_secret);
+                let merge_fields = MergeFields {
+                    name: &subscriber.name,
+                    email: email.as_ref(),
+                    unsubscribe_url: &unsubscribe_link,
+                };
+                match email_client
+                    .send_email_with_headers(
+                        &email,
+                        Some(&subscriber.name),
+                        &merge_fields.render_text(&newsletter_issue.title),
+                        &newsletter_issue.html_content_with_footer(&merge_fields),
+                        &newsletter_issue.text_content_with_footer(&merge_fields),
+                        &list_unsubscribe_headers(&unsubscribe_link),
+                    )
+                    .await
+                {
+                    Ok(sent) => {
+                        record_delivery(
+                            &mut transaction,
+                            &task,
+                            sent.status_code,
+                            sent.message_id.as_deref(),
+                            None,
+                        )
+                        .await
+                        .context("Failed to record delivery attempt.")?;
+                        None
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            n_retries = task.n_retries,
+                            "Failed to deliver issue to a confirmed subscriber.",
+                        );
+                        record_delivery(
+                            &mut transaction,
+                            &task,
+                            e.status_code(),
+                            None,
+                            Some(&e.to_string()),
+                        )
+                        .await
+                        .context("Failed to record delivery attempt.")?;
+                        if e.is_permanent() {
+                            Some(DeliveryFailure::Permanent(e.to_string()))
+                        } else {
+                            Some(DeliveryFailure::Retryable(e.to_string()))
+                        }
+                    }
+                }
             }
-        }
+            None => {
+                tracing::info!("Subscriber is no longer confirmed. Skipping.");
+                None
+            }
+        },
         Err(e) => {
             tracing::error!(
-                error.cause_chain = ?e,
                 error.message = %e,
                 "Failed to parse confirmed subscriber email address. Skipping.",
             );
+            record_delivery(&mut transaction, &task, None, None, Some(&e))
+                .await
+                .context("Failed to record delivery attempt.")?;
+            Some(DeliveryFailure::Permanent(e))
         }
     };
-    delete_task(&mut transaction, &newsletter_issue_id, &subscriber_email)
+
+    match failure {
+        Some(DeliveryFailure::Retryable(_))
+            if i32::from(task.n_retries) < i32::from(settings.max_retries) =>
+        {
+            let delay = settings.retry_delay(task.n_retries as u16);
+            retry_task(&mut transaction, &task, delay)
+                .await
+                .context("Failed to reschedule delivery task.")?;
+        }
+        Some(DeliveryFailure::Retryable(e)) | Some(DeliveryFailure::Permanent(e)) => {
+            tracing::error!(
+                n_retries = task.n_retries,
+                "Giving up on delivering issue, moving it to the dead-letter table.",
+            );
+            delete_task(&mut transaction, &task)
+                .await
+                .context("Failed to delete delivery task.")?;
+            store_failure(&mut transaction, &task, &e)
+                .await
+                .context("Failed to store delivery failure.")?;
+        }
+        None => {
+            delete_task(&mut transaction, &task)
+                .await
+                .context("Failed to delete delivery task.")?;
+        }
+    }
+    mark_issue_as_sent_if_done(&mut transaction, task.newsletter_issue_id)
         .await
-        .context("waisa")?;
+        .context("Failed to update the newsletter issue status.")?;
     transaction.commit().await?;
 
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
-async fn worker_loop(pool: &PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: &PgPool,
+    email_client: EmailClient,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+    settings: &IssueDeliverySettings,
+) -> Result<(), anyhow::Error> {
     loop {
-        match deliver_queued_tasks(pool, &email_client).await {
+        if let Err(e) = publish_scheduled_issues(pool).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to publish scheduled newsletter issues.",
+            );
+        }
+        match deliver_queued_tasks(pool, &email_client, base_url, hmac_secret, settings).await {
             Ok(ExecutionOutcome::TaskCompleted) => {}
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(std::time::Duration::from_secs(10)).await
@@ -92,6 +213,162 @@ async fn worker_loop(pool: &PgPool, email_client: EmailClient) -> Result<(), any
     }
 }
 
+/// Enqueues delivery tasks for every scheduled issue whose send time has passed.
+#[tracing::instrument(skip_all, err)]
+pub async fn publish_scheduled_issues(pool: &PgPool) -> Result<usize, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let due_issues = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE status = 'scheduled' AND scheduled_for <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        "#
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch due newsletter issues.")?;
+
+    for issue in &due_issues {
+        sqlx::query!(
+            r#"
+            UPDATE newsletter_issues
+            SET status = 'queued', published_at = now()
+            WHERE newsletter_issue_id = $1
+            "#,
+            issue.newsletter_issue_id
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to mark the newsletter issue as queued.")?;
+        enqueue_delivery_tasks(&mut transaction, issue.newsletter_issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks.")?;
+    }
+    transaction.commit().await?;
+
+    Ok(due_issues.len())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let issue = sqlx::query!(
+        "SELECT segment FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let segment = Segment::parse(issue.segment.as_deref().unwrap_or_default())
+        .map_err(anyhow::Error::msg)
+        .context("The newsletter issue has an invalid segment.")?;
+
+    // Keep the filter in sync with `count_recipients`.
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        SELECT i.newsletter_issue_id, s.email
+        FROM newsletter_issues i
+        JOIN list_subscriptions l ON l.list_id = i.list_id
+        JOIN subscriptions s ON s.id = l.subscriber_id
+        WHERE
+            i.newsletter_issue_id = $1 AND
+            l.status = 'confirmed' AND
+            ($2::timestamptz IS NULL OR s.subscribed_at < $2) AND
+            ($3::timestamptz IS NULL OR s.subscribed_at >= $3) AND
+            ($4::text IS NULL OR s.locale = $4) AND
+            NOT EXISTS (
+                SELECT 1 FROM UNNEST($5::text[]) AS wanted(tag)
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM subscriber_tags t
+                    WHERE t.subscriber_id = s.id AND t.tag = wanted.tag
+                )
+            ) AND
+            NOT EXISTS (SELECT 1 FROM suppressions x WHERE x.email = lower(s.email))
+        "#,
+        newsletter_issue_id,
+        segment.subscribed_before,
+        segment.subscribed_after,
+        segment.locale.as_ref().map(|l| l.as_ref()),
+        &segment.tags()[..],
+    )
+    .execute(&mut *transaction)
+    .await?;
+    // Nobody to deliver to: the issue is sent as soon as it is published.
+    mark_issue_as_sent_if_done(transaction, newsletter_issue_id).await?;
+    Ok(())
+}
+
+/// How many confirmed subscribers of a list an issue targeting `segment` would reach.
+#[tracing::instrument(skip(pool))]
+pub async fn count_recipients(
+    pool: &PgPool,
+    list_id: Uuid,
+    segment: &Segment,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "n!"
+        FROM list_subscriptions l
+        JOIN subscriptions s ON s.id = l.subscriber_id
+        WHERE
+            l.list_id = $1 AND
+            l.status = 'confirmed' AND
+            ($2::timestamptz IS NULL OR s.subscribed_at < $2) AND
+            ($3::timestamptz IS NULL OR s.subscribed_at >= $3) AND
+            ($4::text IS NULL OR s.locale = $4) AND
+            NOT EXISTS (
+                SELECT 1 FROM UNNEST($5::text[]) AS wanted(tag)
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM subscriber_tags t
+                    WHERE t.subscriber_id = s.id AND t.tag = wanted.tag
+                )
+            ) AND
+            NOT EXISTS (SELECT 1 FROM suppressions x WHERE x.email = lower(s.email))
+        "#,
+        list_id,
+        segment.subscribed_before,
+        segment.subscribed_after,
+        segment.locale.as_ref().map(|l| l.as_ref()),
+        &segment.tags()[..],
+    )
+    .fetch_one(pool)
+    .await
+    .map(|row| row.n)
+}
+
+/// Moves a queued issue to `sent` once none of its delivery tasks are left.
+#[tracing::instrument(skip_all)]
+async fn mark_issue_as_sent_if_done(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues i
+        SET status = 'sent'
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = 'queued' AND
+            NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            )
+        "#,
+        newsletter_issue_id
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
 #[derive(FromRow)]
 struct NewsletterIssue {
     title: String,
@@ -99,6 +376,67 @@ struct NewsletterIssue {
     text_content: String,
 }
 
+impl NewsletterIssue {
+    fn html_content_with_footer(&self, merge_fields: &MergeFields<'_>) -> String {
+        format!(
+            "{}<p><a href=\"{}\">Unsubscribe</a> from this newsletter.</p>",
+            merge_fields.render_html(&self.html_content),
+            merge_fields.unsubscribe_url
+        )
+    }
+
+    fn text_content_with_footer(&self, merge_fields: &MergeFields<'_>) -> String {
+        format!(
+            "{}\n\nUnsubscribe from this newsletter: {}",
+            merge_fields.render_text(&self.text_content),
+            merge_fields.unsubscribe_url
+        )
+    }
+}
+
+/// One-click unsubscribe headers, as described by RFC 8058.
+fn list_unsubscribe_headers(unsubscribe_link: &str) -> HashMap<String, String> {
+    HashMap::from([
+        (
+            "List-Unsubscribe".to_string(),
+            format!("<{}>", unsubscribe_link),
+        ),
+        (
+            "List-Unsubscribe-Post".to_string(),
+            "List-Unsubscribe=One-Click".to_string(),
+        ),
+    ])
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    name: String,
+    list_id: Uuid,
+}
+
+/// Only finds subscribers still confirmed on the list the issue was sent to.
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscriber(
+    pool: &PgPool,
+    subscriber_email: &SubscriberEmail,
+    issue_id: &Uuid,
+) -> Result<Option<ConfirmedSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        ConfirmedSubscriber,
+        r#"
+        SELECT s.id, s.name, l.list_id
+        FROM subscriptions s
+        JOIN list_subscriptions l ON l.subscriber_id = s.id
+        JOIN newsletter_issues i ON i.list_id = l.list_id
+        WHERE s.email = $1 AND i.newsletter_issue_id = $2 AND l.status = 'confirmed'
+        "#,
+        subscriber_email.as_ref(),
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
 #[tracing::instrument(skip_all)]
 async fn get_issue(pool: &PgPool, issue_id: &Uuid) -> Result<NewsletterIssue, sqlx::Error> {
     sqlx::query_as!(
@@ -117,12 +455,14 @@ async fn get_issue(pool: &PgPool, issue_id: &Uuid) -> Result<NewsletterIssue, sq
     .await
 }
 
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+}
+
 #[tracing::instrument(skip_all)]
-async fn delete_task(
-    transaction: &mut PgTransaction,
-    newsletter_issue_id: &Uuid,
-    subscriber_email: &str,
-) -> Result<(), sqlx::Error> {
+async fn delete_task(transaction: &mut PgTransaction, task: &Task) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
             DELETE FROM issue_delivery_queue
@@ -130,8 +470,8 @@ async fn delete_task(
             newsletter_issue_id = $1 AND
             subscriber_email = $2
             "#,
-        newsletter_issue_id,
-        subscriber_email
+        task.newsletter_issue_id,
+        task.subscriber_email
     )
     .execute(transaction)
     .await?;
@@ -139,13 +479,106 @@ async fn delete_task(
 }
 
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
+async fn retry_task(
     transaction: &mut PgTransaction,
-) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    task: &Task,
+    delay: std::time::Duration,
+) -> Result<(), anyhow::Error> {
+    let execute_after = Utc::now() + chrono::Duration::from_std(delay)?;
     sqlx::query!(
         r#"
-        SELECT newsletter_issue_id, subscriber_email
+        UPDATE issue_delivery_queue
+        SET
+            n_retries = n_retries + 1,
+            execute_after = $3
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        execute_after
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn store_failure(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    last_error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures (
+            newsletter_issue_id,
+            subscriber_email,
+            n_retries,
+            last_error,
+            failed_at
+        ) VALUES (
+            $1, $2, $3, $4, now()
+        )
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            n_retries = EXCLUDED.n_retries,
+            last_error = EXCLUDED.last_error,
+            failed_at = EXCLUDED.failed_at
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        task.n_retries,
+        last_error
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    status_code: Option<u16>,
+    provider_message_id: Option<&str>,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries (
+            newsletter_delivery_id,
+            newsletter_issue_id,
+            subscriber_email,
+            attempted_at,
+            status_code,
+            provider_message_id,
+            error
+        ) VALUES (
+            $1, $2, $3, now(), $4, $5, $6
+        )
+        "#,
+        Uuid::new_v4(),
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        status_code.map(|c| c as i16),
+        provider_message_id,
+        error
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(transaction: &mut PgTransaction) -> Result<Option<Task>, sqlx::Error> {
+    sqlx::query_as!(
+        Task,
+        r#"
+        SELECT newsletter_issue_id, subscriber_email, n_retries
         FROM issue_delivery_queue
+        WHERE execute_after <= now()
         FOR UPDATE
         SKIP LOCKED
         LIMIT 1
@@ -153,7 +586,4 @@ async fn dequeue_task(
     )
     .fetch_optional(transaction)
     .await
-    .map(|maybe_result| {
-        maybe_result.map(|result| (result.newsletter_issue_id, result.subscriber_email))
-    })
 }
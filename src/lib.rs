@@ -4,8 +4,13 @@ pub mod domain;
 pub mod email_client;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod negotiation;
+pub mod rate_limiter;
 pub mod routes;
 pub mod session_state;
 pub mod startup;
+pub mod subscriber_data;
+pub mod subscriber_import;
+pub mod suppressions;
 pub mod telemetry;
 pub mod utils;
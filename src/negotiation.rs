@@ -0,0 +1,82 @@
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::http::header::{ACCEPT, CONTENT_TYPE};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Request body sent either as `application/json` or as an urlencoded form.
+pub struct JsonOrForm<T>(pub T);
+
+impl<T> FromRequest for JsonOrForm<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if sends_json(req) {
+            let json = web::Json::<T>::from_request(req, payload);
+            Box::pin(async move {
+                json.await
+                    .map(|json| JsonOrForm(json.into_inner()))
+                    .map_err(|e| {
+                        let body = ErrorBody {
+                            field: None,
+                            code: "invalid_body",
+                            message: e.to_string(),
+                        };
+                        let response = HttpResponse::BadRequest().json(body);
+                        InternalError::from_response(e, response).into()
+                    })
+            })
+        } else {
+            let form = web::Form::<T>::from_request(req, payload);
+            Box::pin(async move { form.await.map(|form| JsonOrForm(form.into_inner())) })
+        }
+    }
+}
+
+/// Error details returned to clients which asked for JSON.
+#[derive(Debug, serde::Serialize)]
+pub struct ErrorBody {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Whether the client accepts, or itself sent, JSON.
+pub fn wants_json(req: &HttpRequest) -> bool {
+    sends_json(req) || header_contains(req, ACCEPT, "application/json")
+}
+
+/// Renders `e` as an [`ErrorBody`] for JSON clients, as usual otherwise.
+pub fn negotiate_error<E>(req: &HttpRequest, e: E, body: ErrorBody) -> actix_web::Error
+where
+    E: ResponseError + 'static,
+{
+    if wants_json(req) {
+        let response = HttpResponse::build(e.status_code()).json(body);
+        InternalError::from_response(e, response).into()
+    } else {
+        e.into()
+    }
+}
+
+fn sends_json(req: &HttpRequest) -> bool {
+    header_contains(req, CONTENT_TYPE, "application/json")
+}
+
+fn header_contains(
+    req: &HttpRequest,
+    name: actix_web::http::header::HeaderName,
+    value: &str,
+) -> bool {
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().contains(value))
+}
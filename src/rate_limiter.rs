@@ -0,0 +1,120 @@
+use actix_web::HttpRequest;
+use anyhow::Context;
+use redis::aio::ConnectionManager;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::configuration::RateLimitSettings;
+
+/// Fixed window request counters kept in redis, so limits hold across
+/// every instance of the application.
+#[derive(Clone)]
+pub struct RateLimiter {
+    connection: ConnectionManager,
+    settings: RateLimitSettings,
+}
+
+impl RateLimiter {
+    pub async fn new(
+        redis_uri: &Secret<String>,
+        settings: RateLimitSettings,
+    ) -> anyhow::Result<Self> {
+        let client = redis::Client::open(redis_uri.expose_secret().as_str())
+            .context("Invalid redis uri.")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Cannot connect to redis.")?;
+        Ok(Self {
+            connection,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &RateLimitSettings {
+        &self.settings
+    }
+
+    /// The address requests are counted against.
+    pub fn client_ip(&self, request: &HttpRequest) -> String {
+        if self.settings.trust_forwarded_for {
+            if let Some(ip) = request.connection_info().realip_remote_addr() {
+                // Without forwarding headers actix falls back to the peer's `ip:port`.
+                return match ip.parse::<std::net::SocketAddr>() {
+                    Ok(addr) => addr.ip().to_string(),
+                    Err(_) => ip.to_string(),
+                };
+            }
+        }
+        request
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// Counts a hit against `key` and returns `false` once more than `limit`
+    /// hits were counted within the current window.
+    #[tracing::instrument(skip(self))]
+    pub async fn hit(
+        &self,
+        key: &str,
+        limit: u32,
+        window: std::time::Duration,
+    ) -> anyhow::Result<bool> {
+        let key = self.key(key);
+        // The window starts with the first hit, later hits must not extend it.
+        let (n_hits,): (u64,) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&key)
+            .arg(0)
+            .arg("NX")
+            .arg("EX")
+            .arg(window.as_secs().max(1))
+            .ignore()
+            .incr(&key, 1)
+            .query_async(&mut self.connection.clone())
+            .await
+            .context("Failed to count the request in redis.")?;
+        Ok(n_hits <= limit.into())
+    }
+
+    /// Counts a failure against `key` and returns the new count. Every
+    /// failure pushes the expiry back, so the count drops only once
+    /// `expiry` went by without one.
+    #[tracing::instrument(skip(self))]
+    pub async fn add_failure(&self, key: &str, expiry: std::time::Duration) -> anyhow::Result<u64> {
+        let key = self.key(key);
+        let (n_failures,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, expiry.as_secs().max(1) as usize)
+            .ignore()
+            .query_async(&mut self.connection.clone())
+            .await
+            .context("Failed to count the failure in redis.")?;
+        Ok(n_failures)
+    }
+
+    /// Takes back a failure counted against `key`, leaving its expiry alone.
+    #[tracing::instrument(skip(self))]
+    pub async fn remove_failure(&self, key: &str) -> anyhow::Result<()> {
+        let _: i64 = redis::cmd("DECR")
+            .arg(self.key(key))
+            .query_async(&mut self.connection.clone())
+            .await
+            .context("Failed to take back the failure in redis.")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn clear_failures(&self, key: &str) -> anyhow::Result<()> {
+        redis::cmd("DEL")
+            .arg(self.key(key))
+            .query_async(&mut self.connection.clone())
+            .await
+            .context("Failed to clear the failure count in redis.")
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.settings.key_prefix, key)
+    }
+}
@@ -3,13 +3,23 @@ use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, utils::e500};
+use crate::{
+    authentication::{Role, UserId},
+    utils::{e500, escape_html},
+};
 
 pub async fn admin_dashboard(
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
 ) -> actix_web::Result<HttpResponse> {
-    let username = get_username(&user_id, &pool).await.map_err(e500)?;
+    let username = escape_html(&get_username(&user_id, &pool).await.map_err(e500)?);
+    let role = role.into_inner();
+    let users_html = if role == Role::Owner {
+        r#"<li><a href="/admin/users">Users</a></li>"#
+    } else {
+        ""
+    };
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -21,9 +31,18 @@ pub async fn admin_dashboard(
     </head>
     <body>
         <p>Welcome, {username}!</p>
+        <p>You are signed in as {role}.</p>
         <ol>
           <li><a href="/admin/password">Change password</a></li>
+          <li><a href="/admin/two-factor">Two-factor authentication</a></li>
           <li><a href="/admin/newsletters">Publish newsletter</a></li>
+          <li><a href="/admin/newsletters/drafts">Newsletter drafts</a></li>
+          <li><a href="/admin/lists">Mailing lists</a></li>
+          <li><a href="/admin/subscribers">Subscribers</a></li>
+          <li><a href="/admin/subscribers/import">Import subscribers</a></li>
+          <li><a href="/admin/subscribers/export">Export subscribers (CSV)</a></li>
+          <li><a href="/admin/suppressions">Suppressions</a></li>
+          {users_html}
           <li>
             <form action="/admin/logout" name="logoutForm" method="post">
               <button name="logout" value="" type="submit">Logout</button>
@@ -0,0 +1,97 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::utils::{e500, escape_html, html_messages};
+
+pub struct MailingList {
+    pub slug: String,
+    pub name: String,
+    pub n_confirmed: i64,
+}
+
+pub async fn get_lists(pool: &PgPool) -> anyhow::Result<Vec<MailingList>> {
+    sqlx::query_as!(
+        MailingList,
+        r#"
+        SELECT
+            slug,
+            name,
+            (
+                SELECT COUNT(*)
+                FROM list_subscriptions m
+                WHERE m.list_id = l.list_id AND m.status = 'confirmed'
+            ) AS "n_confirmed!"
+        FROM lists l
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch mailing lists.")
+}
+
+/// `<option>`s for a list selector, with `selected` preselected.
+pub fn list_options_html(lists: &[MailingList], selected: &str) -> String {
+    lists.iter().fold(String::new(), |a, l| {
+        format!(
+            r#"{}<option value="{}"{}>{}</option>"#,
+            a,
+            l.slug,
+            if l.slug == selected { " selected" } else { "" },
+            escape_html(&l.name)
+        )
+    })
+}
+
+pub async fn lists_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let msg_html = html_messages(&flash_messages);
+    let lists_html = get_lists(&pool)
+        .await
+        .map_err(e500)?
+        .iter()
+        .fold(String::new(), |a, l| {
+            format!(
+                "{}<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                a,
+                escape_html(&l.name),
+                l.slug,
+                l.n_confirmed
+            )
+        });
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Mailing lists</title>
+    </head>
+    <body>
+        {msg_html}
+        <table>
+            <tr><th>Name</th><th>Slug</th><th>Confirmed subscribers</th></tr>
+            {lists_html}
+        </table>
+        <form method="post" action="/admin/lists">
+            <label>Name
+                <input type="text" placeholder="Enter list name" name="name" />
+            </label>
+            <label>Slug
+                <input type="text" placeholder="lowercase-with-dashes" name="slug" />
+            </label>
+            <button type="submit">Create list</button>
+        </form>
+        <p>
+          <a href="/admin/dashboard">&lt;- Back</a>
+        </p>
+    </body>
+</html>"#
+        )))
+}
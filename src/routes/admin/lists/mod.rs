@@ -0,0 +1,4 @@
+mod get;
+pub use get::{get_lists, list_options_html, lists_page};
+mod post;
+pub use post::create_list;
@@ -0,0 +1,59 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::ListSlug,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    slug: String,
+}
+
+#[tracing::instrument(skip(form, pool), fields(slug = %form.slug))]
+pub async fn create_list(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { name, slug } = form.0;
+
+    let name = name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The list needs a name.").send();
+        return Ok(see_other("/admin/lists"));
+    }
+    let slug = match ListSlug::parse(slug) {
+        Ok(slug) => slug,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/lists"));
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO lists (list_id, slug, name, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (slug) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to create the mailing list.")
+    .map_err(e500)?;
+
+    if result.rows_affected() == 0 {
+        FlashMessage::error(format!("A list called {} already exists.", slug.as_ref())).send();
+    } else {
+        FlashMessage::info(format!("The list {} has been created.", slug.as_ref())).send();
+    }
+    Ok(see_other("/admin/lists"))
+}
@@ -2,10 +2,35 @@ mod dashboard;
 mod password;
 mod logout;
 mod newsletters;
+mod lists;
+mod subscribers;
+mod suppressions;
+mod two_factor;
+mod users;
 
 pub use dashboard::admin_dashboard;
 pub use password::change_password;
+pub use password::check_new_password;
 pub use password::change_password_form;
 pub use logout::logout;
 pub use newsletters::publish_newsletter;
 pub use newsletters::publish_newsletter_form;
+pub use newsletters::newsletter_issue_report;
+pub use newsletters::cancel_newsletter_issue;
+pub use newsletters::reschedule_newsletter_issue;
+pub use newsletters::{edit_draft_form, list_drafts, publish_draft, save_draft, update_draft};
+pub use newsletters::{preview_newsletter_issue, send_test_newsletter_issue};
+pub use lists::{create_list, lists_page};
+pub use newsletters::newsletter_recipients;
+pub use subscribers::{export_subscribers, import_subscribers, import_subscribers_form};
+pub use subscribers::{
+    delete_subscriber, manually_confirm_subscriber, manually_unsubscribe_subscriber,
+    resend_confirmation_email, subscribers_page,
+};
+pub use suppressions::{add_suppression, remove_suppression, suppressions_page};
+pub use two_factor::{
+    disable_two_factor_authentication, enable_two_factor_authentication, two_factor_settings,
+};
+pub use users::{
+    change_role, create_user, deactivate_user, invite_user, reactivate_user, users_page,
+};
@@ -0,0 +1,352 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    domain::Segment,
+    idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    issue_delivery_worker::count_recipients,
+    routes::admin::lists::{get_lists, list_options_html},
+    utils::{e400, e500, escape_html, html_messages, see_other},
+};
+
+use super::{
+    post::{
+        default_list, find_list, insert_draft, parse_segment, publish_issue, success_message,
+        validate_merge_fields,
+    },
+    schedule::parse_send_time,
+};
+
+#[derive(serde::Deserialize)]
+pub struct DraftFormData {
+    title: String,
+    html_content: String,
+    text_content: String,
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(default)]
+    segment: String,
+}
+
+pub(super) struct IssueContent {
+    pub title: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub status: String,
+    pub list_id: Uuid,
+    pub list: String,
+    pub segment: Option<String>,
+}
+
+#[tracing::instrument(skip(pool))]
+pub(super) async fn get_issue_content(
+    pool: &PgPool,
+    newsletter_issue_id: &Uuid,
+) -> anyhow::Result<Option<IssueContent>> {
+    sqlx::query_as!(
+        IssueContent,
+        r#"
+        SELECT
+            title,
+            html_content,
+            text_content,
+            status,
+            i.list_id,
+            l.slug AS list,
+            segment
+        FROM newsletter_issues i
+        JOIN lists l ON l.list_id = i.list_id
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the newsletter issue.")
+}
+
+pub async fn list_drafts(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let msg_html = html_messages(&flash_messages);
+    let drafts = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title
+        FROM newsletter_issues
+        WHERE status = 'draft'
+        ORDER BY title
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch newsletter drafts.")
+    .map_err(e500)?;
+    let drafts_html = drafts.iter().fold(String::new(), |a, d| {
+        format!(
+            r#"{}<li><a href="/admin/newsletters/{}/edit">{}</a></li>"#,
+            a,
+            d.newsletter_issue_id,
+            escape_html(&d.title)
+        )
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Newsletter drafts</title>
+    </head>
+    <body>
+        {msg_html}
+        <h1>Drafts</h1>
+        <ul>{drafts_html}</ul>
+        <p>
+          <a href="/admin/newsletters">&lt;- Back</a>
+        </p>
+    </body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(skip(form, pool))]
+pub async fn save_draft(
+    form: web::Form<DraftFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let list_id = find_list(&pool, form.0.list.clone()).await?;
+    let segment = parse_segment(&form.segment).map_err(e400)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let newsletter_issue_id = insert_draft(
+        &mut transaction,
+        list_id,
+        segment.as_deref(),
+        &form.title,
+        &form.text_content,
+        &form.html_content,
+    )
+    .await
+    .context("Failed to store the newsletter draft.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the newsletter draft.")
+        .map_err(e500)?;
+
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&format!(
+        "/admin/newsletters/{}/edit",
+        newsletter_issue_id
+    )))
+}
+
+#[tracing::instrument(skip(flash_messages, pool))]
+pub async fn edit_draft_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    let issue = match get_issue_content(&pool, &newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(issue) => issue,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    if issue.status != "draft" {
+        return Ok(see_other(&format!(
+            "/admin/newsletters/{}",
+            newsletter_issue_id
+        )));
+    }
+
+    let msg_html = html_messages(&flash_messages);
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options_html = list_options_html(&lists, &issue.list);
+    let segment = issue.segment.unwrap_or_default();
+    let n_recipients = count_recipients(
+        &pool,
+        issue.list_id,
+        &Segment::parse(&segment).map_err(e500)?,
+    )
+    .await
+    .context("Failed to count the recipients of the draft.")
+    .map_err(e500)?;
+    let segment = escape_html(&segment);
+    let idempotency_key = Uuid::new_v4();
+    let title = escape_html(&issue.title);
+    let html_content = escape_html(&issue.html_content);
+    let text_content = escape_html(&issue.text_content);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Edit draft</title>
+    </head>
+    <body>
+        {msg_html}
+        <form method="post" action="/admin/newsletters/{newsletter_issue_id}/edit">
+            <label>List
+                <select name="list">{list_options_html}</select>
+            </label>
+            <label>Segment
+                <input type="text" name="segment" value="{segment}" placeholder="e.g. tag:vip locale:en" />
+            </label>
+            <label>Title
+                <input type="text" name="title" value="{title}" />
+            </label>
+            <label>HTML content
+                <textarea name="html_content">{html_content}</textarea>
+            </label>
+            <label>Text content
+                <textarea name="text_content">{text_content}</textarea>
+            </label>
+            <button type="submit">Save draft</button>
+        </form>
+        <p><a href="/admin/newsletters/{newsletter_issue_id}/preview">Preview</a></p>
+        <form method="post" action="/admin/newsletters/{newsletter_issue_id}/test">
+            <label>Send a test to
+                <input type="email" name="recipient" />
+            </label>
+            <button type="submit">Send test</button>
+        </form>
+        <p>Recipients matched: {n_recipients}</p>
+        <form method="post" action="/admin/newsletters/{newsletter_issue_id}/publish">
+            <input type="hidden" name="idempotency_key" value="{idempotency_key}" />
+            <label>Send at (UTC, leave empty to send now)
+                <input type="datetime-local" name="scheduled_for" />
+            </label>
+            <button type="submit">Publish</button>
+        </form>
+        <p>
+          <a href="/admin/newsletters/drafts">&lt;- Back</a>
+        </p>
+    </body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(skip(form, pool))]
+pub async fn update_draft(
+    form: web::Form<DraftFormData>,
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let list_id = find_list(&pool, form.0.list.clone()).await?;
+    let segment = parse_segment(&form.segment).map_err(e400)?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $2, html_content = $3, text_content = $4, list_id = $5, segment = $6
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        *newsletter_issue_id,
+        form.title,
+        form.html_content,
+        form.text_content,
+        list_id,
+        segment,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the newsletter draft.")
+    .map_err(e500)?;
+
+    if result.rows_affected() == 0 {
+        FlashMessage::error("Only drafts can be edited.").send();
+        return Ok(see_other(&format!(
+            "/admin/newsletters/{}",
+            newsletter_issue_id
+        )));
+    }
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&format!(
+        "/admin/newsletters/{}/edit",
+        newsletter_issue_id
+    )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PublishFormData {
+    idempotency_key: String,
+    #[serde(default)]
+    scheduled_for: String,
+}
+
+#[tracing::instrument(skip(form, pool))]
+pub async fn publish_draft(
+    form: web::Form<PublishFormData>,
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let PublishFormData {
+        idempotency_key,
+        scheduled_for,
+    } = form.0;
+    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let scheduled_for = parse_send_time(&scheduled_for).map_err(e400)?;
+    if scheduled_for.is_some_and(|t| t <= chrono::Utc::now()) {
+        FlashMessage::error("The send time must be in the future.").send();
+        return Ok(see_other(&format!(
+            "/admin/newsletters/{}/edit",
+            newsletter_issue_id
+        )));
+    }
+
+    if let Some(issue) = get_issue_content(&pool, &newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        if let Err(e) =
+            validate_merge_fields(&issue.title, &issue.html_content, &issue.text_content)
+        {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&format!(
+                "/admin/newsletters/{}/edit",
+                newsletter_issue_id
+            )));
+        }
+    }
+
+    let mut transaction = match try_processing(&pool, &idempotency_key, &user_id)
+        .await
+        .map_err(e500)?
+    {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            success_message(scheduled_for).send();
+            return Ok(saved_response);
+        }
+    };
+
+    if publish_issue(&mut transaction, *newsletter_issue_id, scheduled_for)
+        .await
+        .map_err(e500)?
+    {
+        success_message(scheduled_for).send();
+    } else {
+        FlashMessage::error("Only drafts can be published.").send();
+    }
+    let response = see_other(&format!("/admin/newsletters/{}", newsletter_issue_id));
+    let response = save_response(transaction, &idempotency_key, &user_id, response)
+        .await
+        .map_err(e500)?;
+    Ok(response)
+}
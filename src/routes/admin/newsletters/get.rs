@@ -1,13 +1,36 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
 
-use crate::utils::html_messages;
+use crate::{
+    domain::ListSlug,
+    routes::admin::lists::{get_lists, list_options_html},
+    utils::{e500, escape_html, html_messages},
+};
 
-pub async fn publish_newsletter_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+pub async fn publish_newsletter_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
     let msg_html = html_messages(&flash_messages);
     let idempotency_key = uuid::Uuid::new_v4();
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options_html = list_options_html(&lists, ListSlug::DEFAULT);
+    let issues_html = get_recent_issues(&pool).await.map_err(e500)?.iter().fold(
+        String::new(),
+        |a, (id, title, status)| {
+            format!(
+                r#"{}<li><a href="/admin/newsletters/{}">{}</a> ({})</li>"#,
+                a,
+                id,
+                escape_html(title),
+                status
+            )
+        },
+    );
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
             r#"<!DOCTYPE html>
@@ -20,16 +43,53 @@ pub async fn publish_newsletter_form(flash_messages: IncomingFlashMessages) -> H
         {msg_html}
         <form method="post" action="/admin/newsletters">
             <input type="hidden" name="idempotency_key" value="{idempotency_key}" />
-            <label>Subject
-                <input type="text" placeholder="Enter email subject" name="subject" />
+            <label>List
+                <select name="list">{list_options_html}</select>
             </label>
-            <label>Body
-                <textarea type="text" placeholder="Enter email content" name="content" />
+            <label>Segment
+                <input type="text" placeholder="e.g. tag:vip locale:en" name="segment" />
+            </label>
+            <button type="submit" formaction="/admin/newsletters/recipients" formmethod="get">Count recipients</button>
+            <label>Title
+                <input type="text" placeholder="Enter email subject" name="title" />
+            </label>
+            <label>HTML content
+                <textarea placeholder="Enter HTML content" name="html_content"></textarea>
+            </label>
+            <label>Text content
+                <textarea placeholder="Enter plain text content" name="text_content"></textarea>
+            </label>
+            <label>Send at (UTC, leave empty to send now)
+                <input type="datetime-local" name="scheduled_for" />
             </label>
 
             <button type="submit">Publish</button>
+            <button type="submit" formaction="/admin/newsletters/drafts">Save as draft</button>
         </form>
+        <p><a href="/admin/newsletters/drafts">Drafts</a></p>
+        <h2>Recent issues</h2>
+        <ul>{issues_html}</ul>
     </body>
 </html>"#
-        ))
+        )))
+}
+
+async fn get_recent_issues(pool: &PgPool) -> anyhow::Result<Vec<(uuid::Uuid, String, String)>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, status
+        FROM newsletter_issues
+        WHERE status <> 'draft'
+        ORDER BY scheduled_for DESC
+        LIMIT 20
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch recent newsletter issues.")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.newsletter_issue_id, r.title, r.status))
+        .collect())
 }
@@ -1,5 +1,15 @@
 mod post;
 mod get;
+mod report;
+mod schedule;
+mod drafts;
+mod preview;
+mod recipients;
 
 pub use get::publish_newsletter_form;
 pub use post::publish_newsletter;
+pub use report::newsletter_issue_report;
+pub use schedule::{cancel_newsletter_issue, reschedule_newsletter_issue};
+pub use drafts::{edit_draft_form, list_drafts, publish_draft, save_draft, update_draft};
+pub use preview::{preview_newsletter_issue, send_test_newsletter_issue};
+pub use recipients::newsletter_recipients;
@@ -1,20 +1,36 @@
 use crate::{
     authentication::UserId,
+    domain::{ListSlug, MergeFields, Segment},
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    issue_delivery_worker::enqueue_delivery_tasks,
+    routes::get_list_id,
     utils::{e400, e500, see_other},
 };
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use super::schedule::parse_send_time;
+
 #[derive(serde::Deserialize)]
 pub struct FormData {
     idempotency_key: String,
     title: String,
     html_content: String,
     text_content: String,
+    #[serde(default)]
+    scheduled_for: String,
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(default)]
+    segment: String,
+}
+
+pub(super) fn default_list() -> String {
+    ListSlug::DEFAULT.to_string()
 }
 
 #[tracing::instrument(
@@ -31,9 +47,16 @@ pub async fn publish_newsletter(
         text_content,
         html_content,
         idempotency_key,
+        scheduled_for,
+        list,
+        segment,
     } = form.0;
 
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    validate_merge_fields(&title, &html_content, &text_content).map_err(e400)?;
+    let scheduled_for = parse_send_time(&scheduled_for).map_err(e400)?;
+    let list_id = find_list(&pool, list).await?;
+    let segment = parse_segment(&segment).map_err(e400)?;
 
     let mut transaction = match try_processing(&pool, &idempotency_key, &user_id)
         .await
@@ -41,23 +64,32 @@ pub async fn publish_newsletter(
     {
         NextAction::StartProcessing(transaction) => transaction,
         NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
+            success_message(scheduled_for).send();
             return Ok(saved_response);
         }
     };
+    // Checked once we know this isn't a retry, a send time may well have
+    // passed by the time the retry comes in.
+    if scheduled_for.is_some_and(|t| t <= Utc::now()) {
+        return Err(e400("The send time must be in the future."));
+    }
 
-    let newsletter_issue_id =
-        insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-            .await
-            .context("Failed to store newsletter issue details.")
-            .map_err(e500)?;
-
-    enqueue_delivery_tasks(&mut transaction, newsletter_issue_id)
+    let newsletter_issue_id = insert_draft(
+        &mut transaction,
+        list_id,
+        segment.as_deref(),
+        &title,
+        &text_content,
+        &html_content,
+    )
+    .await
+    .context("Failed to store newsletter issue details.")
+    .map_err(e500)?;
+    publish_issue(&mut transaction, newsletter_issue_id, scheduled_for)
         .await
-        .context("Failed to enqueue delivery tasks.")
         .map_err(e500)?;
 
-    success_message().send();
+    success_message(scheduled_for).send();
     let response = see_other("/admin/newsletters");
     let response = save_response(transaction, &idempotency_key, &user_id, response)
         .await
@@ -66,30 +98,10 @@ pub async fn publish_newsletter(
 }
 
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
-    transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#,
-        newsletter_issue_id
-    )
-    .execute(transaction)
-    .await?;
-    Ok(())
-}
-
-#[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+pub(super) async fn insert_draft(
     transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    segment: Option<&str>,
     title: &str,
     text_content: &str,
     html_content: &str,
@@ -102,21 +114,94 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
-        ) VALUES (
-            $1, $2, $3, $4, now()
-        )
+            status,
+            list_id,
+            segment
+        ) VALUES ($1, $2, $3, $4, 'draft', $5, $6)
         "#,
         newsletter_issue_id,
         title,
         text_content,
         html_content,
+        list_id,
+        segment,
     )
     .execute(transaction)
     .await?;
     Ok(newsletter_issue_id)
 }
 
-fn success_message() -> FlashMessage {
-    FlashMessage::info("The newsletter issue has been queued!")
+/// Drafts without a send time are queued straight away, others wait for
+/// the delivery worker to pick them up once `scheduled_for` has passed.
+/// Returns `false` if the issue is no longer a draft.
+#[tracing::instrument(skip(transaction))]
+pub(super) async fn publish_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    scheduled_for: Option<DateTime<Utc>>,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            status = CASE WHEN $2::timestamptz IS NULL THEN 'queued' ELSE 'scheduled' END,
+            scheduled_for = COALESCE($2, now()),
+            published_at = CASE WHEN $2 IS NULL THEN now() END
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        scheduled_for,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to publish the newsletter issue.")?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if scheduled_for.is_none() {
+        enqueue_delivery_tasks(transaction, newsletter_issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks.")?;
+    }
+    Ok(true)
+}
+
+/// Resolves the list an issue is sent to, unknown lists are a bad request.
+pub(super) async fn find_list(pool: &PgPool, list: String) -> Result<Uuid, actix_web::Error> {
+    let list = ListSlug::parse(list).map_err(e400)?;
+    get_list_id(pool, &list)
+        .await
+        .context("Failed to look up the mailing list.")
+        .map_err(e500)?
+        .ok_or_else(|| e400(format!("{} is not a known list", list.as_ref())))
+}
+
+/// Validates a segment filter, returning it in the form it is stored in.
+pub(super) fn parse_segment(segment: &str) -> Result<Option<String>, String> {
+    Segment::parse(segment)?;
+    let segment = segment.split_whitespace().collect::<Vec<_>>().join(" ");
+    Ok(Some(segment).filter(|s| !s.is_empty()))
+}
+
+/// Placeholders are only rendered when the issue is delivered, so mistakes
+/// have to be caught before it is published.
+pub(super) fn validate_merge_fields(
+    title: &str,
+    html_content: &str,
+    text_content: &str,
+) -> Result<(), String> {
+    [title, html_content, text_content]
+        .into_iter()
+        .try_for_each(MergeFields::validate)
+}
+
+pub(super) fn success_message(scheduled_for: Option<DateTime<Utc>>) -> FlashMessage {
+    match scheduled_for {
+        Some(scheduled_for) => FlashMessage::info(format!(
+            "The newsletter issue has been scheduled for {}!",
+            scheduled_for.format("%Y-%m-%d %H:%M UTC")
+        )),
+        None => FlashMessage::info("The newsletter issue has been queued!"),
+    }
 }
@@ -0,0 +1,100 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{MergeFields, SubscriberEmail},
+    email_client::EmailClient,
+    utils::{e500, see_other},
+};
+
+use super::drafts::get_issue_content;
+
+/// Renders the HTML content of an issue as subscribers will see it.
+///
+/// The content is written by editors and served from the admin origin, so the
+/// page is sandboxed: its scripts cannot reach the session of whoever previews it.
+#[tracing::instrument(skip(pool))]
+pub async fn preview_newsletter_issue(
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    match get_issue_content(&pool, &newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(issue) => Ok(HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .insert_header(("Content-Security-Policy", "sandbox"))
+            .body(issue.html_content)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TestSendFormData {
+    recipient: String,
+}
+
+/// Sends a single copy of an issue, bypassing the delivery queue.
+#[tracing::instrument(skip(form, pool, email_client), fields(recipient = %form.recipient))]
+pub async fn send_test_newsletter_issue(
+    form: web::Form<TestSendFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    let issue = match get_issue_content(&pool, &newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(issue) => issue,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let location = if issue.status == "draft" {
+        format!("/admin/newsletters/{}/edit", newsletter_issue_id)
+    } else {
+        format!("/admin/newsletters/{}", newsletter_issue_id)
+    };
+
+    let recipient = match SubscriberEmail::parse(form.0.recipient) {
+        Ok(recipient) => recipient,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&location));
+        }
+    };
+
+    // There is no subscriber behind a test send, so the recipient's
+    // address stands in for every merge field but the unsubscribe link.
+    let merge_fields = MergeFields {
+        name: recipient.as_ref(),
+        email: recipient.as_ref(),
+        unsubscribe_url: "#",
+    };
+    match email_client
+        .send_email(
+            &recipient,
+            &format!("[TEST] {}", merge_fields.render_text(&issue.title)),
+            &merge_fields.render_html(&issue.html_content),
+            &merge_fields.render_text(&issue.text_content),
+        )
+        .await
+    {
+        Ok(()) => FlashMessage::info(format!(
+            "A test email has been sent to {}.",
+            recipient.as_ref()
+        ))
+        .send(),
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send a test email.",
+            );
+            FlashMessage::error("Failed to send the test email.").send();
+        }
+    }
+    Ok(see_other(&location))
+}
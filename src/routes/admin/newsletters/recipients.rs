@@ -0,0 +1,60 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    domain::Segment,
+    issue_delivery_worker::count_recipients,
+    utils::{e400, e500, escape_html},
+};
+
+use super::post::{default_list, find_list};
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(default)]
+    segment: String,
+}
+
+/// Tells how many subscribers an issue would be sent to, before sending it.
+#[tracing::instrument(skip(query, pool))]
+pub async fn newsletter_recipients(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let QueryParams { list, segment } = query.0;
+    let segment_filter = Segment::parse(&segment).map_err(e400)?;
+    let list_id = find_list(&pool, list.clone()).await?;
+    let n_recipients = count_recipients(&pool, list_id, &segment_filter)
+        .await
+        .context("Failed to count newsletter recipients.")
+        .map_err(e500)?;
+    let list = escape_html(&list);
+    let segment = if segment_filter.is_everyone() {
+        "everyone".to_string()
+    } else {
+        escape_html(&segment)
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Recipients</title>
+    </head>
+    <body>
+        <p>List: {list}</p>
+        <p>Segment: {segment}</p>
+        <p>Recipients matched: {n_recipients}</p>
+        <p>
+          <a href="/admin/newsletters">&lt;- Back</a>
+        </p>
+    </body>
+</html>"#
+        )))
+}
@@ -0,0 +1,153 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::{e500, escape_html, html_messages};
+
+struct IssueReport {
+    title: String,
+    list_name: String,
+    segment: Option<String>,
+    status: String,
+    scheduled_for: Option<DateTime<Utc>>,
+    published_at: Option<DateTime<Utc>>,
+    sent: i64,
+    failed: i64,
+    pending: i64,
+    attempts: i64,
+}
+
+#[tracing::instrument(skip(flash_messages, pool))]
+pub async fn newsletter_issue_report(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    let report = match get_issue_report(&pool, &newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(report) => report,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let msg_html = html_messages(&flash_messages);
+    let IssueReport {
+        title,
+        list_name,
+        segment,
+        status,
+        scheduled_for,
+        published_at,
+        sent,
+        failed,
+        pending,
+        attempts,
+    } = report;
+    let title = escape_html(&title);
+    let list_name = escape_html(&list_name);
+    let segment = segment.map_or_else(|| "everyone".to_string(), |s| escape_html(&s));
+    let published_html = match (published_at, scheduled_for) {
+        (Some(published_at), _) => format!("<p>Published at {}</p>", published_at),
+        (None, Some(scheduled_for)) => format!("<p>Scheduled for {}</p>", scheduled_for),
+        (None, None) => "<p>Not published yet</p>".to_string(),
+    };
+    let actions_html = if status == "draft" {
+        format!(r#"<p><a href="/admin/newsletters/{newsletter_issue_id}/edit">Edit draft</a></p>"#)
+    } else if status == "scheduled" {
+        format!(
+            r#"<form method="post" action="/admin/newsletters/{newsletter_issue_id}/schedule">
+            <label>New send time (UTC)
+                <input type="datetime-local" name="scheduled_for" />
+            </label>
+            <button type="submit">Reschedule</button>
+        </form>
+        <form method="post" action="/admin/newsletters/{newsletter_issue_id}/cancel">
+            <button type="submit">Cancel</button>
+        </form>"#
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Newsletter issue</title>
+    </head>
+    <body>
+        {msg_html}
+        <h1>{title}</h1>
+        <p>List: {list_name}</p>
+        <p>Segment: {segment}</p>
+        <p>Status: {status}</p>
+        {published_html}
+        <table>
+            <tr><th>Sent</th><td>{sent}</td></tr>
+            <tr><th>Failed</th><td>{failed}</td></tr>
+            <tr><th>Pending</th><td>{pending}</td></tr>
+            <tr><th>Delivery attempts</th><td>{attempts}</td></tr>
+        </table>
+        {actions_html}
+        <p><a href="/admin/newsletters/{newsletter_issue_id}/preview">Preview</a></p>
+        <p>
+          <a href="/admin/newsletters">&lt;- Back</a>
+        </p>
+    </body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_issue_report(
+    pool: &PgPool,
+    newsletter_issue_id: &Uuid,
+) -> anyhow::Result<Option<IssueReport>> {
+    let report = sqlx::query_as!(
+        IssueReport,
+        r#"
+        SELECT
+            title,
+            l.name AS list_name,
+            segment,
+            status,
+            scheduled_for,
+            published_at,
+            (
+                SELECT COUNT(DISTINCT subscriber_email)
+                FROM newsletter_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id AND d.error IS NULL
+            ) AS "sent!",
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_failures f
+                WHERE f.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "failed!",
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "pending!",
+            (
+                SELECT COUNT(*)
+                FROM newsletter_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "attempts!"
+        FROM newsletter_issues i
+        JOIN lists l ON l.list_id = i.list_id
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the newsletter issue report.")?;
+
+    Ok(report)
+}
@@ -0,0 +1,133 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::{e400, e500, see_other};
+
+/// Parses the value of a `datetime-local` input, interpreted as UTC.
+/// An empty value means "now".
+pub(super) fn parse_send_time(s: &str) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .with_context(|| format!("{} is not a valid send time", s))?;
+
+    Ok(Some(Utc.from_utc_datetime(&naive)))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleFormData {
+    scheduled_for: String,
+}
+
+#[tracing::instrument(skip(form, pool))]
+pub async fn reschedule_newsletter_issue(
+    form: web::Form<RescheduleFormData>,
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let location = format!("/admin/newsletters/{}", newsletter_issue_id);
+    let scheduled_for = match parse_send_time(&form.0.scheduled_for).map_err(e400)? {
+        Some(scheduled_for) if scheduled_for > Utc::now() => scheduled_for,
+        _ => {
+            FlashMessage::error("The new send time must be in the future.").send();
+            return Ok(see_other(&location));
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET scheduled_for = $2
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        *newsletter_issue_id,
+        scheduled_for
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to reschedule the newsletter issue.")
+    .map_err(e500)?;
+
+    if result.rows_affected() == 0 {
+        FlashMessage::error("Only scheduled issues can be rescheduled.").send();
+    } else {
+        FlashMessage::info(format!(
+            "The newsletter issue has been rescheduled for {}!",
+            scheduled_for.format("%Y-%m-%d %H:%M UTC")
+        ))
+        .send();
+    }
+    Ok(see_other(&location))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn cancel_newsletter_issue(
+    pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'cancelled'
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        *newsletter_issue_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to cancel the newsletter issue.")
+    .map_err(e500)?;
+
+    if result.rows_affected() == 0 {
+        FlashMessage::error("Only scheduled issues can be cancelled.").send();
+    } else {
+        FlashMessage::info("The newsletter issue has been cancelled.").send();
+    }
+    Ok(see_other(&format!(
+        "/admin/newsletters/{}",
+        newsletter_issue_id
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+    use claim::{assert_err, assert_none};
+
+    use super::parse_send_time;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> DateTime<Utc> {
+        let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        Utc.from_utc_datetime(&date.and_hms_opt(hour, min, sec).unwrap())
+    }
+
+    #[test]
+    fn empty_send_time_means_now() {
+        assert_none!(parse_send_time("  ").unwrap());
+    }
+
+    #[test]
+    fn datetime_local_values_are_parsed_as_utc() {
+        assert_eq!(
+            parse_send_time("2022-10-07T08:30").unwrap(),
+            Some(utc(2022, 10, 7, 8, 30, 0))
+        );
+        assert_eq!(
+            parse_send_time("2022-10-07T08:30:15").unwrap(),
+            Some(utc(2022, 10, 7, 8, 30, 15))
+        );
+    }
+
+    #[test]
+    fn garbage_send_time_is_rejected() {
+        assert_err!(parse_send_time("tomorrow"));
+    }
+}
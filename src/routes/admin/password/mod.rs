@@ -1,4 +1,4 @@
 mod get;
 pub use get::change_password_form;
 mod post;
-pub use post::change_password;
+pub use post::{change_password, check_new_password};
@@ -16,25 +16,35 @@ pub struct FormData {
     new_password_check: Secret<String>,
 }
 
-pub async fn change_password(
-    form: web::Form<FormData>,
-    pool: web::Data<PgPool>,
-    user_id: web::ReqData<UserId>
-) -> Result<HttpResponse, actix_web::Error> {
-    let new_password = form.0.new_password.expose_secret();
+/// The rules every new password must follow.
+pub fn check_new_password(
+    new_password: &Secret<String>,
+    new_password_check: &Secret<String>,
+) -> Result<(), &'static str> {
+    let new_password = new_password.expose_secret();
 
-    if new_password != form.0.new_password_check.expose_secret() {
-        FlashMessage::error("You entered two different passwords!").send();
-        return Ok(see_other("/admin/password"));
+    if new_password != new_password_check.expose_secret() {
+        return Err("You entered two different passwords!");
     }
 
     if new_password.len() < 12 {
-        FlashMessage::error("Your new password should be at least 12 characters in length!").send();
-        return Ok(see_other("/admin/password"));
+        return Err("Your new password should be at least 12 characters in length!");
     }
 
     if new_password.len() > 128 {
-        FlashMessage::error("Your new password should be at most 128 characters in length!").send();
+        return Err("Your new password should be at most 128 characters in length!");
+    }
+
+    Ok(())
+}
+
+pub async fn change_password(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(e) = check_new_password(&form.0.new_password, &form.0.new_password_check) {
+        FlashMessage::error(e).send();
         return Ok(see_other("/admin/password"));
     }
 
@@ -0,0 +1,200 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::SubscriptionSettings,
+    domain::{SubscriberEmail, SubscriptionToken},
+    email_client::EmailClient,
+    routes::{confirm_subscriber, send_confirmation_email, store_token},
+    startup::ApplicationBaseUrl,
+    subscriber_data::remove_subscriber,
+    utils::{e500, see_other},
+};
+
+struct Subscriber {
+    email: String,
+    /// Waiting on a confirmation for at least one of their lists.
+    pending: bool,
+}
+
+async fn get_subscriber(pool: &PgPool, subscriber_id: Uuid) -> anyhow::Result<Option<Subscriber>> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT email, (
+            status = 'pending_confirmation' OR EXISTS (
+                SELECT 1 FROM list_subscriptions
+                WHERE subscriber_id = s.id AND status = 'pending_confirmation'
+            )
+        ) AS "pending!"
+        FROM subscriptions s
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the subscriber.")
+}
+
+fn subscriber_not_found() -> HttpResponse {
+    FlashMessage::error("The subscriber does not exist.").send();
+    see_other("/admin/subscribers")
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn manually_confirm_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = match get_subscriber(&pool, subscriber_id).await.map_err(e500)? {
+        Some(subscriber) => subscriber,
+        None => return Ok(subscriber_not_found()),
+    };
+    if !subscriber.pending {
+        FlashMessage::error(format!("{} is not pending confirmation.", subscriber.email)).send();
+        return Ok(see_other("/admin/subscribers"));
+    }
+
+    confirm_subscriber(&pool, subscriber_id, None)
+        .await
+        .context("Failed to confirm the subscriber.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("{} has been confirmed.", subscriber.email)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn manually_unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = match get_subscriber(&pool, subscriber_id).await.map_err(e500)? {
+        Some(subscriber) => subscriber,
+        None => return Ok(subscriber_not_found()),
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")
+        .map_err(e500)?;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1",
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to unsubscribe the subscriber.")
+    .map_err(e500)?;
+    sqlx::query!(
+        "UPDATE list_subscriptions SET status = 'unsubscribed' WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to remove the subscriber from the lists.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("{} has been unsubscribed.", subscriber.email)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+/// Issues a new confirmation link, regardless of the daily limit subscribers
+/// are held to.
+#[tracing::instrument(skip(pool, email_client, base_url, settings))]
+pub async fn resend_confirmation_email(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> actix_web::Result<HttpResponse> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = match get_subscriber(&pool, subscriber_id).await.map_err(e500)? {
+        Some(subscriber) => subscriber,
+        None => return Ok(subscriber_not_found()),
+    };
+    if !subscriber.pending {
+        FlashMessage::error(format!("{} is not pending confirmation.", subscriber.email)).send();
+        return Ok(see_other("/admin/subscribers"));
+    }
+    let email = SubscriberEmail::parse(subscriber.email).map_err(e500)?;
+
+    let subscription_token = SubscriptionToken::generate();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")
+        .map_err(e500)?;
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        None,
+        subscription_token.as_ref(),
+        settings.confirmation_token_ttl(),
+    )
+    .await
+    .context("Failed to store the confirmation token.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")
+        .map_err(e500)?;
+
+    send_confirmation_email(
+        &pool,
+        &email_client,
+        &email,
+        &base_url.0,
+        subscription_token.as_ref(),
+    )
+    .await
+    .context("Failed to send the confirmation email.")
+    .map_err(e500)?;
+
+    FlashMessage::info(format!("A confirmation email has been sent to {}.", email)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = match get_subscriber(&pool, subscriber_id).await.map_err(e500)? {
+        Some(subscriber) => subscriber,
+        None => return Ok(subscriber_not_found()),
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")
+        .map_err(e500)?;
+    remove_subscriber(&mut transaction, subscriber_id, &subscriber.email)
+        .await
+        .context("Failed to delete the subscriber.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("{} has been deleted.", subscriber.email)).send();
+    Ok(see_other("/admin/subscribers"))
+}
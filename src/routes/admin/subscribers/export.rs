@@ -0,0 +1,188 @@
+use actix_web::{
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web, HttpResponse,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use sqlx::PgPool;
+use std::borrow::Cow;
+use tokio::sync::mpsc::{channel, Sender};
+
+/// Chunks waiting to be sent to a slow client before we stop reading rows.
+const CHANNEL_CAPACITY: usize = 16;
+/// Rows are buffered up to roughly this many bytes before being sent.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    #[serde(default)]
+    format: ExportFormat,
+    status: Option<String>,
+}
+
+struct ExportedSubscriber {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+}
+
+type Chunk = Result<web::Bytes, anyhow::Error>;
+
+/// Streams the subscribers as they are read from postgres, the table is never
+/// held in memory as a whole.
+#[tracing::instrument(skip(query, pool), fields(format = ?query.format, status = ?query.status))]
+pub async fn export_subscribers(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let QueryParams { format, status } = query.0;
+    let pool = pool.get_ref().clone();
+
+    let (sender, receiver) = channel::<Chunk>(CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        if let Err(e) = write_subscribers(&pool, status, format, &sender).await {
+            tracing::error!(error.cause_chain = ?e, "Failed to export the subscribers.");
+            // Aborts the response, so the client can tell the file is incomplete.
+            let _ = sender.send(Err(e)).await;
+        }
+    });
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    let (content_type, file_name) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "subscribers.csv"),
+        ExportFormat::Json => ("application/json", "subscribers.json"),
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name.to_string())],
+        })
+        .streaming(body)
+}
+
+async fn write_subscribers(
+    pool: &PgPool,
+    status: Option<String>,
+    format: ExportFormat,
+    sender: &Sender<Chunk>,
+) -> anyhow::Result<()> {
+    let mut rows = sqlx::query_as!(
+        ExportedSubscriber,
+        r#"
+        SELECT email, name, status, subscribed_at, confirmed_at
+        FROM subscriptions
+        WHERE $1::text IS NULL OR status = $1
+        ORDER BY subscribed_at, email
+        "#,
+        status
+    )
+    .fetch(pool);
+
+    let mut buffer = match format {
+        ExportFormat::Csv => b"email,name,status,subscribed_at,confirmed_at\n".to_vec(),
+        ExportFormat::Json => b"[".to_vec(),
+    };
+    let mut first = true;
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .context("Failed to fetch the subscribers.")?
+    {
+        match format {
+            ExportFormat::Csv => write_csv_row(&mut buffer, &row)?,
+            ExportFormat::Json => {
+                if !first {
+                    buffer.push(b',');
+                }
+                write_json_row(&mut buffer, &row)?;
+            }
+        }
+        first = false;
+        if buffer.len() >= CHUNK_SIZE && !send(sender, &mut buffer).await {
+            return Ok(());
+        }
+    }
+    if let ExportFormat::Json = format {
+        buffer.extend_from_slice(b"]\n");
+    }
+    send(sender, &mut buffer).await;
+    Ok(())
+}
+
+/// Returns `false` once the client went away.
+async fn send(sender: &Sender<Chunk>, buffer: &mut Vec<u8>) -> bool {
+    let chunk = web::Bytes::from(std::mem::take(buffer));
+    sender.send(Ok(chunk)).await.is_ok()
+}
+
+fn write_csv_row(buffer: &mut Vec<u8>, row: &ExportedSubscriber) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(buffer);
+    writer.write_record([
+        &csv_cell(&row.email),
+        &csv_cell(&row.name),
+        row.status.as_str(),
+        &row.subscribed_at.to_rfc3339(),
+        &row.confirmed_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+    ])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Spreadsheets run cells starting with these as formulas, and names are
+/// whatever subscribers typed in.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+fn csv_cell(value: &str) -> Cow<'_, str> {
+    if value.starts_with(FORMULA_PREFIXES) {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn write_json_row(buffer: &mut Vec<u8>, row: &ExportedSubscriber) -> anyhow::Result<()> {
+    serde_json::to_writer(
+        buffer,
+        &serde_json::json!({
+            "email": row.email,
+            "name": row.name,
+            "status": row.status,
+            "subscribed_at": row.subscribed_at.to_rfc3339(),
+            "confirmed_at": row.confirmed_at.map(|t| t.to_rfc3339()),
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_cell;
+
+    #[test]
+    fn cells_which_spreadsheets_would_run_as_formulas_are_quoted() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(csv_cell(value), format!("'{}", value));
+        }
+    }
+
+    #[test]
+    fn other_cells_are_left_alone() {
+        for value in ["Ursula", "ursula@gmail.com", "1=1", ""] {
+            assert_eq!(csv_cell(value), value);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use url::form_urlencoded;
+use uuid::Uuid;
+
+use crate::utils::{e400, e500, escape_html, html_messages};
+
+const PAGE_SIZE: i64 = 50;
+
+const STATUSES: [&str; 3] = ["confirmed", "pending_confirmation", "unsubscribed"];
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    status: String,
+    /// Keyset cursor: `subscribed_at` and `id` of the last row of the previous page.
+    after: Option<String>,
+    after_id: Option<Uuid>,
+}
+
+struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    /// Waiting on a confirmation for at least one of their lists.
+    pending: bool,
+    subscribed_at: DateTime<Utc>,
+}
+
+pub async fn subscribers_page(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let msg_html = html_messages(&flash_messages);
+    let QueryParams {
+        q,
+        status,
+        after,
+        after_id,
+    } = query.0;
+
+    let after = after
+        .map(|after| DateTime::parse_from_rfc3339(&after).map(|t| t.with_timezone(&Utc)))
+        .transpose()
+        .map_err(e400)?;
+    let mut subscribers = search_subscribers(&pool, &q, &status, after.zip(after_id))
+        .await
+        .map_err(e500)?;
+    let next_page_html = if subscribers.len() as i64 > PAGE_SIZE {
+        subscribers.truncate(PAGE_SIZE as usize);
+        let last = subscribers.last().unwrap();
+        let next_page = form_urlencoded::Serializer::new(String::new())
+            .append_pair("q", &q)
+            .append_pair("status", &status)
+            .append_pair("after", &last.subscribed_at.to_rfc3339())
+            .append_pair("after_id", &last.id.to_string())
+            .finish();
+        format!(
+            r#"<p><a href="/admin/subscribers?{}">Next page -&gt;</a></p>"#,
+            next_page
+        )
+    } else {
+        String::new()
+    };
+
+    let subscribers_html = subscribers.iter().fold(String::new(), |a, s| {
+        format!(
+            "{}<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            a,
+            escape_html(&s.email),
+            escape_html(&s.name),
+            s.status,
+            s.subscribed_at.format("%Y-%m-%d %H:%M"),
+            actions_html(s)
+        )
+    });
+    let status_options_html =
+        STATUSES
+            .iter()
+            .fold(r#"<option value="">Any</option>"#.to_string(), |a, s| {
+                format!(
+                    r#"{}<option value="{s}"{}>{s}</option>"#,
+                    a,
+                    if *s == status { " selected" } else { "" },
+                )
+            });
+    let q = escape_html(&q);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Subscribers</title>
+    </head>
+    <body>
+        {msg_html}
+        <form method="get" action="/admin/subscribers">
+            <label>Search
+                <input type="text" placeholder="Email or name" name="q" value="{q}" />
+            </label>
+            <label>Status
+                <select name="status">{status_options_html}</select>
+            </label>
+            <button type="submit">Search</button>
+        </form>
+        <table>
+            <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at (UTC)</th><th>Actions</th></tr>
+            {subscribers_html}
+        </table>
+        {next_page_html}
+        <p><a href="/admin/dashboard">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
+
+fn actions_html(subscriber: &Subscriber) -> String {
+    let action = |path: &str, label: &str| {
+        format!(
+            r#"<form method="post" action="/admin/subscribers/{}/{}"><button type="submit">{}</button></form>"#,
+            subscriber.id, path, label
+        )
+    };
+    let mut html = String::new();
+    if subscriber.pending {
+        html.push_str(&action("confirm", "Confirm"));
+        html.push_str(&action("resend", "Resend confirmation"));
+    }
+    if subscriber.status != "unsubscribed" {
+        html.push_str(&action("unsubscribe", "Unsubscribe"));
+    }
+    html.push_str(&action("delete", "Delete"));
+    html
+}
+
+/// Newest first, one row more than a page to tell whether there is a next one.
+async fn search_subscribers(
+    pool: &PgPool,
+    q: &str,
+    status: &str,
+    after: Option<(DateTime<Utc>, Uuid)>,
+) -> anyhow::Result<Vec<Subscriber>> {
+    let pattern = match q.trim() {
+        "" => None,
+        q => Some(format!("%{}%", escape_like(q))),
+    };
+    let status = Some(status).filter(|s| !s.is_empty());
+    let (after, after_id) = after.unzip();
+    sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at, (
+            status = 'pending_confirmation' OR EXISTS (
+                SELECT 1 FROM list_subscriptions
+                WHERE subscriber_id = s.id AND status = 'pending_confirmation'
+            )
+        ) AS "pending!"
+        FROM subscriptions s
+        WHERE
+            ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1) AND
+            ($2::text IS NULL OR status = $2) AND
+            ($3::timestamptz IS NULL OR (subscribed_at, id) < ($3, $4::uuid))
+        ORDER BY subscribed_at DESC, id DESC
+        LIMIT $5
+        "#,
+        pattern,
+        status,
+        after,
+        after_id,
+        PAGE_SIZE + 1
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search the subscribers.")
+}
+
+/// Searches are substrings, `%` and `_` are not wildcards.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -0,0 +1,132 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    domain::ListSlug,
+    routes::{
+        admin::lists::{get_lists, list_options_html},
+        get_list_id,
+    },
+    subscriber_import::{import_csv, ImportError},
+    utils::{e500, escape_html, html_messages, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    csv: String,
+    #[serde(default = "default_list")]
+    list: String,
+}
+
+fn default_list() -> String {
+    ListSlug::DEFAULT.to_string()
+}
+
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let msg_html = html_messages(&flash_messages);
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options_html = list_options_html(&lists, ListSlug::DEFAULT);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Import subscribers</title>
+    </head>
+    <body>
+        {msg_html}
+        <p>Paste a CSV with <code>email</code>, <code>name</code> and <code>status</code> columns.
+        The status is one of <code>confirmed</code>, <code>pending_confirmation</code> or <code>unsubscribed</code>.</p>
+        <form method="post" action="/admin/subscribers/import">
+            <label>List
+                <select name="list">{list_options_html}</select>
+            </label>
+            <label>CSV
+                <textarea name="csv" rows="20" cols="80" placeholder="email,name,status"></textarea>
+            </label>
+            <button type="submit">Import</button>
+        </form>
+        <p><a href="/admin/dashboard">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(skip(form, pool), fields(list = %form.list))]
+pub async fn import_subscribers(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let FormData { csv, list } = form.0;
+
+    let list = match ListSlug::parse(list) {
+        Ok(list) => list,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+    let list_id = match get_list_id(pool.get_ref(), &list)
+        .await
+        .context("Failed to look up the mailing list.")
+        .map_err(e500)?
+    {
+        Some(list_id) => list_id,
+        None => {
+            FlashMessage::error(format!("{} is not a known list", list.as_ref())).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+
+    let report = match import_csv(&pool, list_id, csv.as_bytes()).await {
+        Ok(report) => report,
+        Err(ImportError::InvalidCsv(e)) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+        Err(e) => return Err(e500(e)),
+    };
+
+    let n_imported = report.n_imported;
+    let n_added_to_list = report.n_added_to_list;
+    let n_skipped = report.skipped.len();
+    let list = list.as_ref();
+    let skipped_html = report.skipped.iter().fold(String::new(), |a, row| {
+        format!(
+            "{}<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            a,
+            row.line,
+            escape_html(&row.email),
+            escape_html(&row.reason)
+        )
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Import report</title>
+    </head>
+    <body>
+        <p>Imported {n_imported} subscribers into {list}, skipped {n_skipped} rows.</p>
+        <p>Added {n_added_to_list} subscribers we already knew to {list}.</p>
+        <table>
+            <tr><th>Line</th><th>Email</th><th>Reason</th></tr>
+            {skipped_html}
+        </table>
+        <p><a href="/admin/subscribers/import">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
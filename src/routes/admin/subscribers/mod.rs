@@ -0,0 +1,11 @@
+mod actions;
+mod export;
+mod get;
+mod import;
+pub use actions::{
+    delete_subscriber, manually_confirm_subscriber, manually_unsubscribe_subscriber,
+    resend_confirmation_email,
+};
+pub use export::export_subscribers;
+pub use get::subscribers_page;
+pub use import::{import_subscribers, import_subscribers_form};
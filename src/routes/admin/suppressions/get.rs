@@ -0,0 +1,99 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    suppressions::SuppressionReason,
+    utils::{e500, escape_html, html_messages},
+};
+
+struct Suppression {
+    email: String,
+    reason: String,
+    suppressed_at: DateTime<Utc>,
+}
+
+pub async fn suppressions_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> actix_web::Result<HttpResponse> {
+    let msg_html = html_messages(&flash_messages);
+    let suppressions = get_suppressions(&pool).await.map_err(e500)?;
+    let n_erased = count_erased(&pool).await.map_err(e500)?;
+
+    let suppressions_html = suppressions.iter().fold(String::new(), |a, s| {
+        let email = escape_html(&s.email);
+        format!(
+            r#"{}<tr><td>{}</td><td>{}</td><td>{}</td><td><form method="post" action="/admin/suppressions/remove"><input type="hidden" name="email" value="{}" /><button type="submit">Remove</button></form></td></tr>"#,
+            a,
+            email,
+            s.reason,
+            s.suppressed_at.format("%Y-%m-%d %H:%M"),
+            email
+        )
+    });
+    let reason_options_html = SuppressionReason::ALL.iter().fold(String::new(), |a, r| {
+        format!(
+            r#"{}<option value="{}"{}>{}</option>"#,
+            a,
+            r.as_str(),
+            if *r == SuppressionReason::Manual {
+                " selected"
+            } else {
+                ""
+            },
+            r.as_str()
+        )
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Suppressions</title>
+    </head>
+    <body>
+        {msg_html}
+        <p>Suppressed addresses are never mailed, not even to confirm a subscription.</p>
+        <table>
+            <tr><th>Email</th><th>Reason</th><th>Suppressed at (UTC)</th><th></th></tr>
+            {suppressions_html}
+        </table>
+        <p>{n_erased} erased addresses are suppressed as well, they can't be listed.</p>
+        <form method="post" action="/admin/suppressions">
+            <label>Email
+                <input type="text" placeholder="Enter an email address" name="email" />
+            </label>
+            <label>Reason
+                <select name="reason">{reason_options_html}</select>
+            </label>
+            <button type="submit">Suppress</button>
+        </form>
+        <p><a href="/admin/dashboard">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
+
+async fn get_suppressions(pool: &PgPool) -> anyhow::Result<Vec<Suppression>> {
+    sqlx::query_as!(
+        Suppression,
+        "SELECT email, reason, suppressed_at FROM suppressions ORDER BY suppressed_at DESC"
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the suppressions.")
+}
+
+async fn count_erased(pool: &PgPool) -> anyhow::Result<i64> {
+    sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM erased_subscribers"#)
+        .fetch_one(pool)
+        .await
+        .map(|row| row.n)
+        .context("Failed to count the erased subscribers.")
+}
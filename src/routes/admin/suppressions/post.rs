@@ -0,0 +1,80 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    domain::SubscriberEmail,
+    suppressions::{suppress, unsuppress, SuppressionReason},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct AddFormData {
+    email: String,
+    reason: String,
+}
+
+#[tracing::instrument(skip(form, pool), fields(email = %form.email, reason = %form.reason))]
+pub async fn add_suppression(
+    form: web::Form<AddFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let AddFormData { email, reason } = form.0;
+
+    let email = match SubscriberEmail::parse(email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+    let reason = match SuppressionReason::parse(&reason) {
+        Ok(reason) => reason,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")
+        .map_err(e500)?;
+    suppress(&mut transaction, email.as_ref(), reason)
+        .await
+        .context("Failed to suppress the address.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("{} has been suppressed.", email)).send();
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RemoveFormData {
+    email: String,
+}
+
+#[tracing::instrument(skip(form, pool), fields(email = %form.email))]
+pub async fn remove_suppression(
+    form: web::Form<RemoveFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let removed = unsuppress(pool.get_ref(), &form.email)
+        .await
+        .context("Failed to remove the suppression.")
+        .map_err(e500)?;
+
+    if removed {
+        FlashMessage::info(format!("{} is no longer suppressed.", form.email)).send();
+    } else {
+        FlashMessage::error(format!("{} was not suppressed.", form.email)).send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
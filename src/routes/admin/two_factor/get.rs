@@ -0,0 +1,87 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{count_recovery_codes, has_two_factor, TotpCipher, TotpSecret, UserId},
+    routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    utils::{e500, escape_html, html_messages},
+};
+
+/// How the account shows up in authenticator apps.
+const ISSUER: &str = "zero2prod";
+
+pub async fn two_factor_settings(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    cipher: web::Data<Option<TotpCipher>>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> actix_web::Result<HttpResponse> {
+    let user_id = user_id.into_inner();
+    let msg_html = html_messages(&flash_messages);
+
+    let body_html = if has_two_factor(&pool, *user_id).await.map_err(e500)? {
+        let recovery_codes = count_recovery_codes(&pool, *user_id).await.map_err(e500)?;
+        format!(
+            r#"<p>Two-factor authentication is enabled, you have {recovery_codes} recovery codes left.</p>
+        <form method="post" action="/admin/two-factor/disable">
+            <label>Code from your authenticator app, or a recovery code
+                <input type="text" placeholder="Enter code" name="code" autocomplete="one-time-code" />
+            </label>
+            <button type="submit">Disable two-factor authentication</button>
+        </form>"#
+        )
+    } else if cipher.is_none() {
+        "<p>Two-factor authentication is not available, no encryption key is configured.</p>"
+            .to_string()
+    } else {
+        // The same secret until it's confirmed, in case the page is reloaded
+        // after it was scanned.
+        let secret = match session
+            .get_pending_totp_secret()
+            .map_err(e500)?
+            .and_then(|s| TotpSecret::parse_base32(&s))
+        {
+            Some(secret) => secret,
+            None => {
+                let secret = TotpSecret::generate();
+                session
+                    .insert_pending_totp_secret(&secret.to_base32())
+                    .map_err(e500)?;
+                secret
+            }
+        };
+        let username = get_username(&user_id, &pool).await.map_err(e500)?;
+        let otpauth_uri = escape_html(&secret.otpauth_uri(ISSUER, &username));
+        let secret = secret.to_base32();
+        format!(
+            r#"<p>Two-factor authentication is disabled.</p>
+        <p>To enable it, scan <a href="{otpauth_uri}">{otpauth_uri}</a> with your authenticator app, or enter the key <code>{secret}</code>, then confirm with the code it shows.</p>
+        <form method="post" action="/admin/two-factor">
+            <label>Code
+                <input type="text" placeholder="Enter code" name="code" autocomplete="one-time-code" />
+            </label>
+            <button type="submit">Enable two-factor authentication</button>
+        </form>"#
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Two-factor authentication</title>
+    </head>
+    <body>
+        {msg_html}
+        {body_html}
+        <p><a href="/admin/dashboard">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
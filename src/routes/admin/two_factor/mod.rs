@@ -0,0 +1,4 @@
+mod get;
+pub use get::two_factor_settings;
+mod post;
+pub use post::{disable_two_factor_authentication, enable_two_factor_authentication};
@@ -0,0 +1,111 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{
+        disable_two_factor, enable_two_factor, get_totp_secret, verify_second_factor, TotpCipher,
+        TotpSecret, UserId,
+    },
+    session_state::TypedSession,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+/// Shows the recovery codes, this one time only.
+#[tracing::instrument(skip(form, pool, cipher, session))]
+pub async fn enable_two_factor_authentication(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    cipher: web::Data<Option<TotpCipher>>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> actix_web::Result<HttpResponse> {
+    let cipher = match cipher.get_ref() {
+        Some(cipher) => cipher,
+        None => {
+            FlashMessage::error("Two-factor authentication is not available.").send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+    };
+    let secret = match session
+        .get_pending_totp_secret()
+        .map_err(e500)?
+        .and_then(|s| TotpSecret::parse_base32(&s))
+    {
+        Some(secret) => secret,
+        None => {
+            FlashMessage::error("Reload the page and scan the new key.").send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+    };
+    let step = match secret.verify(&form.code, Utc::now().timestamp()) {
+        Some(step) => step,
+        None => {
+            FlashMessage::error("The code is not valid, check your device's clock.").send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+    };
+
+    let recovery_codes = enable_two_factor(&pool, cipher, **user_id, &secret, step)
+        .await
+        .map_err(e500)?;
+    session.remove_pending_totp_secret();
+
+    let recovery_codes_html = recovery_codes.iter().fold(String::new(), |a, c| {
+        format!("{}<li><code>{}</code></li>", a, c)
+    });
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Two-factor authentication</title>
+    </head>
+    <body>
+        <p>Two-factor authentication is enabled.</p>
+        <p>Keep these recovery codes somewhere safe, each of them logs you in once without your authenticator app. They won't be shown again.</p>
+        <ul>{recovery_codes_html}</ul>
+        <p><a href="/admin/dashboard">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(skip(form, pool, cipher))]
+pub async fn disable_two_factor_authentication(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    cipher: web::Data<Option<TotpCipher>>,
+    user_id: web::ReqData<UserId>,
+) -> actix_web::Result<HttpResponse> {
+    let cipher = cipher
+        .get_ref()
+        .as_ref()
+        .ok_or_else(|| e500("No TOTP encryption key is configured."))?;
+    let secret = match get_totp_secret(&pool, cipher, **user_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(secret) => secret,
+        None => return Ok(see_other("/admin/two-factor")),
+    };
+    if !verify_second_factor(&pool, &secret, **user_id, &form.code)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("The code is not valid.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    disable_two_factor(&pool, **user_id).await.map_err(e500)?;
+    FlashMessage::info("Two-factor authentication is disabled.").send();
+    Ok(see_other("/admin/two-factor"))
+}
@@ -0,0 +1,175 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Role, UserId},
+    utils::{e500, escape_html, html_messages},
+};
+
+struct User {
+    user_id: Uuid,
+    username: String,
+    email: Option<String>,
+    role: String,
+    deactivated_at: Option<DateTime<Utc>>,
+}
+
+struct Invitation {
+    email: String,
+    role: String,
+    expires_at: DateTime<Utc>,
+}
+
+pub async fn users_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> actix_web::Result<HttpResponse> {
+    let msg_html = html_messages(&flash_messages);
+    let users = get_users(&pool).await.map_err(e500)?;
+    let invitations = get_pending_invitations(&pool).await.map_err(e500)?;
+
+    let users_html = users.iter().fold(String::new(), |a, u| {
+        let status = match u.deactivated_at {
+            Some(at) => format!("Deactivated on {}", at.format("%Y-%m-%d")),
+            None => "Active".to_string(),
+        };
+        // Nobody can lock themselves out.
+        let actions = if u.user_id == **user_id {
+            String::new()
+        } else {
+            user_actions_html(u)
+        };
+        format!(
+            "{}<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            a,
+            escape_html(&u.username),
+            u.role,
+            status,
+            escape_html(u.email.as_deref().unwrap_or("")),
+            actions
+        )
+    });
+    let invitations_html = invitations.iter().fold(String::new(), |a, i| {
+        format!(
+            "{}<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            a,
+            escape_html(&i.email),
+            i.role,
+            i.expires_at.format("%Y-%m-%d %H:%M")
+        )
+    });
+    let role_options_html = role_options_html(Role::Editor.as_str());
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Users</title>
+    </head>
+    <body>
+        {msg_html}
+        <table>
+            <tr><th>Username</th><th>Role</th><th>Status</th><th>Email</th><th>Actions</th></tr>
+            {users_html}
+        </table>
+        <p>Viewers can only look around, editors can also publish and manage subscribers, owners can also manage users.</p>
+        <h2>Pending invitations</h2>
+        <table>
+            <tr><th>Email</th><th>Role</th><th>Expires at (UTC)</th></tr>
+            {invitations_html}
+        </table>
+        <form method="post" action="/admin/users/invitations">
+            <label>Email
+                <input type="email" placeholder="Enter email" name="email" />
+            </label>
+            <label>Role
+                <select name="role">{role_options_html}</select>
+            </label>
+            <button type="submit">Send invitation</button>
+        </form>
+        <h2>Add a user with a password</h2>
+        <form method="post" action="/admin/users">
+            <label>Username
+                <input type="text" placeholder="Enter username" name="username" />
+            </label>
+            <label>Email (to reset a forgotten password)
+                <input type="email" placeholder="Enter email" name="email" />
+            </label>
+            <label>Role
+                <select name="role">{role_options_html}</select>
+            </label>
+            <label>Password
+                <input type="password" placeholder="Enter password" name="new_password" />
+            </label>
+            <label>Confirm password
+                <input type="password" placeholder="Enter password again" name="new_password_check" />
+            </label>
+            <button type="submit">Add user</button>
+        </form>
+        <p><a href="/admin/dashboard">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
+
+fn user_actions_html(user: &User) -> String {
+    let status_action = if user.deactivated_at.is_some() {
+        ("reactivate", "Reactivate")
+    } else {
+        ("deactivate", "Deactivate")
+    };
+    format!(
+        r#"<form method="post" action="/admin/users/{id}/role"><select name="role">{}</select><button type="submit">Change role</button></form><form method="post" action="/admin/users/{id}/{}"><button type="submit">{}</button></form>"#,
+        role_options_html(&user.role),
+        status_action.0,
+        status_action.1,
+        id = user.user_id,
+    )
+}
+
+fn role_options_html(selected: &str) -> String {
+    Role::ALL.iter().fold(String::new(), |a, r| {
+        format!(
+            r#"{}<option value="{r}"{}>{r}</option>"#,
+            a,
+            if r.as_str() == selected {
+                " selected"
+            } else {
+                ""
+            },
+        )
+    })
+}
+
+async fn get_users(pool: &PgPool) -> anyhow::Result<Vec<User>> {
+    sqlx::query_as!(
+        User,
+        "SELECT user_id, username, email, role, deactivated_at FROM users ORDER BY username"
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the users.")
+}
+
+async fn get_pending_invitations(pool: &PgPool) -> anyhow::Result<Vec<Invitation>> {
+    sqlx::query_as!(
+        Invitation,
+        r#"
+        SELECT email, role, expires_at
+        FROM user_invitations
+        WHERE accepted_at IS NULL AND expires_at > now()
+        ORDER BY issued_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the pending invitations.")
+}
@@ -0,0 +1,4 @@
+mod get;
+pub use get::users_page;
+mod post;
+pub use post::{change_role, create_user, deactivate_user, invite_user, reactivate_user};
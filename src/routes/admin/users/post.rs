@@ -0,0 +1,310 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{add_user, Role, UserId},
+    domain::{InvitationToken, SubscriberEmail, Username},
+    email_client::EmailClient,
+    routes::check_new_password,
+    startup::ApplicationBaseUrl,
+    utils::{e500, see_other},
+};
+
+/// How long an invitation link stays valid.
+const INVITATION_TTL_HOURS: i32 = 72;
+
+#[derive(serde::Deserialize)]
+pub struct CreateFormData {
+    username: String,
+    /// Optional, but needed to reset a forgotten password.
+    #[serde(default)]
+    email: String,
+    role: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(skip(form, pool), fields(username = %form.username, role = %form.role))]
+pub async fn create_user(
+    form: web::Form<CreateFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let CreateFormData {
+        username,
+        email,
+        role,
+        new_password,
+        new_password_check,
+    } = form.0;
+
+    let username = match Username::parse(username) {
+        Ok(username) => username,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    let email = match email.trim() {
+        "" => None,
+        email => match SubscriberEmail::parse(email.to_string()) {
+            Ok(email) => Some(email),
+            Err(e) => {
+                FlashMessage::error(e).send();
+                return Ok(see_other("/admin/users"));
+            }
+        },
+    };
+    let role = match Role::parse(&role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    if let Err(e) = check_new_password(&new_password, &new_password_check) {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let email = email.as_ref().map(AsRef::as_ref);
+    let username = username.as_ref();
+    let added = add_user(username, email, &new_password, role, pool.get_ref())
+        .await
+        .map_err(e500)?;
+    if added.is_none() {
+        FlashMessage::error(format!("{} is already taken.", username)).send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    FlashMessage::info(format!("{} has been added as {}.", username, role)).send();
+    Ok(see_other("/admin/users"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    email: String,
+    role: String,
+}
+
+/// Emails a link letting the invitee pick their own username and password.
+#[tracing::instrument(
+    skip(form, pool, email_client, base_url, user_id),
+    fields(email = %form.email, role = %form.role)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    let role = match Role::parse(&form.0.role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    let invitation_token = InvitationToken::generate();
+    store_invitation(&pool, &invitation_token, &email, role, **user_id)
+        .await
+        .map_err(e500)?;
+    send_invitation_email(&email_client, &email, role, &base_url.0, &invitation_token)
+        .await
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("An invitation has been sent to {}.", email)).send();
+    Ok(see_other("/admin/users"))
+}
+
+/// Earlier invitations to the same address stop working.
+async fn store_invitation(
+    pool: &PgPool,
+    invitation_token: &InvitationToken,
+    email: &SubscriberEmail,
+    role: Role,
+    invited_by: Uuid,
+) -> anyhow::Result<()> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    sqlx::query!(
+        r#"
+        UPDATE user_invitations
+        SET expires_at = LEAST(expires_at, now())
+        WHERE lower(email) = lower($1) AND accepted_at IS NULL
+        "#,
+        email.as_ref()
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to expire the previous invitations.")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (
+            invitation_token, email, role, invited_by, issued_at, expires_at
+        ) VALUES (
+            $1, $2, $3, $4, now(), now() + make_interval(hours => $5)
+        )
+        "#,
+        invitation_token.as_ref(),
+        email.as_ref(),
+        role.as_str(),
+        invited_by,
+        INVITATION_TTL_HOURS
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the invitation.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+    Ok(())
+}
+
+async fn send_invitation_email(
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    role: Role,
+    base_url: &str,
+    invitation_token: &InvitationToken,
+) -> anyhow::Result<()> {
+    let invitation_link = format!(
+        "{}/invitations/accept?invitation_token={}",
+        base_url,
+        invitation_token.as_ref()
+    );
+    email_client
+        .send_email(
+            recipient,
+            "You have been invited to help run the newsletter",
+            &format!(
+                "You have been invited to help run the newsletter as {}.<br/>\
+                Click <a href=\"{}\">here</a> to choose your username and password. \
+                The link is valid for {} hours.",
+                role, invitation_link, INVITATION_TTL_HOURS
+            ),
+            &format!(
+                "You have been invited to help run the newsletter as {}.\n\
+                Visit {} to choose your username and password. \
+                The link is valid for {} hours.",
+                role, invitation_link, INVITATION_TTL_HOURS
+            ),
+        )
+        .await
+        .context("Failed to send the invitation email.")?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    role: String,
+}
+
+#[tracing::instrument(skip(form, pool), fields(role = %form.role))]
+pub async fn change_role(
+    target_user_id: web::Path<Uuid>,
+    form: web::Form<RoleFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target_user_id = target_user_id.into_inner();
+    if target_user_id == **user_id {
+        FlashMessage::error("You cannot change your own role.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let role = match Role::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    let username = sqlx::query!(
+        "UPDATE users SET role = $1 WHERE user_id = $2 RETURNING username",
+        role.as_str(),
+        target_user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to change the user's role.")
+    .map_err(e500)?
+    .map(|row| row.username);
+
+    match username {
+        Some(username) => FlashMessage::info(format!("{} is now {}.", username, role)).send(),
+        None => FlashMessage::error("The user does not exist.").send(),
+    }
+    Ok(see_other("/admin/users"))
+}
+
+/// Deactivated users can't log in, and are logged out of their current sessions.
+#[tracing::instrument(skip(pool))]
+pub async fn deactivate_user(
+    target_user_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_deactivated(target_user_id.into_inner(), true, &pool, &user_id).await
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn reactivate_user(
+    target_user_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_deactivated(target_user_id.into_inner(), false, &pool, &user_id).await
+}
+
+async fn set_deactivated(
+    target_user_id: Uuid,
+    deactivated: bool,
+    pool: &PgPool,
+    user_id: &UserId,
+) -> Result<HttpResponse, actix_web::Error> {
+    if target_user_id == **user_id {
+        FlashMessage::error("You cannot deactivate yourself.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let username = sqlx::query!(
+        r#"
+        UPDATE users
+        SET deactivated_at = CASE WHEN $1 THEN COALESCE(deactivated_at, now()) END
+        WHERE user_id = $2
+        RETURNING username
+        "#,
+        deactivated,
+        target_user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to update the user.")
+    .map_err(e500)?
+    .map(|row| row.username);
+
+    match username {
+        Some(username) if deactivated => {
+            FlashMessage::info(format!("{} has been deactivated.", username)).send()
+        }
+        Some(username) => FlashMessage::info(format!("{} has been reactivated.", username)).send(),
+        None => FlashMessage::error("The user does not exist.").send(),
+    }
+    Ok(see_other("/admin/users"))
+}
@@ -0,0 +1,282 @@
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    routes::error_chain_fmt,
+    startup::EmailWebhookSecret,
+    suppressions::{suppress, SuppressionReason},
+};
+
+/// Hex encoded HMAC-SHA256 of `{timestamp}.{body}`, keyed with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// When the event was signed, in seconds since the Unix epoch.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// How far the timestamp may be from our clock, so a captured request cannot
+/// be replayed later on.
+const TIMESTAMP_TOLERANCE_SECS: i64 = 5 * 60;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BounceType {
+    Hard,
+    Soft,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DeliveryEvent {
+    Delivered,
+    Bounce { bounce_type: BounceType },
+    Complaint,
+}
+
+impl DeliveryEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delivered => "delivered",
+            Self::Bounce {
+                bounce_type: BounceType::Hard,
+            } => "hard_bounce",
+            Self::Bounce {
+                bounce_type: BounceType::Soft,
+            } => "soft_bounce",
+            Self::Complaint => "complaint",
+        }
+    }
+
+    /// Soft bounces are worth another try, the others aren't.
+    fn suppression_reason(&self) -> Option<SuppressionReason> {
+        match self {
+            Self::Bounce {
+                bounce_type: BounceType::Hard,
+            } => Some(SuppressionReason::HardBounce),
+            Self::Complaint => Some(SuppressionReason::Complaint),
+            _ => None,
+        }
+    }
+}
+
+/// The recipient is looked up by `message_id`, the id the provider gave back
+/// when we sent the email, falling back to `email`.
+#[derive(serde::Deserialize, Debug)]
+pub struct WebhookPayload {
+    #[serde(flatten)]
+    event: DeliveryEvent,
+    message_id: Option<String>,
+    email: Option<String>,
+    description: Option<String>,
+}
+
+#[tracing::instrument(skip(request, body, pool, secret), fields(event = tracing::field::Empty))]
+pub async fn email_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    secret: web::Data<EmailWebhookSecret>,
+) -> Result<HttpResponse, WebhookError> {
+    verify_signature(&request, &body, secret.0.as_ref())?;
+    let payload: WebhookPayload =
+        serde_json::from_slice(&body).map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+    tracing::Span::current().record("event", tracing::field::display(payload.event.as_str()));
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    let (newsletter_delivery_id, email) = find_recipient(&mut transaction, &payload)
+        .await
+        .context("Failed to look up the delivery.")?
+        .ok_or(WebhookError::UnknownRecipient)?;
+    record_event(&mut transaction, newsletter_delivery_id, &email, &payload)
+        .await
+        .context("Failed to record the delivery event.")?;
+    if let Some(reason) = payload.event.suppression_reason() {
+        tracing::info!(subscriber_email = %email, "Suppressing the address.");
+        suppress(&mut transaction, &email, reason)
+            .await
+            .context("Failed to suppress the address.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn verify_signature(
+    request: &HttpRequest,
+    body: &[u8],
+    secret: Option<&Secret<String>>,
+) -> Result<(), WebhookError> {
+    let secret = secret.ok_or_else(|| {
+        tracing::warn!("EMAIL_WEBHOOK_SECRET is not set, rejecting the webhook.");
+        WebhookError::InvalidSignature
+    })?;
+    let signature = request
+        .headers()
+        .get(SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| hex::decode(v.trim()).ok())
+        .ok_or(WebhookError::InvalidSignature)?;
+    let timestamp = request
+        .headers()
+        .get(TIMESTAMP_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(WebhookError::InvalidSignature)?
+        .trim();
+    let signed_at = timestamp
+        .parse::<i64>()
+        .map_err(|_| WebhookError::InvalidSignature)?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| WebhookError::InvalidSignature)?;
+
+    if (Utc::now().timestamp() - signed_at).abs() > TIMESTAMP_TOLERANCE_SECS {
+        tracing::warn!(
+            signed_at,
+            "The webhook was signed too long ago, rejecting it."
+        );
+        return Err(WebhookError::InvalidSignature);
+    }
+    Ok(())
+}
+
+async fn find_recipient(
+    transaction: &mut Transaction<'_, Postgres>,
+    payload: &WebhookPayload,
+) -> Result<Option<(Option<Uuid>, String)>, sqlx::Error> {
+    if let Some(message_id) = &payload.message_id {
+        let delivery = sqlx::query!(
+            r#"
+            SELECT newsletter_delivery_id, subscriber_email
+            FROM newsletter_deliveries
+            WHERE provider_message_id = $1
+            LIMIT 1
+            "#,
+            message_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        if let Some(delivery) = delivery {
+            return Ok(Some((
+                Some(delivery.newsletter_delivery_id),
+                delivery.subscriber_email,
+            )));
+        }
+    }
+    Ok(payload
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|email| !email.is_empty())
+        .map(|email| (None, email.to_string())))
+}
+
+async fn record_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_delivery_id: Option<Uuid>,
+    email: &str,
+    payload: &WebhookPayload,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO delivery_events (
+            delivery_event_id,
+            newsletter_delivery_id,
+            subscriber_email,
+            event,
+            description,
+            received_at
+        ) VALUES (
+            $1, $2, $3, $4, $5, now()
+        )
+        "#,
+        Uuid::new_v4(),
+        newsletter_delivery_id,
+        email,
+        payload.event.as_str(),
+        payload.description
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("The signature is missing or invalid.")]
+    InvalidSignature,
+    #[error("{0}")]
+    InvalidPayload(String),
+    #[error("The event matches neither a delivery nor an email address.")]
+    UnknownRecipient,
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidSignature => StatusCode::UNAUTHORIZED,
+            Self::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownRecipient => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BounceType, DeliveryEvent, WebhookPayload};
+
+    fn parse(json: &str) -> Result<WebhookPayload, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn a_bounce_carries_its_type() {
+        let payload =
+            parse(r#"{"event": "bounce", "bounce_type": "hard", "email": "ursula@gmail.com"}"#)
+                .unwrap();
+        assert_eq!(
+            payload.event,
+            DeliveryEvent::Bounce {
+                bounce_type: BounceType::Hard
+            }
+        );
+        assert!(payload.event.suppression_reason().is_some());
+    }
+
+    #[test]
+    fn a_bounce_without_a_type_is_rejected() {
+        assert!(parse(r#"{"event": "bounce", "email": "ursula@gmail.com"}"#).is_err());
+    }
+
+    #[test]
+    fn soft_bounces_and_deliveries_are_not_suppressed() {
+        for json in [
+            r#"{"event": "bounce", "bounce_type": "soft", "message_id": "1"}"#,
+            r#"{"event": "delivered", "message_id": "1"}"#,
+        ] {
+            assert!(parse(json).unwrap().event.suppression_reason().is_none());
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use super::{get_pending_invitation, InvitationError};
+use crate::utils::{escape_html, html_messages};
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    invitation_token: String,
+}
+
+#[tracing::instrument(skip(flash_messages, query, pool))]
+pub async fn accept_invitation_form(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, InvitationError> {
+    let (email, role) = get_pending_invitation(pool.get_ref(), &query.invitation_token).await?;
+    let msg_html = html_messages(&flash_messages);
+    let invitation_token = escape_html(&query.invitation_token);
+    let email = escape_html(&email);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Accept invitation</title>
+    </head>
+    <body>
+        {msg_html}
+        <p>You have been invited as {role}, choose how you will log in.</p>
+        <form method="post" action="/invitations/accept">
+            <input type="hidden" name="invitation_token" value="{invitation_token}" />
+            <label>Username
+                <input type="text" placeholder="Enter username" name="username" value="{email}" />
+            </label>
+            <label>Password
+                <input type="password" placeholder="Enter password" name="new_password" />
+            </label>
+            <label>Confirm password
+                <input type="password" placeholder="Enter password again" name="new_password_check" />
+            </label>
+            <button type="submit">Create account</button>
+        </form>
+    </body>
+</html>"#
+        )))
+}
@@ -0,0 +1,79 @@
+use actix_web::{http::StatusCode, ResponseError};
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+use crate::{authentication::Role, domain::InvitationToken, routes::error_chain_fmt};
+
+mod get;
+pub use get::accept_invitation_form;
+mod post;
+pub use post::accept_invitation;
+
+struct Invitation {
+    email: String,
+    role: String,
+    expires_at: DateTime<Utc>,
+    accepted_at: Option<DateTime<Utc>>,
+}
+
+/// The invitation behind `invitation_token`, as long as it can still be accepted.
+///
+/// Pass a transaction to lock it until the account is created.
+async fn get_pending_invitation<'e>(
+    executor: impl PgExecutor<'e>,
+    invitation_token: &str,
+) -> Result<(String, Role), InvitationError> {
+    let invitation_token = InvitationToken::parse(invitation_token.to_string())
+        .map_err(|_| InvitationError::UnknownToken)?;
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"
+        SELECT email, role, expires_at, accepted_at
+        FROM user_invitations
+        WHERE invitation_token = $1
+        FOR UPDATE
+        "#,
+        invitation_token.as_ref()
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| InvitationError::UnexpectedError(e.into()))?
+    .ok_or(InvitationError::UnknownToken)?;
+    if invitation.accepted_at.is_some() {
+        return Err(InvitationError::UsedToken);
+    }
+    if invitation.expires_at <= Utc::now() {
+        return Err(InvitationError::ExpiredToken);
+    }
+    let role = Role::parse(&invitation.role).map_err(anyhow::Error::msg)?;
+    Ok((invitation.email, role))
+}
+
+#[derive(thiserror::Error)]
+pub enum InvitationError {
+    #[error("This invitation link is not valid.")]
+    UnknownToken,
+    #[error("This invitation link was already used.")]
+    UsedToken,
+    #[error("This invitation link has expired, ask for a new one.")]
+    ExpiredToken,
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for InvitationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for InvitationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::UsedToken => StatusCode::GONE,
+            Self::ExpiredToken => StatusCode::FORBIDDEN,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
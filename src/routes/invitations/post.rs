@@ -0,0 +1,83 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use url::form_urlencoded;
+
+use super::{get_pending_invitation, InvitationError};
+use crate::{
+    authentication::add_user, domain::Username, routes::check_new_password, utils::see_other,
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    invitation_token: String,
+    username: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(skip(form, pool), fields(username = %form.username))]
+pub async fn accept_invitation(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, InvitationError> {
+    let FormData {
+        invitation_token,
+        username,
+        new_password,
+        new_password_check,
+    } = form.0;
+    let retry = || {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("invitation_token", &invitation_token)
+            .finish();
+        see_other(&format!("/invitations/accept?{}", query))
+    };
+
+    let username = match Username::parse(username) {
+        Ok(username) => username,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(retry());
+        }
+    };
+    if let Err(e) = check_new_password(&new_password, &new_password_check) {
+        FlashMessage::error(e).send();
+        return Ok(retry());
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    let (email, role) = get_pending_invitation(&mut transaction, &invitation_token).await?;
+    if add_user(
+        username.as_ref(),
+        Some(&email),
+        &new_password,
+        role,
+        &mut transaction,
+    )
+    .await?
+    .is_none()
+    {
+        FlashMessage::error(format!("{} is already taken.", username.as_ref())).send();
+        return Ok(retry());
+    }
+    sqlx::query!(
+        "UPDATE user_invitations SET accepted_at = now() WHERE invitation_token = $1",
+        invitation_token
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to mark the invitation as accepted.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+
+    FlashMessage::info("Your account is ready, you can log in now.").send();
+    Ok(see_other("/login"))
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use actix_web::HttpRequest;
+use uuid::Uuid;
+
+use super::post::LoginError;
+use crate::rate_limiter::RateLimiter;
+
+/// The slowest a login attempt gets before the lockout kicks in.
+const MAX_LOGIN_DELAY: Duration = Duration::from_secs(8);
+
+/// Failed logins counted against what is being guessed and where the guesses
+/// come from, which slow down and then lock out further attempts.
+///
+/// Every attempt is counted as failed up front, and taken back if it was not,
+/// so concurrent attempts cannot all get in under the limits.
+pub(super) struct LoginAttempts<'a> {
+    rate_limiter: &'a RateLimiter,
+    subject_key: String,
+    ip_key: String,
+    /// Attempts counted so far, this one included.
+    subject_attempts: u64,
+    ip_attempts: u64,
+}
+
+impl<'a> LoginAttempts<'a> {
+    pub fn for_username(
+        rate_limiter: &'a RateLimiter,
+        request: &HttpRequest,
+        username: &str,
+    ) -> Self {
+        Self::new(
+            rate_limiter,
+            request,
+            format!("login:username:{}", username),
+        )
+    }
+
+    /// Someone who knows the password still only gets a few guesses at the code.
+    pub fn for_second_factor(
+        rate_limiter: &'a RateLimiter,
+        request: &HttpRequest,
+        user_id: Uuid,
+    ) -> Self {
+        Self::new(
+            rate_limiter,
+            request,
+            format!("login:second_factor:{}", user_id),
+        )
+    }
+
+    fn new(rate_limiter: &'a RateLimiter, request: &HttpRequest, subject_key: String) -> Self {
+        let ip_key = format!("login:ip:{}", rate_limiter.client_ip(request));
+        Self {
+            rate_limiter,
+            subject_key,
+            ip_key,
+            subject_attempts: 0,
+            ip_attempts: 0,
+        }
+    }
+
+    /// Counts the attempt, then waits out the delay earned by the previous
+    /// failures, and fails without looking at the credentials while locked out.
+    pub async fn throttle(&mut self) -> Result<(), LoginError> {
+        let rate_limiter = self.rate_limiter;
+        let settings = rate_limiter.settings();
+        let lockout = Duration::from_secs(settings.login_lockout_minutes * 60);
+        self.subject_attempts = rate_limiter.add_failure(&self.subject_key, lockout).await?;
+        self.ip_attempts = rate_limiter.add_failure(&self.ip_key, lockout).await?;
+        if self.subject_attempts > settings.login_failures_per_username.into()
+            || self.ip_attempts > settings.login_failures_per_ip.into()
+        {
+            // Attempts turned away do not count as failures.
+            self.take_back().await?;
+            return Err(LoginError::Auth(anyhow::anyhow!(
+                "Locked out after too many failed logins."
+            )));
+        }
+        let delay = login_delay(
+            self.subject_attempts - 1,
+            settings.login_failures_before_delay,
+        );
+        if !delay.is_zero() {
+            tracing::info!(delay_secs = delay.as_secs(), "Delaying the login attempt.");
+            actix_web::rt::time::sleep(delay).await;
+        }
+        Ok(())
+    }
+
+    /// The failure was counted by `throttle` already.
+    pub fn failed(&self) {
+        let settings = self.rate_limiter.settings();
+        for (key, attempts, limit) in [
+            (
+                &self.subject_key,
+                self.subject_attempts,
+                settings.login_failures_per_username,
+            ),
+            (
+                &self.ip_key,
+                self.ip_attempts,
+                settings.login_failures_per_ip,
+            ),
+        ] {
+            if attempts == u64::from(limit) {
+                tracing::warn!(
+                    security_event = "login_lockout",
+                    lockout_key = %key,
+                    lockout_minutes = settings.login_lockout_minutes,
+                    "Too many failed logins, locking out further attempts."
+                );
+            }
+        }
+    }
+
+    /// The credentials could not be checked, which is not held against
+    /// anyone, so that an outage does not lock users out.
+    pub async fn unexpected(&self, e: anyhow::Error) -> LoginError {
+        if let Err(take_back_error) = self.take_back().await {
+            tracing::error!(
+                error.cause_chain = ?take_back_error,
+                "Failed to take back the login attempt."
+            );
+        }
+        LoginError::Unexpected(e)
+    }
+
+    async fn take_back(&self) -> Result<(), LoginError> {
+        self.rate_limiter.remove_failure(&self.subject_key).await?;
+        self.rate_limiter.remove_failure(&self.ip_key).await?;
+        Ok(())
+    }
+
+    /// Forgets the failures of the subject, but not of the address, which may
+    /// still be guessing at other accounts.
+    pub async fn succeeded(&self) -> Result<(), LoginError> {
+        self.rate_limiter.clear_failures(&self.subject_key).await?;
+        self.rate_limiter.remove_failure(&self.ip_key).await?;
+        Ok(())
+    }
+}
+
+/// Doubles with every failure past `failures_before_delay`.
+fn login_delay(failures: u64, failures_before_delay: u32) -> Duration {
+    match failures.checked_sub(failures_before_delay.into()) {
+        None => Duration::ZERO,
+        Some(n) => Duration::from_secs(1u64 << n.min(16)).min(MAX_LOGIN_DELAY),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{login_delay, MAX_LOGIN_DELAY};
+
+    #[test]
+    fn the_first_failures_are_not_delayed() {
+        assert_eq!(login_delay(0, 3), Duration::ZERO);
+        assert_eq!(login_delay(2, 3), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_up_to_the_maximum() {
+        assert_eq!(login_delay(3, 3), Duration::from_secs(1));
+        assert_eq!(login_delay(4, 3), Duration::from_secs(2));
+        assert_eq!(login_delay(5, 3), Duration::from_secs(4));
+        assert_eq!(login_delay(9, 3), MAX_LOGIN_DELAY);
+        assert_eq!(login_delay(u64::MAX, 0), MAX_LOGIN_DELAY);
+    }
+}
@@ -1,10 +1,17 @@
-use actix_web::{error::InternalError, http::StatusCode, web, HttpResponse, ResponseError};
+use actix_web::{
+    error::InternalError, http::StatusCode, web, HttpRequest, HttpResponse, ResponseError,
+};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
+use uuid::Uuid;
 
+use super::attempts::LoginAttempts;
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    authentication::{
+        get_session_epoch, has_two_factor, validate_credentials, AuthError, Credentials,
+    },
+    rate_limiter::RateLimiter,
     routes::error_chain_fmt,
     session_state::TypedSession,
     utils::see_other,
@@ -17,42 +24,78 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(request, form, pool, session, rate_limiter),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty
 ))]
 pub async fn login(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    rate_limiter: web::Data<RateLimiter>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username,
         password: form.0.password,
     };
 
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+
+    let mut attempts = LoginAttempts::for_username(&rate_limiter, &request, &credentials.username);
+    attempts.throttle().await.map_err(login_redirect)?;
 
     match validate_credentials(credentials, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            attempts.succeeded().await.map_err(login_redirect)?;
 
-            session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::Unexpected(e.into())))?;
+            let two_factor = has_two_factor(&pool, user_id)
+                .await
+                .map_err(|e| login_redirect(LoginError::Unexpected(e)))?;
+            if two_factor {
+                session.renew();
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::Unexpected(e.into())))?;
+                return Ok(see_other("/login/two-factor"));
+            }
+            log_in(&session, &pool, user_id)
+                .await
+                .map_err(login_redirect)?;
             Ok(see_other("/admin/dashboard"))
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::Auth(e.into()),
-                AuthError::Unexpected(_) => LoginError::Unexpected(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    attempts.failed();
+                    LoginError::Auth(e.into())
+                }
+                AuthError::Unexpected(_) => attempts.unexpected(e.into()).await,
             };
             Err(login_redirect(e))
         }
     }
 }
 
-fn login_redirect(e: LoginError) -> InternalError<LoginError> {
+/// Starts the session of a user who got past every factor.
+pub(super) async fn log_in(
+    session: &TypedSession,
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<(), LoginError> {
+    let session_epoch = get_session_epoch(pool, user_id).await?;
+    session.renew();
+    session.remove_pending_user_id();
+    session
+        .insert_user_id(user_id)
+        .map_err(|e| LoginError::Unexpected(e.into()))?;
+    session
+        .insert_session_epoch(session_epoch)
+        .map_err(|e| LoginError::Unexpected(e.into()))?;
+    Ok(())
+}
+
+pub(super) fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
     InternalError::from_response(e, see_other("/login"))
 }
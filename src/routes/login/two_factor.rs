@@ -0,0 +1,126 @@
+use actix_web::{error::InternalError, http::header::ContentType, web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+
+use super::{
+    attempts::LoginAttempts,
+    post::{log_in, login_redirect, LoginError},
+};
+use crate::{
+    authentication::{get_totp_secret, verify_second_factor, TotpCipher},
+    rate_limiter::RateLimiter,
+    session_state::TypedSession,
+    utils::{e500, html_messages, see_other},
+};
+
+/// Wrong codes allowed before the password has to be entered again.
+const MAX_SECOND_FACTOR_FAILURES: u32 = 5;
+
+#[tracing::instrument(skip(flash_messages, session))]
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let msg_html = html_messages(&flash_messages);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Two-factor authentication</title>
+    </head>
+    <body>
+        {msg_html}
+        <form method="post" action="/login/two-factor">
+            <label>Code from your authenticator app, or a recovery code
+                <input type="text" placeholder="Enter code" name="code" autocomplete="one-time-code" />
+            </label>
+            <button type="submit">Verify</button>
+        </form>
+        <p><a href="/login">&lt;- Back</a></p>
+    </body>
+</html>"#
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    skip(request, form, pool, cipher, session, rate_limiter),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn verify_two_factor(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    cipher: web::Data<Option<TotpCipher>>,
+    session: TypedSession,
+    rate_limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let user_id = match session
+        .get_pending_user_id()
+        .map_err(|e| login_redirect(LoginError::Unexpected(e.into())))?
+    {
+        Some(user_id) => user_id,
+        None => return Ok(see_other("/login")),
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let mut attempts = LoginAttempts::for_second_factor(&rate_limiter, &request, user_id);
+    attempts.throttle().await.map_err(|e| {
+        session.logout();
+        login_redirect(e)
+    })?;
+
+    let cipher = cipher.get_ref().as_ref().ok_or_else(|| {
+        login_redirect(LoginError::Unexpected(anyhow::anyhow!(
+            "No TOTP encryption key is configured."
+        )))
+    })?;
+    let secret = match get_totp_secret(&pool, cipher, user_id).await {
+        Ok(secret) => secret,
+        Err(e) => return Err(login_redirect(attempts.unexpected(e).await)),
+    }
+    .ok_or_else(|| {
+        session.logout();
+        login_redirect(LoginError::Auth(anyhow::anyhow!(
+            "Two-factor authentication was disabled in the meantime."
+        )))
+    })?;
+    let verified = match verify_second_factor(&pool, &secret, user_id, &form.code).await {
+        Ok(verified) => verified,
+        Err(e) => return Err(login_redirect(attempts.unexpected(e).await)),
+    };
+    if verified {
+        attempts.succeeded().await.map_err(login_redirect)?;
+        log_in(&session, &pool, user_id)
+            .await
+            .map_err(login_redirect)?;
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    attempts.failed();
+    let failures = session
+        .add_second_factor_failure()
+        .map_err(|e| login_redirect(LoginError::Unexpected(e.into())))?;
+    let e = LoginError::Auth(anyhow::anyhow!("Invalid second factor code."));
+    if failures >= MAX_SECOND_FACTOR_FAILURES {
+        tracing::warn!("Too many invalid second factor codes, starting over.");
+        session.logout();
+        return Err(login_redirect(e));
+    }
+    FlashMessage::error(e.to_string()).send();
+    Err(InternalError::from_response(
+        e,
+        see_other("/login/two-factor"),
+    ))
+}
@@ -1,16 +1,26 @@
 mod admin;
+mod email_webhook;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_unsubscribe;
 
 pub use admin::*;
+pub use email_webhook::*;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data::*;
+pub use subscriptions_unsubscribe::*;
 
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
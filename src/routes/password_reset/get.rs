@@ -0,0 +1,89 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{verify_reset_link, ResetError};
+use crate::{
+    startup::HmacSecret,
+    utils::{escape_html, html_messages},
+};
+
+#[tracing::instrument(skip(flash_messages))]
+pub async fn password_reset_request_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let msg_html = html_messages(&flash_messages);
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Forgot your password?</title>
+    </head>
+    <body>
+        {msg_html}
+        <p>We will email you a link to choose a new password.</p>
+        <form method="post" action="/password-reset">
+            <label>Username
+                <input type="text" placeholder="Enter username" name="username" />
+            </label>
+            <button type="submit">Send reset link</button>
+        </form>
+        <p><a href="/login">&lt;- Back</a></p>
+    </body>
+</html>"#
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    user_id: Uuid,
+    expires_at: i64,
+    token: String,
+}
+
+#[tracing::instrument(skip(flash_messages, query, pool, hmac_secret), fields(user_id = %query.user_id))]
+pub async fn password_reset_form(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, ResetError> {
+    let QueryParams {
+        user_id,
+        expires_at,
+        token,
+    } = query.0;
+    verify_reset_link(&pool, &hmac_secret.0, &user_id, expires_at, &token).await?;
+    let msg_html = html_messages(&flash_messages);
+    let token = escape_html(&token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Reset password</title>
+    </head>
+    <body>
+        {msg_html}
+        <form method="post" action="/password-reset/new">
+            <input type="hidden" name="user_id" value="{user_id}" />
+            <input type="hidden" name="expires_at" value="{expires_at}" />
+            <input type="hidden" name="token" value="{token}" />
+            <label>New password
+                <input type="password" placeholder="Enter new password" name="new_password" />
+            </label>
+            <label>Confirm new password
+                <input type="password" placeholder="Enter new password again" name="new_password_check" />
+            </label>
+            <button type="submit">Reset password</button>
+        </form>
+    </body>
+</html>"#
+        )))
+}
@@ -0,0 +1,81 @@
+use actix_web::{http::StatusCode, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::PgPool;
+use url::form_urlencoded;
+use uuid::Uuid;
+
+use crate::{domain::PasswordResetToken, routes::error_chain_fmt};
+
+mod get;
+pub use get::{password_reset_form, password_reset_request_form};
+mod post;
+pub use post::{request_password_reset, reset_password};
+
+/// How long a reset link stays valid.
+const RESET_LINK_TTL_MINUTES: i64 = 30;
+
+/// The query string of a reset link.
+fn reset_link_query(user_id: &Uuid, expires_at: i64, token: &str) -> String {
+    form_urlencoded::Serializer::new(String::new())
+        .append_pair("user_id", &user_id.to_string())
+        .append_pair("expires_at", &expires_at.to_string())
+        .append_pair("token", token)
+        .finish()
+}
+
+/// Checks the link was issued by us, for an active user, since their last
+/// password change.
+async fn verify_reset_link(
+    pool: &PgPool,
+    hmac_secret: &Secret<String>,
+    user_id: &Uuid,
+    expires_at: i64,
+    token: &str,
+) -> Result<(), ResetError> {
+    if expires_at <= Utc::now().timestamp() {
+        return Err(ResetError::ExpiredLink);
+    }
+    let token =
+        PasswordResetToken::parse(token.to_string()).map_err(|_| ResetError::InvalidLink)?;
+    let password_hash = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1 AND deactivated_at IS NULL",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the user.")?
+    .map(|row| Secret::new(row.password_hash))
+    .ok_or(ResetError::InvalidLink)?;
+    if !token.verify(user_id, expires_at, &password_hash, hmac_secret) {
+        return Err(ResetError::InvalidLink);
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum ResetError {
+    #[error("This reset link is not valid.")]
+    InvalidLink,
+    #[error("This reset link has expired, ask for a new one.")]
+    ExpiredLink,
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ResetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ResetError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidLink => StatusCode::UNAUTHORIZED,
+            Self::ExpiredLink => StatusCode::FORBIDDEN,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::PgPool;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::{reset_link_query, verify_reset_link, ResetError, RESET_LINK_TTL_MINUTES};
+use crate::{
+    authentication::{change_password, revoke_sessions},
+    domain::{PasswordResetToken, SubscriberEmail},
+    email_client::EmailClient,
+    rate_limiter::RateLimiter,
+    routes::check_new_password,
+    startup::{ApplicationBaseUrl, HmacSecret},
+    utils::{e500, see_other},
+};
+
+/// The window the `password_resets_per_*_per_hour` limits are counted over.
+const RATE_LIMIT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    username: String,
+}
+
+/// Answers the same whether or not the user exists, and sends the email in
+/// the background so that the response time doesn't tell either. For the same
+/// reason, an address which got too many reset emails is skipped silently.
+#[tracing::instrument(
+    skip(request, form, pool, email_client, base_url, hmac_secret, rate_limiter),
+    fields(username = %form.username)
+)]
+pub async fn request_password_reset(
+    request: HttpRequest,
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    rate_limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let limits = rate_limiter.settings();
+    let client_ip = rate_limiter.client_ip(&request);
+    if !rate_limiter
+        .hit(
+            &format!("password_reset:ip:{}", client_ip),
+            limits.password_resets_per_ip_per_hour,
+            RATE_LIMIT_WINDOW,
+        )
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("Too many password reset requests, try again later.").send();
+        return Ok(see_other("/password-reset"));
+    }
+
+    let user = sqlx::query!(
+        r#"
+        SELECT user_id, email AS "email!", password_hash
+        FROM users
+        WHERE username = $1 AND deactivated_at IS NULL AND email IS NOT NULL
+        "#,
+        form.username.trim()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch the user.")
+    .map_err(e500)?;
+
+    match user {
+        Some(user)
+            if !rate_limiter
+                .hit(
+                    &format!("password_reset:email:{}", user.email.to_lowercase()),
+                    limits.password_resets_per_email_per_hour,
+                    RATE_LIMIT_WINDOW,
+                )
+                .await
+                .map_err(e500)? =>
+        {
+            tracing::warn!("Too many reset emails were sent to this address, skipping it.");
+        }
+        Some(user) => {
+            let expires_at =
+                (Utc::now() + chrono::Duration::minutes(RESET_LINK_TTL_MINUTES)).timestamp();
+            let token = PasswordResetToken::generate(
+                &user.user_id,
+                expires_at,
+                &Secret::new(user.password_hash),
+                &hmac_secret.0,
+            );
+            let reset_link = format!(
+                "{}/password-reset/new?{}",
+                base_url.0,
+                reset_link_query(&user.user_id, expires_at, token.as_ref())
+            );
+            let email_client = email_client.clone();
+            tokio::spawn(
+                async move {
+                    if let Err(e) = send_reset_email(&email_client, user.email, &reset_link).await {
+                        tracing::error!(error.cause_chain = ?e, "Failed to send the reset email.");
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+        None => tracing::info!("No active user with an email address goes by that name."),
+    }
+
+    FlashMessage::info(
+        "If this user has an email address, a link to reset the password was sent to it.",
+    )
+    .send();
+    Ok(see_other("/login"))
+}
+
+async fn send_reset_email(
+    email_client: &EmailClient,
+    recipient: String,
+    reset_link: &str,
+) -> anyhow::Result<()> {
+    let recipient = SubscriberEmail::parse(recipient).map_err(anyhow::Error::msg)?;
+    email_client
+        .send_email(
+            &recipient,
+            "Reset your password",
+            &format!(
+                "Click <a href=\"{}\">here</a> to choose a new password. \
+                The link is valid for {} minutes.<br/>\
+                If you did not ask for it, you can ignore this email.",
+                reset_link, RESET_LINK_TTL_MINUTES
+            ),
+            &format!(
+                "Visit {} to choose a new password. \
+                The link is valid for {} minutes.\n\
+                If you did not ask for it, you can ignore this email.",
+                reset_link, RESET_LINK_TTL_MINUTES
+            ),
+        )
+        .await
+        .context("Failed to send the reset email.")?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    user_id: Uuid,
+    expires_at: i64,
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+/// Logs the user out of every session they had.
+#[tracing::instrument(skip(form, pool, hmac_secret), fields(user_id = %form.user_id))]
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, ResetError> {
+    let ResetFormData {
+        user_id,
+        expires_at,
+        token,
+        new_password,
+        new_password_check,
+    } = form.0;
+    verify_reset_link(&pool, &hmac_secret.0, &user_id, expires_at, &token).await?;
+    if let Err(e) = check_new_password(&new_password, &new_password_check) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&format!(
+            "/password-reset/new?{}",
+            reset_link_query(&user_id, expires_at, &token)
+        )));
+    }
+
+    change_password(&user_id, &new_password, &pool).await?;
+    revoke_sessions(pool.get_ref(), user_id).await?;
+
+    FlashMessage::info("Your password has been reset, you can log in now.").send();
+    Ok(see_other("/login"))
+}
@@ -1,13 +1,20 @@
-use actix_web::{web, http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
 use chrono::Utc;
-use sqlx::{Acquire, PgPool, Postgres, Transaction};
+use sqlx::{Acquire, PgExecutor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionToken},
+    configuration::SubscriptionSettings,
+    domain::{
+        EmailDomainBlocklist, ListSlug, NewSubscriber, SubscriberEmail, SubscriberLocale,
+        SubscriberName, SubscriberTag, SubscriptionToken,
+    },
     email_client::EmailClient,
+    negotiation::{negotiate_error, wants_json, ErrorBody, JsonOrForm},
+    rate_limiter::RateLimiter,
     startup::ApplicationBaseUrl,
+    suppressions::is_suppressed,
 };
 
 use crate::routes::error_chain_fmt;
@@ -16,16 +23,79 @@ use crate::routes::error_chain_fmt;
 pub struct SubscribeForm {
     email: String,
     name: String,
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(default)]
+    locale: String,
+    /// Comma separated
+    #[serde(default)]
+    tags: String,
+    /// Honeypot: hidden from humans, so only bots fill it in.
+    #[serde(default)]
+    website: String,
 }
 
-impl TryFrom<SubscribeForm> for NewSubscriber {
-    type Error = String;
+fn default_list() -> String {
+    ListSlug::DEFAULT.to_string()
+}
 
-    fn try_from(form: SubscribeForm) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(form.name)?;
-        let email = SubscriberEmail::parse(form.email)?;
+impl SubscribeForm {
+    fn parse(
+        self,
+        blocked_domains: &EmailDomainBlocklist,
+    ) -> Result<NewSubscriber, ValidationError> {
+        let name = SubscriberName::parse(self.name).map_err(ValidationError::invalid("name"))?;
+        let email =
+            SubscriberEmail::parse(self.email).map_err(ValidationError::invalid("email"))?;
+        blocked_domains
+            .check(&email)
+            .map_err(|e| ValidationError::new("email", "blocked_domain", e))?;
+        let list = ListSlug::parse(self.list).map_err(ValidationError::invalid("list"))?;
+        let locale = match self.locale.trim() {
+            "" => None,
+            locale => Some(
+                SubscriberLocale::parse(locale.to_string())
+                    .map_err(ValidationError::invalid("locale"))?,
+            ),
+        };
+        let tags =
+            SubscriberTag::parse_list(&self.tags).map_err(ValidationError::invalid("tags"))?;
 
-        Ok(NewSubscriber { name, email })
+        Ok(NewSubscriber {
+            name,
+            email,
+            list,
+            locale,
+            tags,
+        })
+    }
+}
+
+/// A rejected form field.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(field: &'static str, code: &'static str, message: String) -> Self {
+        Self {
+            field,
+            code,
+            message,
+        }
+    }
+
+    fn invalid(field: &'static str) -> impl FnOnce(String) -> Self {
+        move |message| Self::new(field, "invalid", message)
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.message.fmt(f)
     }
 }
 
@@ -50,26 +120,118 @@ impl std::fmt::Display for StoreTokenError {
     }
 }
 
+const RATE_LIMIT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 #[tracing::instrument(
-    skip(form, pool, base_url),
+    skip(request, body, pool, base_url, settings, rate_limiter),
     fields(
-        subscriber_name = %form.name,
-        subscriber_email = %form.email
+        subscriber_name = %body.0.name,
+        subscriber_email = %body.0.email
     )
 )]
 pub async fn subscribe(
-    form: web::Form<SubscribeForm>,
+    request: HttpRequest,
+    body: JsonOrForm<SubscribeForm>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
-) -> Result<HttpResponse, SubscribeError> {
-    let subscriber: NewSubscriber = form.0.try_into().map_err(SubscribeError::Validation)?;
+    settings: web::Data<SubscriptionSettings>,
+    rate_limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match try_subscribe(
+        &request,
+        body.0,
+        &pool,
+        &email_client,
+        &base_url.0,
+        &settings,
+        &rate_limiter,
+    )
+    .await
+    {
+        // The same body whatever happened, so it does not tell who subscribed.
+        Ok(()) if wants_json(&request) => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "accepted" })))
+        }
+        Ok(()) => Ok(HttpResponse::Ok().finish()),
+        Err(e) => {
+            let body = e.error_body();
+            Err(negotiate_error(&request, e, body))
+        }
+    }
+}
+
+async fn try_subscribe(
+    request: &HttpRequest,
+    form: SubscribeForm,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    settings: &SubscriptionSettings,
+    rate_limiter: &RateLimiter,
+) -> Result<(), SubscribeError> {
+    // Pretend everything went fine, so bots have no reason to try again.
+    if !form.website.is_empty() {
+        tracing::warn!("The honeypot field was filled in, ignoring the subscription.");
+        return Ok(());
+    }
+
+    let limits = rate_limiter.settings();
+    let client_ip = rate_limiter.client_ip(request);
+    if !rate_limiter
+        .hit(
+            &format!("subscribe:ip:{}", client_ip),
+            limits.subscriptions_per_ip_per_hour,
+            RATE_LIMIT_WINDOW,
+        )
+        .await?
+    {
+        return Err(SubscribeError::TooManyRequests);
+    }
+
+    let subscriber = form
+        .parse(&settings.blocked_email_domains)
+        .map_err(SubscribeError::Validation)?;
+
+    if !rate_limiter
+        .hit(
+            &format!(
+                "subscribe:email:{}",
+                subscriber.email.as_ref().to_lowercase()
+            ),
+            limits.subscriptions_per_email_per_hour,
+            RATE_LIMIT_WINDOW,
+        )
+        .await?
+    {
+        return Err(SubscribeError::TooManyRequests);
+    }
+
+    // Same as the honeypot, whether an address is suppressed is nobody's business.
+    if is_suppressed(pool, subscriber.email.as_ref())
+        .await
+        .context("Failed to check whether the address is suppressed.")?
+    {
+        tracing::warn!("The address is suppressed, ignoring the subscription.");
+        return Ok(());
+    }
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a postgres connection from the pool.")?;
 
+    let list_id = get_list_id(&mut transaction, &subscriber.list)
+        .await
+        .context("Failed to look up the mailing list.")?
+        .ok_or_else(|| {
+            SubscribeError::Validation(ValidationError::new(
+                "list",
+                "unknown_list",
+                format!("{} is not a known list", subscriber.list.as_ref()),
+            ))
+        })?;
+
     let existing_subscriber_res = sqlx::query!(
         r#"SELECT id FROM subscriptions WHERE email = $1"#,
         subscriber.email.as_ref()
@@ -77,50 +239,78 @@ pub async fn subscribe(
     .fetch_one(&mut transaction)
     .await;
 
-    let subscription_token = match existing_subscriber_res {
-        Ok(sub) => {
-            let row = sqlx::query!(
-                "SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1",
-                sub.id
-            )
-            .fetch_one(&mut transaction)
-            .await
-            .context("Failed to fetch existing subscriber's token.")?;
-            Ok(row.subscription_token)
-        }
+    let subscriber_id = match existing_subscriber_res {
+        Ok(sub) => Ok(sub.id),
         Err(sqlx::Error::RowNotFound) => {
             let subscriber_id = insert_subscriber(&mut transaction, &subscriber)
                 .await
                 .context("Failed to insert a new subscriber in the database.")?;
-
-            let subscription_token = SubscriptionToken::generate();
-
-            store_token(&mut transaction, subscriber_id, subscription_token.as_ref())
-                .await
-                .context("Failed to store the confirmation token for a new subscriber.")?;
-
-            transaction
-                .commit()
-                .await
-                .context("Failed to commit the SQL transaction.")?;
-
-            Ok(subscription_token.as_ref().to_string())
+            Ok(subscriber_id)
         }
         Err(e) => Err(e),
     }
     .context("Failed to check if the subscriber is already subscribed.")?;
 
-    send_confirmation_email(&email_client, subscriber, &base_url.0, &subscription_token)
+    let status = add_to_list(&mut transaction, list_id, subscriber_id)
+        .await
+        .context("Failed to add the subscriber to the mailing list.")?;
+    add_tags(&mut transaction, subscriber_id, &subscriber.tags)
+        .await
+        .context("Failed to tag the subscriber.")?;
+
+    // Anyone can submit any address, so every list is confirmed on its own.
+    if status == "confirmed" {
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the SQL transaction.")?;
+        return Ok(());
+    }
+
+    let n_recent_tokens = count_tokens_issued_today(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to count the confirmation emails sent today.")?;
+    if n_recent_tokens >= settings.max_confirmation_emails_per_day.into() {
+        return Err(SubscribeError::TooManyConfirmationEmails);
+    }
+
+    let subscription_token = SubscriptionToken::generate();
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        Some(list_id),
+        subscription_token.as_ref(),
+        settings.confirmation_token_ttl(),
+    )
+    .await
+    .context("Failed to store the confirmation token.")?;
+
+    transaction
+        .commit()
         .await
-        .context("Failed to send confirmation email.")?;
+        .context("Failed to commit the SQL transaction.")?;
+
+    send_confirmation_email(
+        pool,
+        email_client,
+        &subscriber.email,
+        base_url,
+        subscription_token.as_ref(),
+    )
+    .await
+    .context("Failed to send confirmation email.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(())
 }
 
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
-    Validation(String),
+    Validation(ValidationError),
+    #[error("Too many confirmation emails were sent to this address today.")]
+    TooManyConfirmationEmails,
+    #[error("Too many subscription requests, try again later.")]
+    TooManyRequests,
     #[error("transparent")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -131,10 +321,33 @@ impl std::fmt::Debug for SubscribeError {
     }
 }
 
+impl SubscribeError {
+    fn error_body(&self) -> ErrorBody {
+        let (field, code) = match self {
+            Self::Validation(e) => (Some(e.field), e.code),
+            Self::TooManyConfirmationEmails => (None, "too_many_confirmation_emails"),
+            Self::TooManyRequests => (None, "too_many_requests"),
+            Self::UnexpectedError(_) => (None, "unexpected_error"),
+        };
+        let message = match self {
+            Self::UnexpectedError(_) => "Something went wrong".to_string(),
+            e => e.to_string(),
+        };
+        ErrorBody {
+            field,
+            code,
+            message,
+        }
+    }
+}
+
 impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::TooManyConfirmationEmails | Self::TooManyRequests => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
             Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -148,14 +361,15 @@ pub async fn insert_subscriber(
     let subscriber_id = Uuid::new_v4();
     sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, locale)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         subscriber_id,
         form.email.as_ref(),
         form.name.as_ref(),
         Utc::now(),
-        "pending_confirmation"
+        "pending_confirmation",
+        form.locale.as_ref().map(|l| l.as_ref())
     )
     .execute(transaction.acquire().await?)
     .await
@@ -167,17 +381,100 @@ pub async fn insert_subscriber(
     Ok(subscriber_id)
 }
 
+#[tracing::instrument(skip(executor))]
+pub async fn get_list_id<'e>(
+    executor: impl PgExecutor<'e>,
+    list: &ListSlug,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query!("SELECT list_id FROM lists WHERE slug = $1", list.as_ref())
+        .fetch_optional(executor)
+        .await
+        .map(|maybe_row| maybe_row.map(|row| row.list_id))
+}
+
+/// Returns the status of the membership, which stays confirmed if it was,
+/// and otherwise waits for a new confirmation.
+#[tracing::instrument(skip(transaction))]
+async fn add_to_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<String, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at, status)
+        VALUES ($1, $2, now(), 'pending_confirmation')
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = CASE list_subscriptions.status
+            WHEN 'confirmed' THEN 'confirmed'
+            ELSE 'pending_confirmation'
+        END
+        RETURNING status
+        "#,
+        list_id,
+        subscriber_id
+    )
+    .fetch_one(transaction)
+    .await
+    .map(|row| row.status)
+}
+
 #[tracing::instrument(skip(transaction))]
+async fn add_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tags: &[SubscriberTag],
+) -> Result<(), sqlx::Error> {
+    let tags: Vec<_> = tags.iter().map(|t| t.as_ref().to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $1, tag FROM UNNEST($2::text[]) AS tag
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        &tags[..]
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+/// Stores a new confirmation token for `list_id`, or for every list the
+/// subscriber is waiting on, expiring any sent before for the same.
+#[tracing::instrument(skip(transaction, subscription_token))]
 pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Option<Uuid>,
     subscription_token: &str,
+    ttl: chrono::Duration,
 ) -> Result<(), StoreTokenError> {
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscriber_id, subscription_token)
-        VALUES ($1, $2);"#,
+        r#"
+        UPDATE subscription_tokens
+        SET expires_at = LEAST(expires_at, now())
+        WHERE subscriber_id = $1 AND list_id IS NOT DISTINCT FROM $2 AND NOT used
+        "#,
         subscriber_id,
-        subscription_token
+        list_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        StoreTokenError(e)
+    })?;
+
+    let issued_at = Utc::now();
+    sqlx::query!(
+        r#"INSERT INTO subscription_tokens (subscriber_id, subscription_token, issued_at, expires_at, list_id)
+        VALUES ($1, $2, $3, $4, $5);"#,
+        subscriber_id,
+        subscription_token,
+        issued_at,
+        issued_at + ttl,
+        list_id
     )
     .execute(transaction)
     .await
@@ -188,13 +485,41 @@ pub async fn store_token(
     Ok(())
 }
 
-#[tracing::instrument(skip(email_client, subscriber, base_url, subscription_token))]
+#[tracing::instrument(skip(transaction))]
+async fn count_tokens_issued_today(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "n!"
+        FROM subscription_tokens
+        WHERE subscriber_id = $1 AND issued_at > now() - interval '1 day'
+        "#,
+        subscriber_id
+    )
+    .fetch_one(transaction)
+    .await
+    .map(|row| row.n)
+}
+
+/// Suppressed addresses are skipped silently.
+#[tracing::instrument(skip(pool, email_client, recipient, base_url, subscription_token))]
 pub async fn send_confirmation_email(
+    pool: &PgPool,
     email_client: &EmailClient,
-    subscriber: NewSubscriber,
+    recipient: &SubscriberEmail,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
+    if is_suppressed(pool, recipient.as_ref())
+        .await
+        .context("Failed to check whether the address is suppressed.")?
+    {
+        tracing::warn!("The address is suppressed, not sending the confirmation email.");
+        return Ok(());
+    }
+
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token
@@ -202,7 +527,7 @@ pub async fn send_confirmation_email(
 
     email_client
         .send_email(
-            &subscriber.email,
+            recipient,
             "Welcome!",
             &format!(
                 "Welcome to our newsletter!<br/>\
@@ -214,5 +539,6 @@ pub async fn send_confirmation_email(
                 confirmation_link
             ),
         )
-        .await
+        .await?;
+    Ok(())
 }
@@ -0,0 +1,239 @@
+use actix_web::{
+    error::InternalError, http::header::ContentType, http::StatusCode, web, HttpRequest,
+    HttpResponse, ResponseError,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::{instrument, log::error};
+use uuid::Uuid;
+
+use crate::{
+    configuration::SubscriptionSettings,
+    domain::SubscriptionToken,
+    negotiation::{negotiate_error, wants_json, ErrorBody},
+    routes::error_chain_fmt,
+    utils::see_other,
+};
+
+mod pages;
+pub use pages::ConfirmationPages;
+
+#[derive(serde::Deserialize)]
+pub struct Params {
+    subscription_token: String,
+}
+
+impl TryFrom<Params> for SubscriptionToken {
+    type Error = String;
+
+    fn try_from(value: Params) -> Result<Self, Self::Error> {
+        SubscriptionToken::parse(value.subscription_token)
+    }
+}
+
+#[instrument(
+    name = "Confirm a pending subscriber",
+    skip(request, parameters, pages, settings)
+)]
+pub async fn confirm(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    parameters: web::Query<Params>,
+    pages: web::Data<ConfirmationPages>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match try_confirm(&pool, &parameters.subscription_token).await {
+        Ok(_) if wants_json(&request) => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "confirmed" })))
+        }
+        Ok(name) => Ok(match &settings.confirmation_redirect_url {
+            Some(url) => see_other(url),
+            None => html_page(StatusCode::OK, pages.confirmed(&name)),
+        }),
+        Err(e) if wants_json(&request) => {
+            let body = e.error_body();
+            Err(negotiate_error(&request, e, body))
+        }
+        Err(e) => {
+            let page = match e {
+                ConfirmError::UnknownToken => pages.invalid(),
+                ConfirmError::UsedToken => pages.already_confirmed(),
+                ConfirmError::ExpiredToken => pages.expired(),
+                ConfirmError::UnexpectedError(_) => return Err(e.into()),
+            };
+            let response = html_page(e.status_code(), page.to_string());
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+fn html_page(status: StatusCode, page: String) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(page)
+}
+
+/// Returns the name of the confirmed subscriber.
+async fn try_confirm(pool: &PgPool, subscription_token: &str) -> Result<String, ConfirmError> {
+    let token = get_token_info(pool, subscription_token)
+        .await
+        .context("Failed to look up the confirmation token.")?
+        .ok_or(ConfirmError::UnknownToken)?;
+    if token.used {
+        return Err(ConfirmError::UsedToken);
+    }
+    if token.expires_at <= Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
+    confirm_subscriber(pool, token.subscriber_id, token.list_id)
+        .await
+        .context("Failed to confirm the subscriber.")?;
+    Ok(token.name)
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("This confirmation link is not valid.")]
+    UnknownToken,
+    #[error("This confirmation link was already used.")]
+    UsedToken,
+    #[error("This confirmation link has expired, subscribe again to receive a new one.")]
+    ExpiredToken,
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ConfirmError {
+    fn error_body(&self) -> ErrorBody {
+        let (field, code) = match self {
+            Self::UnknownToken => (Some("subscription_token"), "unknown_token"),
+            Self::UsedToken => (Some("subscription_token"), "used_token"),
+            Self::ExpiredToken => (Some("subscription_token"), "expired_token"),
+            Self::UnexpectedError(_) => (None, "unexpected_error"),
+        };
+        ErrorBody {
+            field,
+            code,
+            message: self.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::UsedToken => StatusCode::GONE,
+            Self::ExpiredToken => StatusCode::FORBIDDEN,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Confirms `list_id`, or every list the subscriber is waiting on if `None`.
+#[instrument(skip(pool, subscriber_id))]
+pub async fn confirm_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE list_subscriptions
+        SET status = 'confirmed', confirmed_at = now()
+        WHERE subscriber_id = $1
+            AND ($2::uuid IS NULL OR list_id = $2)
+            AND status = 'pending_confirmation'
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|e| {
+        error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    // Leaving every list unsubscribes, an old link must not undo that.
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed',
+            confirmed_at = CASE WHEN status = 'confirmed' THEN confirmed_at ELSE now() END
+        WHERE id = $1 AND (
+            EXISTS (
+                SELECT 1 FROM list_subscriptions
+                WHERE subscriber_id = $1 AND status = 'confirmed'
+            )
+            OR NOT EXISTS (SELECT 1 FROM list_subscriptions WHERE subscriber_id = $1)
+        )
+        "#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|e| {
+        error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscription_tokens SET used = TRUE
+        WHERE subscriber_id = $1 AND ($2::uuid IS NULL OR list_id = $2)
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|e| {
+        error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+struct TokenInfo {
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+    name: String,
+    used: bool,
+    expires_at: DateTime<Utc>,
+}
+
+#[instrument(skip(pool, subscription_token))]
+async fn get_token_info(
+    pool: &PgPool,
+    subscription_token: &str,
+) -> Result<Option<TokenInfo>, sqlx::Error> {
+    sqlx::query_as!(
+        TokenInfo,
+        r#"
+        SELECT t.subscriber_id, t.list_id, s.name, t.used, t.expires_at
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1
+        "#,
+        subscription_token
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Failed to execute query: {:?}", e);
+        e
+    })
+}
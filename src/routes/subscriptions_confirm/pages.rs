@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use crate::utils::escape_html;
+
+/// Pages shown after clicking a confirmation link.
+///
+/// Each one can be replaced by a file with the same name in the templates
+/// directory. In the confirmed page `{{ name }}` is replaced with the
+/// subscriber's name.
+#[derive(Clone, Debug)]
+pub struct ConfirmationPages {
+    confirmed: String,
+    already_confirmed: String,
+    invalid: String,
+    expired: String,
+}
+
+impl Default for ConfirmationPages {
+    fn default() -> Self {
+        Self {
+            confirmed: include_str!("confirmed.html").to_string(),
+            already_confirmed: include_str!("already_confirmed.html").to_string(),
+            invalid: include_str!("invalid.html").to_string(),
+            expired: include_str!("expired.html").to_string(),
+        }
+    }
+}
+
+impl ConfirmationPages {
+    /// Loads the templates found in `directory`, missing ones keep the default.
+    pub fn load(directory: Option<&Path>) -> Result<Self, std::io::Error> {
+        let mut pages = Self::default();
+        let directory = match directory {
+            Some(directory) => directory,
+            None => return Ok(pages),
+        };
+        for (file_name, page) in [
+            ("confirmed.html", &mut pages.confirmed),
+            ("already_confirmed.html", &mut pages.already_confirmed),
+            ("invalid.html", &mut pages.invalid),
+            ("expired.html", &mut pages.expired),
+        ] {
+            match std::fs::read_to_string(directory.join(file_name)) {
+                Ok(template) => *page = template,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(pages)
+    }
+
+    pub fn confirmed(&self, name: &str) -> String {
+        self.confirmed.replace("{{ name }}", &escape_html(name))
+    }
+
+    pub fn already_confirmed(&self) -> &str {
+        &self.already_confirmed
+    }
+
+    pub fn invalid(&self) -> &str {
+        &self.invalid
+    }
+
+    pub fn expired(&self) -> &str {
+        &self.expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfirmationPages;
+    use uuid::Uuid;
+
+    #[test]
+    fn the_subscriber_name_is_escaped() {
+        let pages = ConfirmationPages::default();
+        let page = pages.confirmed("<b>Ursula</b>");
+        assert!(page.contains("&lt;b&gt;Ursula&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn missing_templates_keep_the_default() {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir(&directory).unwrap();
+        std::fs::write(
+            directory.join("confirmed.html"),
+            "Welcome aboard {{ name }}",
+        )
+        .unwrap();
+
+        let pages = ConfirmationPages::load(Some(&directory)).unwrap();
+
+        assert_eq!(pages.confirmed("Ursula"), "Welcome aboard Ursula");
+        assert!(pages.expired().contains("expired"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
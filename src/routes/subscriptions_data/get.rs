@@ -0,0 +1,71 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use super::{get_unused_token, DataAccessError};
+use crate::utils::{escape_html, html_messages};
+
+#[tracing::instrument(skip(flash_messages))]
+pub async fn data_access_request_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let msg_html = html_messages(&flash_messages);
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Your data</title>
+    </head>
+    <body>
+        {msg_html}
+        <p>We will email you a link to download or erase what we store about you.</p>
+        <form method="post" action="/subscriptions/data/request">
+            <label>Email
+                <input type="email" placeholder="Enter your email" name="email" />
+            </label>
+            <button type="submit">Send link</button>
+        </form>
+    </body>
+</html>"#
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    data_access_token: String,
+}
+
+/// Only shows the choices, so mail scanners opening the link do not use it up.
+#[tracing::instrument(skip(query, pool))]
+pub async fn data_access_form(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, DataAccessError> {
+    get_unused_token(pool.get_ref(), &query.data_access_token).await?;
+    let data_access_token = escape_html(&query.data_access_token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Your data</title>
+    </head>
+    <body>
+        <p>This link works once, ask for a new one to do both.</p>
+        <form method="post" action="/subscriptions/data">
+            <input type="hidden" name="data_access_token" value="{data_access_token}" />
+            <button type="submit">Download my data</button>
+        </form>
+        <form method="post" action="/subscriptions/erase">
+            <input type="hidden" name="data_access_token" value="{data_access_token}" />
+            <button type="submit">Erase my data</button>
+        </form>
+    </body>
+</html>"#
+        )))
+}
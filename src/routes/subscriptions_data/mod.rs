@@ -0,0 +1,99 @@
+use actix_web::{http::StatusCode, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{domain::DataAccessToken, routes::error_chain_fmt};
+
+mod get;
+pub use get::{data_access_form, data_access_request_form};
+mod post;
+pub use post::{erase_subscriber_data, request_data_access, subscriber_data};
+
+/// How long a data access link stays valid.
+const DATA_ACCESS_LINK_TTL_MINUTES: i64 = 30;
+
+struct AccessToken {
+    subscriber_id: Uuid,
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+}
+
+/// The subscriber `data_access_token` was sent to, as long as it can still be used.
+///
+/// Pass a transaction to lock it until it is used.
+async fn get_unused_token<'e>(
+    executor: impl PgExecutor<'e>,
+    data_access_token: &str,
+) -> Result<Uuid, DataAccessError> {
+    let data_access_token = DataAccessToken::parse(data_access_token.to_string())
+        .map_err(|_| DataAccessError::UnknownToken)?;
+    let token = sqlx::query_as!(
+        AccessToken,
+        r#"
+        SELECT subscriber_id, expires_at, used_at
+        FROM data_access_tokens
+        WHERE data_access_token = $1
+        FOR UPDATE
+        "#,
+        data_access_token.as_ref()
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to fetch the data access token.")?
+    .ok_or(DataAccessError::UnknownToken)?;
+    if token.used_at.is_some() {
+        return Err(DataAccessError::UsedToken);
+    }
+    if token.expires_at <= Utc::now() {
+        return Err(DataAccessError::ExpiredToken);
+    }
+    Ok(token.subscriber_id)
+}
+
+/// Returns the subscriber the token was sent to, it cannot be used again once
+/// `transaction` is committed.
+async fn use_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    data_access_token: &str,
+) -> Result<Uuid, DataAccessError> {
+    let subscriber_id = get_unused_token(&mut *transaction, data_access_token).await?;
+    sqlx::query!(
+        "UPDATE data_access_tokens SET used_at = now() WHERE data_access_token = $1",
+        data_access_token
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to use up the data access token.")?;
+    Ok(subscriber_id)
+}
+
+#[derive(thiserror::Error)]
+pub enum DataAccessError {
+    #[error("This link is not valid.")]
+    UnknownToken,
+    #[error("This link was already used, ask for a new one.")]
+    UsedToken,
+    #[error("This link has expired, ask for a new one.")]
+    ExpiredToken,
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for DataAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for DataAccessError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::UsedToken => StatusCode::GONE,
+            Self::ExpiredToken => StatusCode::FORBIDDEN,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::{use_token, DataAccessError, DATA_ACCESS_LINK_TTL_MINUTES};
+use crate::{
+    configuration::SubscriptionSettings,
+    domain::{DataAccessToken, SubscriberEmail},
+    email_client::EmailClient,
+    startup::ApplicationBaseUrl,
+    subscriber_data::{collect_subscriber_data, erase_subscriber},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    email: String,
+}
+
+/// Answers the same whether or not we know the address, and sends the email
+/// in the background so that the response time doesn't tell either.
+#[tracing::instrument(skip(form, pool, email_client, base_url, settings))]
+pub async fn request_data_access(
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match issue_token(&pool, form.0.email.trim(), &settings)
+        .await
+        .map_err(e500)?
+    {
+        Some((recipient, token)) => {
+            let link = format!(
+                "{}/subscriptions/data?data_access_token={}",
+                base_url.0,
+                token.as_ref()
+            );
+            let email_client = email_client.clone();
+            tokio::spawn(
+                async move {
+                    if let Err(e) = send_data_access_email(&email_client, recipient, &link).await {
+                        tracing::error!(error.cause_chain = ?e, "Failed to send the email.");
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+        None => tracing::info!("Not sending a data access link."),
+    }
+
+    FlashMessage::info("If we know this address, a link to your data was sent to it.").send();
+    Ok(see_other("/subscriptions/data/request"))
+}
+
+/// Stores a new token for the subscriber behind `email`, unless we do not
+/// know the address or it was sent too many links today.
+///
+/// Suppressed addresses still get it, they asked for it.
+async fn issue_token(
+    pool: &PgPool,
+    email: &str,
+    settings: &SubscriptionSettings,
+) -> anyhow::Result<Option<(SubscriberEmail, DataAccessToken)>> {
+    let recipient = match SubscriberEmail::parse(email.to_string()) {
+        Ok(recipient) => recipient,
+        Err(_) => return Ok(None),
+    };
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT
+            s.id,
+            (
+                SELECT COUNT(*)
+                FROM data_access_tokens t
+                WHERE t.subscriber_id = s.id AND t.issued_at > now() - interval '1 day'
+            ) AS "n_recent_tokens!"
+        FROM subscriptions s
+        WHERE lower(s.email) = lower($1)
+        "#,
+        recipient.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the subscriber.")?;
+    let subscriber = match subscriber {
+        Some(s) if s.n_recent_tokens < settings.max_confirmation_emails_per_day.into() => s,
+        _ => return Ok(None),
+    };
+    let token = DataAccessToken::generate();
+    store_token(pool, subscriber.id, &token)
+        .await
+        .context("Failed to store the data access token.")?;
+    Ok(Some((recipient, token)))
+}
+
+async fn store_token(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    token: &DataAccessToken,
+) -> Result<(), sqlx::Error> {
+    let issued_at = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO data_access_tokens (data_access_token, subscriber_id, issued_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        token.as_ref(),
+        subscriber_id,
+        issued_at,
+        issued_at + chrono::Duration::minutes(DATA_ACCESS_LINK_TTL_MINUTES)
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn send_data_access_email(
+    email_client: &EmailClient,
+    recipient: SubscriberEmail,
+    link: &str,
+) -> anyhow::Result<()> {
+    email_client
+        .send_email(
+            &recipient,
+            "Your data",
+            &format!(
+                "Click <a href=\"{}\">here</a> to download or erase what we store about you. \
+                The link is valid for {} minutes.<br/>\
+                If you did not ask for it, you can ignore this email.",
+                link, DATA_ACCESS_LINK_TTL_MINUTES
+            ),
+            &format!(
+                "Visit {} to download or erase what we store about you. \
+                The link is valid for {} minutes.\n\
+                If you did not ask for it, you can ignore this email.",
+                link, DATA_ACCESS_LINK_TTL_MINUTES
+            ),
+        )
+        .await
+        .context("Failed to send the data access email.")?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    data_access_token: String,
+}
+
+#[tracing::instrument(name = "Export a subscriber's data", skip(form, pool))]
+pub async fn subscriber_data(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, DataAccessError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    let subscriber_id = use_token(&mut transaction, &form.data_access_token).await?;
+
+    // The token stays usable if collecting the data fails.
+    let data = collect_subscriber_data(&pool, subscriber_id)
+        .await?
+        .ok_or(DataAccessError::UnknownToken)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[tracing::instrument(name = "Erase a subscriber", skip(form, pool))]
+pub async fn erase_subscriber_data(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, DataAccessError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    let subscriber_id = use_token(&mut transaction, &form.data_access_token).await?;
+
+    if !erase_subscriber(&mut transaction, subscriber_id).await? {
+        return Err(DataAccessError::UnknownToken);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Your data</title>
+    </head>
+    <body>
+        <p>Everything we stored about you has been erased.</p>
+    </body>
+</html>"#,
+    ))
+}
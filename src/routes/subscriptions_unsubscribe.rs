@@ -0,0 +1,184 @@
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use secrecy::Secret;
+use sqlx::PgPool;
+use tracing::{instrument, log::error};
+use uuid::Uuid;
+
+use crate::{domain::UnsubscribeToken, startup::HmacSecret, utils::escape_html};
+
+/// Links sent before lists had their own unsubscribe state carry no
+/// `list_id`, and unsubscribe from every list.
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParams {
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+    token: String,
+}
+
+/// Only shows a form, so mail scanners and link prefetchers opening the link
+/// do not unsubscribe anyone.
+#[instrument(name = "Show the unsubscribe form", skip(parameters, hmac_secret))]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParams>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> HttpResponse {
+    let (subscriber_id, list_id, token) = match authenticate(parameters.0, &hmac_secret) {
+        Ok(credentials) => credentials,
+        Err(status) => return HttpResponse::new(status),
+    };
+    let query = escape_html(&unsubscribe_query(&subscriber_id, list_id.as_ref(), &token));
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Unsubscribe</title>
+    </head>
+    <body>
+        <form method="post" action="/subscriptions/unsubscribe?{query}">
+            <p>Do you want to stop receiving this newsletter?</p>
+            <button type="submit">Unsubscribe</button>
+        </form>
+    </body>
+</html>"#
+        ))
+}
+
+/// Submitted by the form, and by mail clients as a RFC 8058 one-click unsubscribe.
+#[instrument(name = "Unsubscribe a subscriber", skip(parameters, hmac_secret))]
+pub async fn unsubscribe(
+    pool: web::Data<PgPool>,
+    parameters: web::Query<UnsubscribeParams>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> HttpResponse {
+    let (subscriber_id, list_id, _) = match authenticate(parameters.0, &hmac_secret) {
+        Ok(credentials) => credentials,
+        Err(status) => return HttpResponse::new(status),
+    };
+
+    match unsubscribe_subscriber(&pool, subscriber_id, list_id).await {
+        Ok(true) => HttpResponse::Ok().content_type(ContentType::html()).body(
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8"/>
+        <title>Unsubscribed</title>
+    </head>
+    <body>
+        <p>You have been unsubscribed.</p>
+    </body>
+</html>"#,
+        ),
+        Ok(false) => HttpResponse::Unauthorized().finish(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn authenticate(
+    parameters: UnsubscribeParams,
+    hmac_secret: &HmacSecret,
+) -> Result<(Uuid, Option<Uuid>, UnsubscribeToken), StatusCode> {
+    let UnsubscribeParams {
+        subscriber_id,
+        list_id,
+        token,
+    } = parameters;
+
+    let token = UnsubscribeToken::parse(token).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !token.verify(&subscriber_id, list_id.as_ref(), &hmac_secret.0) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok((subscriber_id, list_id, token))
+}
+
+/// The link to leave `list_id`, found in every issue sent to that list.
+pub fn unsubscribe_link(
+    base_url: &str,
+    subscriber_id: &Uuid,
+    list_id: &Uuid,
+    hmac_secret: &Secret<String>,
+) -> String {
+    let token = UnsubscribeToken::generate(subscriber_id, Some(list_id), hmac_secret);
+    format!(
+        "{}/subscriptions/unsubscribe?{}",
+        base_url,
+        unsubscribe_query(subscriber_id, Some(list_id), &token)
+    )
+}
+
+fn unsubscribe_query(
+    subscriber_id: &Uuid,
+    list_id: Option<&Uuid>,
+    token: &UnsubscribeToken,
+) -> String {
+    match list_id {
+        Some(list_id) => format!(
+            "subscriber_id={}&list_id={}&token={}",
+            subscriber_id,
+            list_id,
+            token.as_ref()
+        ),
+        None => format!("subscriber_id={}&token={}", subscriber_id, token.as_ref()),
+    }
+}
+
+/// The subscriber only counts as unsubscribed once they left every list.
+#[instrument(skip(pool, subscriber_id))]
+async fn unsubscribe_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let subscriber = sqlx::query!(
+        "SELECT id FROM subscriptions WHERE id = $1 FOR UPDATE",
+        subscriber_id
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    if subscriber.is_none() {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE list_subscriptions
+        SET status = 'unsubscribed'
+        WHERE subscriber_id = $1 AND ($2::uuid IS NULL OR list_id = $2)
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|e| {
+        error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE id = $1 AND NOT EXISTS (
+            SELECT 1 FROM list_subscriptions
+            WHERE subscriber_id = $1 AND status = 'confirmed'
+        )
+        "#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|e| {
+        error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
@@ -9,6 +9,10 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const SESSION_EPOCH_KEY: &'static str = "session_epoch";
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const SECOND_FACTOR_FAILURES_KEY: &'static str = "second_factor_failures";
+    const PENDING_TOTP_SECRET_KEY: &'static str = "pending_totp_secret";
 
     pub fn renew(&self) {
         self.0.renew()
@@ -22,6 +26,52 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    pub fn insert_session_epoch(&self, session_epoch: i32) -> Result<(), serde_json::Error> {
+        self.0.insert(Self::SESSION_EPOCH_KEY, session_epoch)
+    }
+
+    pub fn get_session_epoch(&self) -> Result<Option<i32>, serde_json::Error> {
+        self.0.get(Self::SESSION_EPOCH_KEY)
+    }
+
+    /// The password checked out, the second factor is yet to be.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), serde_json::Error> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, serde_json::Error> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::SECOND_FACTOR_FAILURES_KEY);
+    }
+
+    /// Counts the wrong second factor codes, returning the new total.
+    pub fn add_second_factor_failure(&self) -> Result<u32, serde_json::Error> {
+        let failures = self
+            .0
+            .get::<u32>(Self::SECOND_FACTOR_FAILURES_KEY)?
+            .unwrap_or(0)
+            + 1;
+        self.0.insert(Self::SECOND_FACTOR_FAILURES_KEY, failures)?;
+        Ok(failures)
+    }
+
+    /// The base32 secret being enrolled, until the user confirms it with a code.
+    pub fn insert_pending_totp_secret(&self, secret: &str) -> Result<(), serde_json::Error> {
+        self.0.insert(Self::PENDING_TOTP_SECRET_KEY, secret)
+    }
+
+    pub fn get_pending_totp_secret(&self) -> Result<Option<String>, serde_json::Error> {
+        self.0.get(Self::PENDING_TOTP_SECRET_KEY)
+    }
+
+    pub fn remove_pending_totp_secret(&self) {
+        self.0.remove(Self::PENDING_TOTP_SECRET_KEY);
+    }
+
     pub fn logout(&self) {
         self.0.purge();
     }
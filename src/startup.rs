@@ -9,34 +9,79 @@ use std::net::TcpListener;
 use tracing_actix_web::TracingLogger;
 
 use crate::{
-    configuration::Settings,
+    authentication::{
+        reject_anonymous_users, reject_non_owners, reject_read_only_users, TotpCipher,
+    },
+    configuration::{RateLimitSettings, Settings, SubscriptionSettings},
     email_client::EmailClient,
+    rate_limiter::RateLimiter,
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, health_check, home, login,
-        login_form, logout, publish_newsletter, subscribe, publish_newsletter_form,
-    }, authentication::reject_anonymous_users,
+        accept_invitation, accept_invitation_form, add_suppression, admin_dashboard,
+        cancel_newsletter_issue, change_password, change_password_form, change_role, confirm,
+        create_list, create_user, data_access_form, data_access_request_form, deactivate_user,
+        delete_subscriber, disable_two_factor_authentication, edit_draft_form, email_webhook,
+        enable_two_factor_authentication, erase_subscriber_data, export_subscribers, health_check,
+        home, import_subscribers, import_subscribers_form, invite_user, list_drafts, lists_page,
+        login, login_form, logout, manually_confirm_subscriber, manually_unsubscribe_subscriber,
+        newsletter_issue_report, newsletter_recipients, password_reset_form,
+        password_reset_request_form, preview_newsletter_issue, publish_draft, publish_newsletter,
+        publish_newsletter_form, reactivate_user, remove_suppression, request_data_access,
+        request_password_reset, reschedule_newsletter_issue, resend_confirmation_email,
+        reset_password, save_draft, send_test_newsletter_issue, subscribe, subscriber_data,
+        subscribers_page, suppressions_page, two_factor_form, two_factor_settings, unsubscribe,
+        unsubscribe_form, update_draft, users_page, verify_two_factor, ConfirmationPages,
+    },
 };
 
 pub struct ApplicationBaseUrl(pub String);
 
-#[tracing::instrument(skip(listener,pool,email_client,hmac_secret,redis_uri))]
+/// Size limit of the subscriber CSV import, in bytes.
+const MAX_IMPORT_SIZE: usize = 32 * 1024 * 1024;
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(
+    listener,
+    pool,
+    email_client,
+    hmac_secret,
+    email_webhook_secret,
+    totp_encryption_key,
+    redis_uri,
+    subscriptions,
+    rate_limit
+))]
 pub async fn run(
     listener: TcpListener,
     pool: PgPool,
     email_client: EmailClient,
     base_url: String,
     hmac_secret: Secret<String>,
+    email_webhook_secret: Option<Secret<String>>,
+    totp_encryption_key: Option<Secret<String>>,
     redis_uri: Secret<String>,
+    subscriptions: SubscriptionSettings,
+    rate_limit: RateLimitSettings,
 ) -> anyhow::Result<Server> {
     let db_pool = web::Data::new(pool);
     let email_client = web::Data::new(email_client);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let hmac_data = web::Data::new(HmacSecret(hmac_secret.clone()));
+    let email_webhook_secret = web::Data::new(EmailWebhookSecret(email_webhook_secret));
+    let totp_cipher = web::Data::new(totp_encryption_key.as_ref().map(TotpCipher::new));
+    let confirmation_pages = web::Data::new(
+        ConfirmationPages::load(subscriptions.confirmation_templates_directory.as_deref())
+            .context("Cannot load the confirmation page templates.")?,
+    );
+    let subscriptions = web::Data::new(subscriptions);
+    let rate_limiter = web::Data::new(RateLimiter::new(&redis_uri, rate_limit).await?);
 
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
 
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await.context("Cannot connect to redis.")?;
+    let redis_store = RedisSessionStore::new(redis_uri.expose_secret())
+        .await
+        .context("Cannot connect to redis.")?;
 
     let server = HttpServer::new(move || {
         App::new()
@@ -49,22 +94,147 @@ pub async fn run(
             .route("/", web::get().to(home))
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
+            .route("/login/two-factor", web::get().to(two_factor_form))
+            .route("/login/two-factor", web::post().to(verify_two_factor))
             .route("/health_check", web::get().to(health_check))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/subscriptions/data", web::get().to(data_access_form))
+            .route("/subscriptions/data", web::post().to(subscriber_data))
+            .route(
+                "/subscriptions/data/request",
+                web::get().to(data_access_request_form),
+            )
+            .route(
+                "/subscriptions/data/request",
+                web::post().to(request_data_access),
+            )
+            .route(
+                "/subscriptions/erase",
+                web::post().to(erase_subscriber_data),
+            )
+            .route("/webhooks/email", web::post().to(email_webhook))
+            .route(
+                "/password-reset",
+                web::get().to(password_reset_request_form),
+            )
+            .route("/password-reset", web::post().to(request_password_reset))
+            .route("/password-reset/new", web::get().to(password_reset_form))
+            .route("/password-reset/new", web::post().to(reset_password))
+            .route("/invitations/accept", web::get().to(accept_invitation_form))
+            .route("/invitations/accept", web::post().to(accept_invitation))
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(reject_read_only_users))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/logout", web::post().to(logout))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
+                    .route("/two-factor", web::get().to(two_factor_settings))
+                    .route(
+                        "/two-factor",
+                        web::post().to(enable_two_factor_authentication),
+                    )
+                    .route(
+                        "/two-factor/disable",
+                        web::post().to(disable_two_factor_authentication),
+                    )
+                    .route("/lists", web::get().to(lists_page))
+                    .route("/lists", web::post().to(create_list))
+                    .route("/subscribers", web::get().to(subscribers_page))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .service(
+                        web::resource("/subscribers/import")
+                            // Imports are way larger than the default form limit.
+                            .app_data(web::FormConfig::default().limit(MAX_IMPORT_SIZE))
+                            .route(web::get().to(import_subscribers_form))
+                            .route(web::post().to(import_subscribers)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/confirm",
+                        web::post().to(manually_confirm_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/unsubscribe",
+                        web::post().to(manually_unsubscribe_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/resend",
+                        web::post().to(resend_confirmation_email),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/delete",
+                        web::post().to(delete_subscriber),
+                    )
+                    .route("/suppressions", web::get().to(suppressions_page))
+                    .route("/suppressions", web::post().to(add_suppression))
+                    .route("/suppressions/remove", web::post().to(remove_suppression))
                     .route("/newsletters", web::get().to(publish_newsletter_form))
                     .route("/newsletters", web::post().to(publish_newsletter))
+                    .route("/newsletters/drafts", web::get().to(list_drafts))
+                    .route("/newsletters/drafts", web::post().to(save_draft))
+                    .route(
+                        "/newsletters/recipients",
+                        web::get().to(newsletter_recipients),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}",
+                        web::get().to(newsletter_issue_report),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/schedule",
+                        web::post().to(reschedule_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/cancel",
+                        web::post().to(cancel_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/edit",
+                        web::get().to(edit_draft_form),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/edit",
+                        web::post().to(update_draft),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/publish",
+                        web::post().to(publish_draft),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/preview",
+                        web::get().to(preview_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/test",
+                        web::post().to(send_test_newsletter_issue),
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(reject_non_owners))
+                            .route("", web::get().to(users_page))
+                            .route("", web::post().to(create_user))
+                            .route("/invitations", web::post().to(invite_user))
+                            .route("/{user_id}/role", web::post().to(change_role))
+                            .route("/{user_id}/deactivate", web::post().to(deactivate_user))
+                            .route("/{user_id}/reactivate", web::post().to(reactivate_user)),
+                    ),
             )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(hmac_data.clone())
+            .app_data(email_webhook_secret.clone())
+            .app_data(totp_cipher.clone())
+            .app_data(subscriptions.clone())
+            .app_data(confirmation_pages.clone())
+            .app_data(rate_limiter.clone())
     })
     .listen(listener)
     .context("Cannot start HTTP server.")?
@@ -76,6 +246,9 @@ pub async fn run(
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+#[derive(Clone)]
+pub struct EmailWebhookSecret(pub Option<Secret<String>>);
+
 pub struct Application {
     pub port: u16,
     pub server: Server,
@@ -85,6 +258,8 @@ impl Application {
     pub async fn build(configuration: Settings) -> anyhow::Result<Self> {
         let pool = get_connection_pool(configuration.database_url.expose_secret());
 
+        let email_webhook_secret = configuration.email_client.webhook_secret.clone();
+        let totp_encryption_key = configuration.application.totp_encryption_key.clone();
         let email_client = configuration.email_client.client();
 
         let listener = TcpListener::bind(&configuration.application.address).unwrap_or_else(|_| {
@@ -100,7 +275,11 @@ impl Application {
             email_client,
             configuration.application.base_url,
             configuration.application.hmac_secret,
+            email_webhook_secret,
+            totp_encryption_key,
             configuration.redis_uri,
+            configuration.subscriptions,
+            configuration.rate_limit,
         )
         .await?;
 
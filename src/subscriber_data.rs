@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::suppressions::unsuppress;
+
+/// What is kept of an erased address: enough to recognise it, not to read it.
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+/// Everything stored about a subscriber, as answered to a data access request.
+#[tracing::instrument(skip(pool))]
+pub async fn collect_subscriber_data(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    let subscription = match sqlx::query!(
+        r#"
+        SELECT email, name, status, locale, subscribed_at, confirmed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the subscription.")?
+    {
+        Some(subscription) => subscription,
+        None => return Ok(None),
+    };
+
+    let lists = sqlx::query!(
+        r#"
+        SELECT l.slug, l.name, ls.status, ls.subscribed_at, ls.confirmed_at
+        FROM list_subscriptions ls
+        JOIN lists l ON l.list_id = ls.list_id
+        WHERE ls.subscriber_id = $1
+        ORDER BY ls.subscribed_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the lists.")?;
+    let tags: Vec<_> = sqlx::query!(
+        "SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag",
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the tags.")?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT i.title, d.attempted_at, d.status_code, d.provider_message_id, d.error
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_email = $1
+        ORDER BY d.attempted_at
+        "#,
+        subscription.email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the delivery history.")?;
+    let events = sqlx::query!(
+        r#"
+        SELECT event, description, received_at
+        FROM delivery_events
+        WHERE lower(subscriber_email) = lower($1)
+        ORDER BY received_at
+        "#,
+        subscription.email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the delivery events.")?;
+    let queued = sqlx::query!(
+        r#"
+        SELECT i.title, q.n_retries, q.execute_after
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+        WHERE q.subscriber_email = $1
+        "#,
+        subscription.email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the queued deliveries.")?;
+    let failures = sqlx::query!(
+        r#"
+        SELECT i.title, f.n_retries, f.last_error, f.failed_at
+        FROM issue_delivery_failures f
+        JOIN newsletter_issues i ON i.newsletter_issue_id = f.newsletter_issue_id
+        WHERE f.subscriber_email = $1
+        "#,
+        subscription.email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the failed deliveries.")?;
+
+    Ok(Some(json!({
+        "subscription": {
+            "id": subscriber_id,
+            "email": subscription.email,
+            "name": subscription.name,
+            "status": subscription.status,
+            "locale": subscription.locale,
+            "subscribed_at": subscription.subscribed_at.to_rfc3339(),
+            "confirmed_at": subscription.confirmed_at.map(|t| t.to_rfc3339()),
+        },
+        "lists": lists.iter().map(|l| json!({
+            "slug": l.slug,
+            "name": l.name,
+            "status": l.status,
+            "subscribed_at": l.subscribed_at.to_rfc3339(),
+            "confirmed_at": l.confirmed_at.map(|t| t.to_rfc3339()),
+        })).collect::<Vec<_>>(),
+        "tags": tags,
+        "deliveries": deliveries.iter().map(|d| json!({
+            "newsletter_issue": d.title,
+            "attempted_at": d.attempted_at.to_rfc3339(),
+            "status_code": d.status_code,
+            "provider_message_id": d.provider_message_id,
+            "error": d.error,
+        })).collect::<Vec<_>>(),
+        "delivery_events": events.iter().map(|e| json!({
+            "event": e.event,
+            "description": e.description,
+            "received_at": e.received_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "queued_deliveries": queued.iter().map(|q| json!({
+            "newsletter_issue": q.title,
+            "n_retries": q.n_retries,
+            "execute_after": q.execute_after.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "failed_deliveries": failures.iter().map(|f| json!({
+            "newsletter_issue": f.title,
+            "n_retries": f.n_retries,
+            "last_error": f.last_error,
+            "failed_at": f.failed_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+    })))
+}
+
+/// Hard-deletes the subscriber and their delivery history, leaving only the
+/// hash of their address behind so that imports can't bring them back.
+///
+/// Returns `false` if there is no such subscriber.
+#[tracing::instrument(skip(transaction))]
+pub async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> anyhow::Result<bool> {
+    let email = match sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1 FOR UPDATE",
+        subscriber_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch the subscriber.")?
+    {
+        Some(r) => r.email,
+        None => return Ok(false),
+    };
+
+    sqlx::query!(
+        r#"
+        DELETE FROM delivery_events
+        WHERE
+            lower(subscriber_email) = lower($1) OR
+            newsletter_delivery_id IN (
+                SELECT newsletter_delivery_id FROM newsletter_deliveries WHERE subscriber_email = $1
+            )
+        "#,
+        email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the delivery events.")?;
+    sqlx::query!(
+        "DELETE FROM newsletter_deliveries WHERE subscriber_email = $1",
+        email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the delivery history.")?;
+    sqlx::query!(
+        "DELETE FROM issue_delivery_failures WHERE subscriber_email = $1",
+        email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the failed deliveries.")?;
+    remove_subscriber(transaction, subscriber_id, &email)
+        .await
+        .context("Failed to delete the subscriber.")?;
+    // The hash recorded below keeps the address suppressed.
+    unsuppress(&mut *transaction, &email)
+        .await
+        .context("Failed to delete the suppression.")?;
+    record_erasure(transaction, &email, Utc::now())
+        .await
+        .context("Failed to record the erasure.")?;
+    Ok(true)
+}
+
+async fn record_erasure(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    erased_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO erased_subscribers (email_hash, erased_at)
+        VALUES ($1, $2)
+        ON CONFLICT (email_hash) DO UPDATE SET erased_at = EXCLUDED.erased_at
+        "#,
+        email_hash(email),
+        erased_at
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+/// Deletes the subscriber along with anything still waiting to be sent to them.
+pub async fn remove_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_email = $1",
+        email
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM data_access_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM subscriber_tags WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM list_subscriptions WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!("DELETE FROM subscriptions WHERE id = $1", subscriber_id)
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+/// The hashes, among those of `emails`, of addresses which have been erased.
+pub async fn erased_email_hashes(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let hashes: Vec<_> = emails.iter().map(|e| email_hash(e)).collect();
+    let erased = sqlx::query!(
+        "SELECT email_hash FROM erased_subscribers WHERE email_hash = ANY($1)",
+        &hashes[..]
+    )
+    .fetch_all(transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.email_hash)
+    .collect();
+    Ok(erased)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::email_hash;
+
+    #[test]
+    fn the_hash_ignores_case_and_surrounding_whitespace() {
+        assert_eq!(
+            email_hash("ursula@gmail.com"),
+            email_hash(" Ursula@GMAIL.com ")
+        );
+        assert_ne!(
+            email_hash("ursula@gmail.com"),
+            email_hash("le_guin@gmail.com")
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use uuid::Uuid;
+
+use crate::{
+    domain::{SubscriberEmail, SubscriberName},
+    subscriber_data::{email_hash, erased_email_hashes},
+    suppressions::{normalise_email, suppressed_emails},
+};
+
+/// Rows inserted per statement.
+const BATCH_SIZE: usize = 1000;
+
+const STATUSES: [&str; 3] = ["confirmed", "pending_confirmation", "unsubscribed"];
+
+#[derive(serde::Deserialize)]
+struct CsvRow {
+    email: String,
+    name: String,
+    status: String,
+}
+
+struct ValidRow {
+    line: u64,
+    email: SubscriberEmail,
+    name: SubscriberName,
+    status: &'static str,
+}
+
+/// A CSV row which was not imported, and why.
+#[derive(Debug)]
+pub struct SkippedRow {
+    pub line: u64,
+    pub email: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub n_imported: usize,
+    /// Addresses we already knew, which joined the list.
+    pub n_added_to_list: usize,
+    pub skipped: Vec<SkippedRow>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ImportError {
+    #[error("{0}")]
+    InvalidCsv(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Imports an `email,name,status` CSV into `list_id`, all or nothing.
+///
+/// Invalid rows, suppressed addresses, addresses erased at their owner's
+/// request and addresses already on the list are skipped and reported, they
+/// don't stop the import. Other addresses we already know join the list.
+#[tracing::instrument(skip(pool, csv))]
+pub async fn import_csv(
+    pool: &PgPool,
+    list_id: Uuid,
+    csv: impl Read,
+) -> Result<ImportReport, ImportError> {
+    let (rows, mut skipped) = parse_csv(csv)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection from the pool.")?;
+    let mut n_imported = 0;
+    let mut n_added_to_list = 0;
+    for batch in rows.chunks(BATCH_SIZE) {
+        let emails: Vec<_> = batch.iter().map(|row| row.email.to_string()).collect();
+        let erased = erased_email_hashes(&mut transaction, &emails)
+            .await
+            .context("Failed to look up erased subscribers.")?;
+        let suppressed = suppressed_emails(&mut transaction, &emails)
+            .await
+            .context("Failed to look up suppressed addresses.")?;
+        let mut importable = vec![];
+        for row in batch {
+            let reason = if erased.contains(&email_hash(row.email.as_ref())) {
+                "Erased at the subscriber's request"
+            } else if suppressed.contains(&normalise_email(row.email.as_ref())) {
+                "Suppressed"
+            } else {
+                importable.push(row);
+                continue;
+            };
+            skipped.push(SkippedRow {
+                line: row.line,
+                email: row.email.to_string(),
+                reason: reason.to_string(),
+            });
+        }
+
+        let (inserted, joined) = insert_batch(&mut transaction, list_id, &importable)
+            .await
+            .context("Failed to insert the subscribers.")?;
+        n_imported += inserted.len();
+        for row in importable {
+            if inserted.contains_key(row.email.as_ref()) {
+                continue;
+            }
+            if joined.contains(&row.email.as_ref().to_lowercase()) {
+                n_added_to_list += 1;
+            } else {
+                skipped.push(SkippedRow {
+                    line: row.line,
+                    email: row.email.to_string(),
+                    reason: "Already subscribed".to_string(),
+                });
+            }
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction.")?;
+
+    skipped.sort_by_key(|row| row.line);
+    Ok(ImportReport {
+        n_imported,
+        n_added_to_list,
+        skipped,
+    })
+}
+
+fn parse_csv(csv: impl Read) -> Result<(Vec<ValidRow>, Vec<SkippedRow>), ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv);
+    let headers = reader
+        .headers()
+        .map_err(|e| ImportError::InvalidCsv(format!("Cannot read the CSV header: {}", e)))?
+        .clone();
+    for column in ["email", "name", "status"] {
+        if !headers.iter().any(|h| h == column) {
+            return Err(ImportError::InvalidCsv(format!(
+                "The CSV has no {} column.",
+                column
+            )));
+        }
+    }
+
+    let mut rows = vec![];
+    let mut skipped = vec![];
+    let mut first_seen_on = HashMap::new();
+    for record in reader.records() {
+        let record =
+            record.map_err(|e| ImportError::InvalidCsv(format!("Cannot read the CSV: {}", e)))?;
+        let line = record.position().map_or(0, |p| p.line());
+        let row: CsvRow = match record.deserialize(Some(&headers)) {
+            Ok(row) => row,
+            Err(e) => {
+                skipped.push(SkippedRow {
+                    line,
+                    email: String::new(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let email = row.email.clone();
+        match validate_row(line, row) {
+            Ok(row) => match first_seen_on.get(&row.email.as_ref().to_lowercase()) {
+                Some(first_line) => skipped.push(SkippedRow {
+                    line,
+                    email,
+                    reason: format!("Duplicate of line {}", first_line),
+                }),
+                None => {
+                    first_seen_on.insert(row.email.as_ref().to_lowercase(), line);
+                    rows.push(row);
+                }
+            },
+            Err(reason) => skipped.push(SkippedRow {
+                line,
+                email,
+                reason,
+            }),
+        }
+    }
+    Ok((rows, skipped))
+}
+
+fn validate_row(line: u64, row: CsvRow) -> Result<ValidRow, String> {
+    let email = SubscriberEmail::parse(row.email)?;
+    let name = SubscriberName::parse(row.name)?;
+    let status = STATUSES
+        .into_iter()
+        .find(|s| *s == row.status)
+        .ok_or_else(|| format!("{} is not a valid status", row.status))?;
+    Ok(ValidRow {
+        line,
+        email,
+        name,
+        status,
+    })
+}
+
+/// Returns the ids of the inserted subscribers, by email, and the lowercase
+/// addresses of those we already knew which joined the list.
+///
+/// Addresses we already know, whatever their case, are added to the list too,
+/// but the import cannot confirm them if they have not confirmed themselves.
+async fn insert_batch(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    batch: &[&ValidRow],
+) -> Result<(HashMap<String, Uuid>, HashSet<String>), sqlx::Error> {
+    let lowercase_emails: Vec<_> = batch
+        .iter()
+        .map(|r| r.email.as_ref().to_lowercase())
+        .collect();
+    let existing: HashMap<_, _> = sqlx::query!(
+        r#"
+        SELECT id, lower(email) AS "email!"
+        FROM subscriptions
+        WHERE lower(email) = ANY($1::text[])
+        "#,
+        &lowercase_emails[..]
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|row| (row.email, row.id))
+    .collect();
+
+    let new_rows: Vec<_> = batch
+        .iter()
+        .filter(|r| !existing.contains_key(&r.email.as_ref().to_lowercase()))
+        .collect();
+    let ids: Vec<_> = new_rows.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<_> = new_rows.iter().map(|r| r.email.to_string()).collect();
+    let names: Vec<_> = new_rows
+        .iter()
+        .map(|r| r.name.as_ref().to_string())
+        .collect();
+    let statuses: Vec<_> = new_rows.iter().map(|r| r.status.to_string()).collect();
+
+    let inserted: HashMap<_, _> = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+        SELECT id, email, name, now(), status, CASE WHEN status = 'confirmed' THEN now() END
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[]) AS t(id, email, name, status)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id, email
+        "#,
+        &ids[..],
+        &emails[..],
+        &names[..],
+        &statuses[..]
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|row| (row.email, row.id))
+    .collect();
+
+    let (subscriber_ids, statuses): (Vec<_>, Vec<_>) = batch
+        .iter()
+        .filter_map(|r| {
+            let id = inserted
+                .get(r.email.as_ref())
+                .or_else(|| existing.get(&r.email.as_ref().to_lowercase()))?;
+            Some((*id, r.status.to_string()))
+        })
+        .unzip();
+    let joined: HashSet<_> = sqlx::query!(
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at, status, confirmed_at)
+        SELECT $1, s.id, now(), m.status, CASE WHEN m.status = 'confirmed' THEN now() END
+        FROM UNNEST($2::uuid[], $3::text[]) AS t(subscriber_id, status)
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        CROSS JOIN LATERAL (
+            SELECT CASE
+                WHEN t.status = 'confirmed' AND s.status <> 'confirmed' THEN s.status
+                ELSE t.status
+            END AS status
+        ) m
+        ON CONFLICT (list_id, subscriber_id) DO NOTHING
+        RETURNING subscriber_id
+        "#,
+        list_id,
+        &subscriber_ids[..],
+        &statuses[..]
+    )
+    .fetch_all(transaction)
+    .await?
+    .into_iter()
+    .map(|row| row.subscriber_id)
+    .collect();
+    let joined = existing
+        .into_iter()
+        .filter(|(_, id)| joined.contains(id))
+        .map(|(email, _)| email)
+        .collect();
+
+    Ok((inserted, joined))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_csv;
+
+    #[test]
+    fn valid_rows_are_parsed() {
+        let csv = "email,name,status\nursula@gmail.com,Ursula,confirmed\n";
+        let (rows, skipped) = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_with_their_line() {
+        let csv = "email,name,status\n\
+            not-an-email,Ursula,confirmed\n\
+            ursula@gmail.com,,confirmed\n\
+            le_guin@gmail.com,Le Guin,active\n\
+            ursula@gmail.com,Ursula,confirmed\n\
+            URSULA@gmail.com,Ursula,confirmed\n";
+        let (rows, skipped) = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 1);
+        let lines: Vec<_> = skipped.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![2, 3, 4, 6]);
+        assert_eq!(skipped[3].reason, "Duplicate of line 5");
+    }
+
+    #[test]
+    fn a_missing_column_is_rejected() {
+        let csv = "email,name\nursula@gmail.com,Ursula\n";
+        assert!(parse_csv(csv.as_bytes()).is_err());
+    }
+}
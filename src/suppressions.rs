@@ -0,0 +1,132 @@
+use sqlx::{PgExecutor, Postgres, Transaction};
+use std::collections::HashSet;
+
+use crate::subscriber_data::email_hash;
+
+/// Why an address must not be mailed anymore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuppressionReason {
+    HardBounce,
+    Complaint,
+    Manual,
+}
+
+impl SuppressionReason {
+    pub const ALL: [SuppressionReason; 3] = [Self::HardBounce, Self::Complaint, Self::Manual];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HardBounce => "hard_bounce",
+            Self::Complaint => "complaint",
+            Self::Manual => "manual",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|r| r.as_str() == s)
+            .ok_or_else(|| format!("{} is not a suppression reason", s))
+    }
+}
+
+/// Suppressions are matched regardless of case.
+pub fn normalise_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Whether `email` was suppressed, or erased at its owner's request.
+#[tracing::instrument(skip(executor))]
+pub async fn is_suppressed<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM suppressions WHERE email = $1) OR
+            EXISTS (SELECT 1 FROM erased_subscribers WHERE email_hash = $2)
+            AS "suppressed!"
+        "#,
+        normalise_email(email),
+        email_hash(email)
+    )
+    .fetch_one(executor)
+    .await
+    .map(|row| row.suppressed)
+}
+
+/// The addresses, among `emails` and normalised, which were suppressed.
+pub async fn suppressed_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let emails: Vec<_> = emails.iter().map(|e| normalise_email(e)).collect();
+    let suppressed = sqlx::query!(
+        "SELECT email FROM suppressions WHERE email = ANY($1)",
+        &emails[..]
+    )
+    .fetch_all(transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.email)
+    .collect();
+    Ok(suppressed)
+}
+
+/// Suppresses `email`, dropping anything still queued for it.
+#[tracing::instrument(skip(transaction))]
+pub async fn suppress(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    reason: SuppressionReason,
+) -> Result<(), sqlx::Error> {
+    let email = normalise_email(email);
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, suppressed_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (email) DO UPDATE
+        SET reason = EXCLUDED.reason, suppressed_at = EXCLUDED.suppressed_at
+        "#,
+        email,
+        reason.as_str()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE lower(subscriber_email) = $1",
+        email
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+/// Returns `false` if `email` was not suppressed.
+#[tracing::instrument(skip(executor))]
+pub async fn unsuppress<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM suppressions WHERE email = $1",
+        normalise_email(email)
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionReason;
+
+    #[test]
+    fn reasons_round_trip() {
+        for reason in SuppressionReason::ALL {
+            assert_eq!(SuppressionReason::parse(reason.as_str()), Ok(reason));
+        }
+        assert!(SuppressionReason::parse("soft_bounce").is_err());
+    }
+}
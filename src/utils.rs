@@ -28,6 +28,22 @@ pub fn html_messages(flash_messages: &IncomingFlashMessages) -> String {
         .iter()
         .fold(String::new(), |a, m| {
             tracing::debug!("message: {}", m.content());
-            format!("{}<p><i>{}</i></p>", a, m.content())
+            format!("{}<p><i>{}</i></p>", a, escape_html(m.content()))
         })
 }
+
+/// Escapes text so it can be embedded in HTML, including attribute values.
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,47 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_manage_lists() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_list(&serde_json::json!({ "name": "Rust weekly", "slug": "rust-weekly" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn new_lists_are_created_and_listed() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_list(&serde_json::json!({ "name": "Rust weekly", "slug": "rust-weekly" }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("The list rust-weekly has been created."));
+    assert!(html_page.contains("<tr><td>Rust weekly</td><td>rust-weekly</td><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><td>Newsletter</td><td>newsletter</td><td>0</td></tr>"));
+}
+
+#[actix_web::test]
+async fn list_slugs_must_be_unique_and_valid() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    app.post_list(&serde_json::json!({ "name": "Another newsletter", "slug": "newsletter" }))
+        .await;
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("A list called newsletter already exists."));
+
+    app.post_list(&serde_json::json!({ "name": "Rust weekly", "slug": "Rust Weekly" }))
+        .await;
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("List can only contain lowercase letters, digits and dashes"));
+}
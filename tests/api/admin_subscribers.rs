@@ -0,0 +1,628 @@
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    when_sending_an_email, TestApp,
+};
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_import_subscribers(&serde_json::json!({
+            "csv": "email,name,status\nursula@gmail.com,Ursula,confirmed",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn import_inserts_valid_rows_and_reports_the_others() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    let existing = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    let csv = format!(
+        "email,name,status\n\
+        ursula@gmail.com,Ursula,confirmed\n\
+        not-an-email,Someone,confirmed\n\
+        {},Already Here,confirmed\n\
+        le_guin@gmail.com,Le Guin,pending_confirmation\n\
+        ursula@gmail.com,Ursula,confirmed\n\
+        bob@gmail.com,Bob,gone\n",
+        existing
+    );
+
+    // when
+    let response = app
+        .post_import_subscribers(&serde_json::json!({ "csv": csv }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Imported 2 subscribers into newsletter, skipped 4 rows."));
+    assert!(html_page.contains("<tr><td>3</td><td>not-an-email</td>"));
+    assert!(html_page.contains(&format!(
+        "<tr><td>4</td><td>{}</td><td>Already subscribed</td></tr>",
+        existing
+    )));
+    assert!(html_page.contains("<td>Duplicate of line 2</td>"));
+    assert!(html_page
+        .contains("<tr><td>7</td><td>bob@gmail.com</td><td>gone is not a valid status</td></tr>"));
+
+    let imported = sqlx::query!(
+        r#"
+        SELECT s.email, s.status
+        FROM subscriptions s
+        JOIN list_subscriptions m ON m.subscriber_id = s.id
+        WHERE s.email <> $1
+        ORDER BY s.email
+        "#,
+        existing
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[0].email, "le_guin@gmail.com");
+    assert_eq!(imported[0].status, "pending_confirmation");
+    assert_eq!(imported[1].email, "ursula@gmail.com");
+    assert_eq!(imported[1].status, "confirmed");
+}
+
+#[actix_web::test]
+async fn import_adds_subscribers_to_the_chosen_list() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    app.create_list("rust-weekly").await;
+
+    // when
+    let response = app
+        .post_import_subscribers(&serde_json::json!({
+            "csv": "email,name,status\nursula@gmail.com,Ursula,confirmed",
+            "list": "rust-weekly",
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!(
+        r#"
+        SELECT l.slug
+        FROM list_subscriptions m
+        JOIN lists l ON l.list_id = m.list_id
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.slug, "rust-weekly");
+}
+
+#[actix_web::test]
+async fn import_adds_known_addresses_to_the_list_whatever_their_case() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    app.create_list("rust-weekly").await;
+    create_confirmed_subscriber(&app).await;
+    let existing = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    let csv = format!(
+        "email,name,status\n{},Already Here,confirmed\nursula@gmail.com,Ursula,confirmed",
+        existing.to_uppercase()
+    );
+
+    // when
+    let response = app
+        .post_import_subscribers(&serde_json::json!({ "csv": csv, "list": "rust-weekly" }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Imported 1 subscribers into rust-weekly, skipped 0 rows."));
+    assert!(html_page.contains("Added 1 subscribers we already knew to rust-weekly."));
+    let memberships = sqlx::query!(
+        r#"
+        SELECT s.email, m.status, m.confirmed_at
+        FROM subscriptions s
+        JOIN list_subscriptions m ON m.subscriber_id = s.id
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE l.slug = 'rust-weekly'
+        ORDER BY s.email = $1 DESC
+        "#,
+        existing
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(memberships.len(), 2);
+    assert_eq!(memberships[0].email, existing);
+    assert_eq!(memberships[0].status, "confirmed");
+    assert_eq!(memberships[1].email, "ursula@gmail.com");
+    assert!(memberships[1].confirmed_at.is_some());
+    let confirmed_at =
+        sqlx::query!("SELECT confirmed_at FROM subscriptions WHERE email = 'ursula@gmail.com'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .confirmed_at;
+    assert!(confirmed_at.is_some());
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 2);
+}
+
+#[actix_web::test]
+async fn import_does_not_confirm_known_addresses_which_are_not() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    app.create_list("rust-weekly").await;
+    create_unconfirmed_subscriber(&app).await;
+    let existing = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+
+    // when
+    let response = app
+        .post_import_subscribers(&serde_json::json!({
+            "csv": format!("email,name,status\n{},Already Here,confirmed", existing),
+            "list": "rust-weekly",
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let membership = sqlx::query!(
+        r#"
+        SELECT m.status
+        FROM list_subscriptions m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE l.slug = 'rust-weekly'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(membership.status, "pending_confirmation");
+}
+
+#[actix_web::test]
+async fn import_skips_suppressed_addresses() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, suppressed_at)
+        VALUES ('ursula@gmail.com', 'hard_bounce', now())
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // when
+    let response = app
+        .post_import_subscribers(&serde_json::json!({
+            "csv": "email,name,status\nUrsula@gmail.com,Ursula,confirmed\nle_guin@gmail.com,Le Guin,confirmed",
+        }))
+        .await;
+
+    // then
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Imported 1 subscribers into newsletter, skipped 1 rows."));
+    assert!(html_page.contains("<tr><td>2</td><td>Ursula@gmail.com</td><td>Suppressed</td></tr>"));
+    let emails: Vec<_> = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.email)
+        .collect();
+    assert_eq!(emails, vec!["le_guin@gmail.com"]);
+}
+
+#[actix_web::test]
+async fn import_rejects_a_csv_without_the_required_columns() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_import_subscribers(&serde_json::json!({
+            "csv": "email,name\nursula@gmail.com,Ursula",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+    let html_page = app.get_import_subscribers_html().await;
+    assert!(html_page.contains("The CSV has no status column."));
+    let saved = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_export_subscribers("format=csv").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn subscribers_are_exported_as_csv() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+
+    // when
+    let response = app.get_export_subscribers("format=csv").await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    let csv = response.text().await.unwrap();
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["email", "name", "status", "subscribed_at", "confirmed_at"]
+    );
+    let mut records: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+    records.sort_by(|a, b| a[2].cmp(&b[2]));
+    assert_eq!(records.len(), 2);
+    assert_eq!(&records[0][2], "confirmed");
+    assert!(!records[0][4].is_empty());
+    assert_eq!(&records[1][2], "pending_confirmation");
+    assert!(records[1][4].is_empty());
+}
+
+#[actix_web::test]
+async fn subscribers_are_exported_as_json_filtered_by_status() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+
+    // when
+    let response = app
+        .get_export_subscribers("format=json&status=pending_confirmation")
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let subscribers: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["status"], "pending_confirmation");
+    assert!(subscribers[0]["confirmed_at"].is_null());
+    assert!(subscribers[0]["email"].as_str().unwrap().contains('@'));
+}
+
+#[actix_web::test]
+async fn an_empty_export_is_still_valid_json() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app.get_export_subscribers("format=json").await;
+
+    // then
+    let subscribers: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert!(subscribers.is_empty());
+}
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str, status: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status) VALUES ($1, $2, $3, now(), $4)",
+        subscriber_id,
+        email,
+        name,
+        status
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+async fn subscriber_status(app: &TestApp, subscriber_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_see_the_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_act_on_a_subscriber() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula", "confirmed").await;
+
+    let response = app.post_subscriber_action(&subscriber_id, "delete").await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(subscriber_status(&app, subscriber_id).await.is_some());
+}
+
+#[actix_web::test]
+async fn subscribers_can_be_searched_by_email_or_name_and_status() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    insert_subscriber(&app, "ursula@gmail.com", "Ursula Le Guin", "confirmed").await;
+    insert_subscriber(
+        &app,
+        "octavia@gmail.com",
+        "Octavia Butler",
+        "pending_confirmation",
+    )
+    .await;
+    insert_subscriber(&app, "n_k@gmail.com", "Jemisin", "unsubscribed").await;
+
+    // when
+    let by_name = app.get_subscribers_html("q=le+gUIN").await;
+    let by_email = app.get_subscribers_html("q=octavia%40").await;
+    let by_status = app.get_subscribers_html("status=unsubscribed").await;
+    let wildcard = app.get_subscribers_html("q=_").await;
+    let everyone = app.get_subscribers_html("").await;
+
+    // then
+    assert!(by_name.contains("ursula@gmail.com"));
+    assert!(!by_name.contains("octavia@gmail.com"));
+    assert!(by_email.contains("octavia@gmail.com"));
+    assert!(!by_email.contains("ursula@gmail.com"));
+    assert!(by_status.contains("n_k@gmail.com"));
+    assert!(!by_status.contains("ursula@gmail.com"));
+    assert!(wildcard.contains("n_k@gmail.com"));
+    assert!(!wildcard.contains("ursula@gmail.com"));
+    for email in ["ursula@gmail.com", "octavia@gmail.com", "n_k@gmail.com"] {
+        assert!(everyone.contains(email));
+    }
+}
+
+#[actix_web::test]
+async fn subscribers_are_paginated() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), 'reader' || n || '@gmail.com', 'Reader', now() - n * interval '1 minute', 'confirmed'
+        FROM generate_series(1, 60) AS n
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // when
+    let first_page = app.get_subscribers_html("").await;
+    let next_page = first_page
+        .split(r#"<a href="/admin/subscribers?"#)
+        .nth(1)
+        .and_then(|s| s.split('"').next())
+        .expect("There is no link to the next page")
+        .replace("&amp;", "&");
+    let second_page = app.get_subscribers_html(&next_page).await;
+
+    // then
+    assert!(first_page.contains("reader1@gmail.com"));
+    assert!(first_page.contains("reader50@gmail.com"));
+    assert!(!first_page.contains("reader51@gmail.com"));
+    assert!(second_page.contains("reader51@gmail.com"));
+    assert!(second_page.contains("reader60@gmail.com"));
+    assert!(!second_page.contains("reader50@gmail.com"));
+    assert!(!second_page.contains("Next page"));
+}
+
+#[actix_web::test]
+async fn a_pending_subscriber_can_be_confirmed_manually() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@gmail.com", "Ursula", "pending_confirmation").await;
+
+    // when
+    let response = app.post_subscriber_action(&subscriber_id, "confirm").await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains("ursula@gmail.com has been confirmed."));
+    let saved = sqlx::query!(
+        "SELECT status, confirmed_at FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    assert!(saved.confirmed_at.is_some());
+}
+
+#[actix_web::test]
+async fn a_subscriber_pending_on_one_of_their_lists_can_be_confirmed_manually() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let subscriber_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula", "confirmed").await;
+    app.create_list("rust-weekly").await;
+    sqlx::query!(
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at, status)
+        SELECT list_id, $1, now(), 'pending_confirmation' FROM lists WHERE slug = 'rust-weekly'
+        "#,
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // when
+    let html_page = app.get_subscribers_html("").await;
+    let response = app.post_subscriber_action(&subscriber_id, "confirm").await;
+
+    // then
+    assert!(html_page.contains(&format!("/admin/subscribers/{}/confirm", subscriber_id)));
+    assert!(html_page.contains(&format!("/admin/subscribers/{}/resend", subscriber_id)));
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let membership = sqlx::query!(
+        "SELECT status, confirmed_at FROM list_subscriptions WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(membership.status, "confirmed");
+    assert!(membership.confirmed_at.is_some());
+}
+
+#[actix_web::test]
+async fn a_subscriber_can_be_unsubscribed_manually() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let subscriber_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula", "confirmed").await;
+
+    // when
+    let response = app
+        .post_subscriber_action(&subscriber_id, "unsubscribe")
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("unsubscribed")
+    );
+}
+
+#[actix_web::test]
+async fn the_confirmation_email_can_be_resent_to_a_pending_subscriber() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let confirmed_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula", "confirmed").await;
+    let pending_id =
+        insert_subscriber(&app, "octavia@gmail.com", "Octavia", "pending_confirmation").await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_subscriber_action(&confirmed_id, "resend").await;
+    let response = app.post_subscriber_action(&pending_id, "resend").await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        subscriber_status(&app, pending_id).await.as_deref(),
+        Some("confirmed")
+    );
+}
+
+#[actix_web::test]
+async fn a_deleted_subscriber_is_gone_with_their_tokens() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_unconfirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    // when
+    let response = app.post_subscriber_action(&subscriber_id, "delete").await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(subscriber_status(&app, subscriber_id).await.is_none());
+    let n_tokens = sqlx::query!(
+        "SELECT COUNT(*) AS \"n!\" FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .n;
+    assert_eq!(n_tokens, 0);
+}
+
+#[actix_web::test]
+async fn acting_on_an_unknown_subscriber_is_reported() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app.post_subscriber_action(&Uuid::new_v4(), "delete").await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains("The subscriber does not exist."));
+}
@@ -0,0 +1,204 @@
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    TestApp,
+};
+
+async fn only_subscriber_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_suppression(&serde_json::json!({
+            "email": "ursula@gmail.com",
+            "reason": "manual",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn suppressions_can_be_added_listed_and_removed() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_suppression(&serde_json::json!({
+            "email": "Ursula@gmail.com",
+            "reason": "complaint",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("Ursula@gmail.com has been suppressed."));
+    assert!(html_page.contains("<td>ursula@gmail.com</td><td>complaint</td>"));
+
+    // when
+    let response = app
+        .post_remove_suppression(&serde_json::json!({ "email": "ursula@gmail.com" }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("ursula@gmail.com is no longer suppressed."));
+    assert!(!html_page.contains("<td>ursula@gmail.com</td>"));
+}
+
+#[actix_web::test]
+async fn an_invalid_suppression_is_rejected() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    app.post_suppression(&serde_json::json!({
+        "email": "ursula@gmail.com",
+        "reason": "soft_bounce",
+    }))
+    .await;
+
+    // then
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("soft_bounce is not a suppression reason"));
+    assert!(!html_page.contains("<td>ursula@gmail.com</td>"));
+}
+
+#[actix_web::test]
+async fn suppressed_addresses_cannot_subscribe() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    app.post_suppression(&serde_json::json!({
+        "email": "ursula_le_guin@gmail.com",
+        "reason": "hard_bounce",
+    }))
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=URSULA_LE_GUIN%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[actix_web::test]
+async fn suppressed_subscribers_are_not_sent_confirmation_emails() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_unconfirmed_subscriber(&app).await;
+    let email = only_subscriber_email(&app).await;
+    app.post_suppression(&serde_json::json!({ "email": email, "reason": "manual" }))
+        .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/subscribers/{}/resend",
+            &app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_is_redirect_to(&response, "/admin/subscribers");
+}
+
+#[actix_web::test]
+async fn newsletters_are_not_delivered_to_suppressed_subscribers() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+    let email = only_subscriber_email(&app).await;
+    app.post_suppression(&serde_json::json!({ "email": email, "reason": "complaint" }))
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[actix_web::test]
+async fn suppressing_an_address_drops_its_queued_deliveries() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+    let email = only_subscriber_email(&app).await;
+    app.post_newsletter(&serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    }))
+    .await;
+
+    // when
+    app.post_suppression(&serde_json::json!({ "email": email, "reason": "hard_bounce" }))
+        .await;
+
+    // then
+    let n_queued = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_queued, 0);
+}
@@ -0,0 +1,268 @@
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestUser};
+
+#[actix_web::test]
+async fn viewers_can_look_around_but_not_publish() {
+    // given
+    let app = spawn_app().await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login(&viewer).await.unwrap();
+
+    // when
+    let dashboard = app.get_admin_dashboard_html().await;
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+        }))
+        .await;
+
+    // then
+    assert!(dashboard.contains("You are signed in as viewer."));
+    assert!(!dashboard.contains(r#"href="/admin/users""#));
+    assert_eq!(response.status().as_u16(), 403);
+    let issues = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.count, 0);
+}
+
+#[actix_web::test]
+async fn viewers_can_still_change_their_password() {
+    // given
+    let app = spawn_app().await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login(&viewer).await.unwrap();
+    let new_password = Uuid::new_v4().to_string();
+
+    // when
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": viewer.password,
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/password");
+}
+
+#[actix_web::test]
+async fn only_owners_can_manage_users() {
+    // given
+    let app = spawn_app().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login(&editor).await.unwrap();
+
+    // when
+    let page = app.get_users().await;
+    let create = app
+        .post_users(&serde_json::json!({
+            "username": "ursula",
+            "role": "owner",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_eq!(page.status().as_u16(), 403);
+    assert_eq!(create.status().as_u16(), 403);
+}
+
+#[actix_web::test]
+async fn owners_can_add_users_and_change_their_role() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_users(&serde_json::json!({
+            "username": "ursula",
+            "role": "viewer",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("ursula has been added as viewer."));
+    assert!(html_page.contains("<td>ursula</td><td>viewer</td><td>Active</td>"));
+
+    // when
+    let user_id = sqlx::query!("SELECT user_id FROM users WHERE username = 'ursula'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .user_id;
+    let response = app
+        .post_user_action(&user_id, "role", &serde_json::json!({ "role": "editor" }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("ursula is now editor."));
+    assert!(html_page.contains("<td>ursula</td><td>editor</td>"));
+
+    // when the new user logs in
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "ursula",
+            "password": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let dashboard = app.get_admin_dashboard_html().await;
+    assert!(dashboard.contains("You are signed in as editor."));
+}
+
+#[actix_web::test]
+async fn usernames_must_be_unique() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_users(&serde_json::json!({
+            "username": app.test_user.username,
+            "role": "viewer",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("{} is already taken.", app.test_user.username)));
+}
+
+#[actix_web::test]
+async fn new_users_need_a_valid_password() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_users(&serde_json::json!({
+            "username": "ursula",
+            "role": "viewer",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "another-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("You entered two different passwords!"));
+    assert!(!html_page.contains("<td>ursula</td>"));
+}
+
+#[actix_web::test]
+async fn deactivated_users_are_logged_out_and_cannot_log_back_in() {
+    // given
+    let app = spawn_app().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login(&editor).await.unwrap();
+
+    // when the owner deactivates them
+    sqlx::query!(
+        "UPDATE users SET deactivated_at = now() WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // then their session is no good anymore
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // and neither are their credentials
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": editor.username,
+            "password": editor.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn owners_can_deactivate_and_reactivate_users() {
+    // given
+    let app = spawn_app().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let response = app
+        .post_user_action(&editor.user_id, "deactivate", &serde_json::json!({}))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("{} has been deactivated.", editor.username)));
+    assert!(html_page.contains("Deactivated on"));
+
+    // when
+    let response = app
+        .post_user_action(&editor.user_id, "reactivate", &serde_json::json!({}))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/users");
+    app.post_logout().await;
+    app.login(&editor).await.unwrap();
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn owners_cannot_demote_or_deactivate_themselves() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    app.post_user_action(&app.test_user.user_id, "deactivate", &serde_json::json!({}))
+        .await;
+    app.post_user_action(
+        &app.test_user.user_id,
+        "role",
+        &serde_json::json!({ "role": "viewer" }),
+    )
+    .await;
+
+    // then
+    let user = sqlx::query!(
+        "SELECT role, deactivated_at FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(user.role, "owner");
+    assert!(user.deactivated_at.is_none());
+}
@@ -0,0 +1,227 @@
+use secrecy::Secret;
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, spawn_app_with,
+    when_sending_an_email, TestApp,
+};
+
+const WEBHOOK_SECRET: &str = "webhook-secret";
+
+async fn spawn_app_with_webhook() -> TestApp {
+    spawn_app_with(|c| c.email_client.webhook_secret = Some(Secret::new(WEBHOOK_SECRET.into())))
+        .await
+}
+
+/// Delivers a newsletter to a confirmed subscriber, the provider answering
+/// with `message_id`.
+async fn deliver_newsletter(app: &TestApp, message_id: &str) -> String {
+    create_confirmed_subscriber(app).await;
+    app.login_test_user().await.unwrap();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "messageId": message_id })),
+        )
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+async fn suppression_reason(app: &TestApp, email: &str) -> Option<String> {
+    sqlx::query!(
+        "SELECT reason FROM suppressions WHERE email = $1",
+        email.to_lowercase()
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.reason)
+}
+
+#[actix_web::test]
+async fn events_with_an_invalid_signature_are_rejected_with_401() {
+    // given
+    let app = spawn_app_with_webhook().await;
+    let body = serde_json::json!({ "event": "complaint", "email": "ursula@gmail.com" });
+
+    // when
+    let response = app.post_email_webhook(&body, "not-the-secret").await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(suppression_reason(&app, "ursula@gmail.com").await.is_none());
+}
+
+#[actix_web::test]
+async fn events_signed_too_long_ago_are_rejected_with_401() {
+    // given
+    let app = spawn_app_with_webhook().await;
+    let body = serde_json::json!({ "event": "complaint", "email": "ursula@gmail.com" });
+    let an_hour_ago = chrono::Utc::now().timestamp() - 60 * 60;
+
+    // when
+    let response = app
+        .post_email_webhook_signed_at(&body, WEBHOOK_SECRET, an_hour_ago)
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(suppression_reason(&app, "ursula@gmail.com").await.is_none());
+}
+
+#[actix_web::test]
+async fn events_are_rejected_when_no_secret_is_configured() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({ "event": "complaint", "email": "ursula@gmail.com" });
+
+    let response = app.post_email_webhook(&body, "").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn invalid_payloads_are_rejected_with_400() {
+    let app = spawn_app_with_webhook().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "event": "opened", "email": "ursula@gmail.com" }),
+            "unknown event",
+        ),
+        (
+            serde_json::json!({ "event": "bounce", "email": "ursula@gmail.com" }),
+            "bounce without a type",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_email_webhook(&body, WEBHOOK_SECRET).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The webhook did not reject a payload with an {}.",
+            description
+        );
+    }
+}
+
+#[actix_web::test]
+async fn events_matching_no_recipient_are_rejected_with_422() {
+    let app = spawn_app_with_webhook().await;
+    let body = serde_json::json!({ "event": "delivered", "message_id": "<unknown@provider>" });
+
+    let response = app.post_email_webhook(&body, WEBHOOK_SECRET).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[actix_web::test]
+async fn a_hard_bounce_is_logged_against_its_delivery_and_suppressed() {
+    // given
+    let app = spawn_app_with_webhook().await;
+    let email = deliver_newsletter(&app, "<bounce@provider>").await;
+
+    // when
+    let response = app
+        .post_email_webhook(
+            &serde_json::json!({
+                "event": "bounce",
+                "bounce_type": "hard",
+                "message_id": "<bounce@provider>",
+                "description": "550 5.1.1 The email account does not exist",
+            }),
+            WEBHOOK_SECRET,
+        )
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let event = sqlx::query!(
+        r#"
+        SELECT e.event, e.subscriber_email, e.description
+        FROM delivery_events e
+        JOIN newsletter_deliveries d ON d.newsletter_delivery_id = e.newsletter_delivery_id
+        WHERE d.provider_message_id = '<bounce@provider>'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(event.event, "hard_bounce");
+    assert_eq!(event.subscriber_email, email);
+    assert_eq!(
+        event.description.as_deref(),
+        Some("550 5.1.1 The email account does not exist")
+    );
+    assert_eq!(
+        suppression_reason(&app, &email).await.as_deref(),
+        Some("hard_bounce")
+    );
+}
+
+#[actix_web::test]
+async fn a_complaint_is_matched_by_email_and_suppressed() {
+    // given
+    let app = spawn_app_with_webhook().await;
+    let email = deliver_newsletter(&app, "<complaint@provider>").await;
+
+    // when
+    let response = app
+        .post_email_webhook(
+            &serde_json::json!({ "event": "complaint", "email": email.to_uppercase() }),
+            WEBHOOK_SECRET,
+        )
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        suppression_reason(&app, &email).await.as_deref(),
+        Some("complaint")
+    );
+}
+
+#[actix_web::test]
+async fn soft_bounces_and_deliveries_are_logged_but_not_suppressed() {
+    // given
+    let app = spawn_app_with_webhook().await;
+    let email = deliver_newsletter(&app, "<soft@provider>").await;
+
+    // when
+    for body in [
+        serde_json::json!({ "event": "delivered", "message_id": "<soft@provider>" }),
+        serde_json::json!({ "event": "bounce", "bounce_type": "soft", "message_id": "<soft@provider>" }),
+    ] {
+        let response = app.post_email_webhook(&body, WEBHOOK_SECRET).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // then
+    let events: Vec<_> = sqlx::query!("SELECT event FROM delivery_events ORDER BY received_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.event)
+        .collect();
+    assert_eq!(events, vec!["delivered", "soft_bounce"]);
+    assert!(suppression_reason(&app, &email).await.is_none());
+}
@@ -1,16 +1,26 @@
 use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
 use dotenv::from_filename;
+use fake::{
+    faker::{internet::en::SafeEmail, name::en::Name},
+    Fake,
+};
+use hmac::{Hmac, Mac};
 use once_cell::sync::Lazy;
 use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::{env, io};
 use url::Url;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockBuilder, MockServer, ResponseTemplate,
+};
 use zero2prod::{
-    configuration::get_configuration,
+    configuration::{get_configuration, EmailTransportSettings, IssueDeliverySettings, Settings},
     email_client::EmailClient,
-    issue_delivery_worker::{self, deliver_queued_tasks, ExecutionOutcome},
+    issue_delivery_worker::{deliver_queued_tasks, publish_scheduled_issues, ExecutionOutcome},
+    routes::{SIGNATURE_HEADER, TIMESTAMP_HEADER},
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -20,7 +30,7 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
-    if env::var("TEST_LOG").map_or(false, |v| matches!(&*v, "true" | "enabled")) {
+    if env::var("TEST_LOG").is_ok_and(|v| matches!(&*v, "true" | "enabled")) {
         let subscriber = get_subscriber(subscriber_name, default_filter_level, io::stdout);
         init_subscriber(subscriber);
     } else {
@@ -37,6 +47,11 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
+    pub issue_delivery: IssueDeliverySettings,
+    pub redis_uri: Secret<String>,
+    pub rate_limit_key_prefix: String,
 }
 
 pub struct ConfirmationLinks {
@@ -45,22 +60,50 @@ pub struct ConfirmationLinks {
 }
 
 impl TestApp {
+    /// What the rate limiter counted against `key`.
+    pub async fn rate_limit_count(&self, key: &str) -> u64 {
+        let mut connection = redis::Client::open(self.redis_uri.expose_secret().as_str())
+            .unwrap()
+            .get_async_connection()
+            .await
+            .unwrap();
+        let count: Option<u64> = redis::cmd("GET")
+            .arg(format!("{}:{}", self.rate_limit_key_prefix, key))
+            .query_async(&mut connection)
+            .await
+            .unwrap();
+        count.unwrap_or(0)
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                deliver_queued_tasks(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = deliver_queued_tasks(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.hmac_secret,
+                &self.issue_delivery,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
         }
     }
 
+    pub async fn publish_scheduled_issues(&self) -> usize {
+        publish_scheduled_issues(&self.db_pool).await.unwrap()
+    }
+
     pub async fn login_test_user(&self) -> Result<(), reqwest::Error> {
+        self.login(&self.test_user).await
+    }
+
+    pub async fn login(&self, user: &TestUser) -> Result<(), reqwest::Error> {
         self.post_login(&serde_json::json!({
-            "username": self.test_user.username,
-            "password": self.test_user.password,
+            "username": user.username,
+            "password": user.password,
         }))
         .await
         .error_for_status()?;
@@ -69,7 +112,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -84,7 +127,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -93,7 +136,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -105,7 +148,7 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -113,6 +156,15 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -136,12 +188,96 @@ impl TestApp {
         }
     }
 
+    /// The unsubscribe links in the footer of a newsletter issue, whatever
+    /// other links the issue contains.
+    pub fn get_unsubscribe_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+
+        let get_link = |s: &str| {
+            let links: Vec<_> = linkify::LinkFinder::new()
+                .links(s)
+                .filter(|l| *l.kind() == linkify::LinkKind::Url)
+                .filter_map(|l| reqwest::Url::parse(l.as_str()).ok())
+                .filter(|l| l.path() == "/subscriptions/unsubscribe")
+                .collect();
+            assert_eq!(links.len(), 1);
+            let mut unsubscribe_link = links[0].clone();
+            assert_eq!(unsubscribe_link.host_str().unwrap(), "127.0.0.1");
+            unsubscribe_link.set_port(Some(self.port)).unwrap();
+
+            unsubscribe_link
+        };
+
+        ConfirmationLinks {
+            html: get_link(body["htmlContent"].as_str().unwrap()),
+            plain_text: get_link(body["textContent"].as_str().unwrap()),
+        }
+    }
+
+    pub async fn get_unsubscribe(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/subscriptions/unsubscribe?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unsubscribe(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/unsubscribe?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_data_access_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/data/request", &self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_data_access(&self, data_access_token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/subscriptions/data", &self.address))
+            .query(&[("data_access_token", data_access_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscriber_data(&self, data_access_token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/data", &self.address))
+            .form(&[("data_access_token", data_access_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_erase_subscriber(&self, data_access_token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/erase", &self.address))
+            .form(&[("data_access_token", data_access_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_newsletter<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .form(body)
             .send()
             .await
@@ -150,7 +286,7 @@ impl TestApp {
 
     pub async fn get_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -160,12 +296,506 @@ impl TestApp {
         self.get_newsletter().await.text().await.unwrap()
     }
 
+    pub async fn get_newsletter_issue_report(
+        &self,
+        newsletter_issue_id: &Uuid,
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_newsletter_issue_report_html(&self, newsletter_issue_id: &Uuid) -> String {
+        self.get_newsletter_issue_report(newsletter_issue_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_reschedule_newsletter_issue<Body>(
+        &self,
+        newsletter_issue_id: &Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/schedule",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_cancel_newsletter_issue(
+        &self,
+        newsletter_issue_id: &Uuid,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/cancel",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_draft<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters/drafts", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_drafts_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/newsletters/drafts", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_edit_draft(&self, newsletter_issue_id: &Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}/edit",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_edit_draft_html(&self, newsletter_issue_id: &Uuid) -> String {
+        self.get_edit_draft(newsletter_issue_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_edit_draft<Body>(
+        &self,
+        newsletter_issue_id: &Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/edit",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_publish_draft<Body>(
+        &self,
+        newsletter_issue_id: &Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/publish",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_preview(&self, newsletter_issue_id: &Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}/preview",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_test_send<Body>(
+        &self,
+        newsletter_issue_id: &Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/test",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn create_list(&self, slug: &str) {
+        sqlx::query!(
+            "INSERT INTO lists (list_id, slug, name, created_at) VALUES ($1, $2, $2, now())",
+            Uuid::new_v4(),
+            slug
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to create a mailing list.");
+    }
+
+    pub async fn get_lists_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_list<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_subscribers_html(&self, query: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriber_action(
+        &self,
+        subscriber_id: &Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_export_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/export?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_import_subscribers_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers/import", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_import_subscribers<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_suppression<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/suppressions", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_remove_suppression<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/suppressions/remove", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_users<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_user_action<Body>(
+        &self,
+        user_id: &Uuid,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/users/{}/{}",
+                &self.address, user_id, action
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users/invitations", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The link of the invitation email, pointing at the test app.
+    pub fn get_invitation_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        self.get_confirmation_links(email_request).html
+    }
+
+    pub async fn get_accept_invitation(&self, link: &reqwest::Url) -> reqwest::Response {
+        self.api_client
+            .get(link.clone())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/invitations/accept", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/new", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_two_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_two_factor<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/two-factor", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_two_factor<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/two-factor/disable", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login_two_factor(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/login/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_login_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/two-factor", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_recipients_html(&self, list: &str, segment: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/newsletters/recipients", &self.address))
+            .query(&[("list", list), ("segment", segment)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Signs `body` with `secret` the way the email provider would.
+    pub async fn post_email_webhook(
+        &self,
+        body: &serde_json::Value,
+        secret: &str,
+    ) -> reqwest::Response {
+        self.post_email_webhook_signed_at(body, secret, chrono::Utc::now().timestamp())
+            .await
+    }
+
+    pub async fn post_email_webhook_signed_at(
+        &self,
+        body: &serde_json::Value,
+        secret: &str,
+        timestamp: i64,
+    ) -> reqwest::Response {
+        let body = serde_json::to_vec(body).unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(&body);
+        self.api_client
+            .post(format!("{}/webhooks/email", &self.address))
+            .header("Content-Type", "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, hex::encode(mac.finalize().into_bytes()))
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
@@ -174,7 +804,7 @@ impl TestApp {
 
     pub async fn get_login(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -186,7 +816,7 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -194,6 +824,11 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the app after letting the test adjust its configuration.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -209,8 +844,17 @@ pub async fn spawn_app() -> TestApp {
         c.database_url =
             Secret::new(configure_database(&database_url, &Uuid::new_v4().to_string()).await);
 
-        c.email_client.base_url = email_server.uri();
+        c.email_client.transport = EmailTransportSettings::Http {
+            base_url: email_server.uri(),
+            authorization_token: Secret::new(Uuid::new_v4().to_string()),
+        };
+
+        c.application.totp_encryption_key = Some(Secret::new(Uuid::new_v4().to_string()));
 
+        // Every test app gets its own rate limit counters.
+        c.rate_limit.key_prefix = Uuid::new_v4().to_string();
+
+        configure(&mut c);
         c
     };
 
@@ -218,7 +862,7 @@ pub async fn spawn_app() -> TestApp {
         .await
         .expect("Failed to bind to address");
     let port = application.port();
-    let _ = tokio::spawn(application.server);
+    drop(tokio::spawn(application.server));
 
     let database_url = configuration.database_url.expose_secret();
     let address_len = configuration.application.address.len();
@@ -243,12 +887,72 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        base_url: configuration.application.base_url,
+        hmac_secret: configuration.application.hmac_secret,
+        issue_delivery: configuration.issue_delivery,
+        redis_uri: configuration.redis_uri,
+        rate_limit_key_prefix: configuration.rate_limit.key_prefix,
     };
     test_app.test_user.store(&test_app.db_pool).await;
 
     test_app
 }
 
+pub fn when_sending_an_email() -> MockBuilder {
+    Mock::given(path("/email")).and(method("POST"))
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    create_confirmed_subscriber_in_list(app, "newsletter").await
+}
+
+pub async fn create_confirmed_subscriber_in_list(app: &TestApp, list: &str) {
+    let confirmation_links = create_unconfirmed_subscriber_in_list(app, list).await;
+
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    create_unconfirmed_subscriber_in_list(app, "newsletter").await
+}
+
+pub async fn create_unconfirmed_subscriber_in_list(app: &TestApp, list: &str) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let json = &serde_json::json!({
+        "name": name,
+        "email": email,
+        "list": list,
+    });
+    let body = serde_urlencoded::to_string(json).unwrap();
+    tracing::debug!("body = {}", &body);
+
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(email_request)
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -258,14 +962,22 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: String,
+    pub email: String,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::with_role("owner")
+    }
+
+    pub fn with_role(role: &str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            role: role.to_string(),
+            email: SafeEmail().fake(),
         }
     }
 
@@ -282,11 +994,13 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3);",
+            "INSERT INTO users (user_id, username, password_hash, role, email)
+            VALUES ($1, $2, $3, $4, $5);",
             self.user_id,
             self.username,
-            password_hash
+            password_hash,
+            self.role,
+            self.email
         )
         .execute(pool)
         .await
@@ -1,4 +1,14 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use std::time::{Duration, Instant};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+async fn login_with_password(app: &TestApp, username: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": username,
+        "password": password,
+    }))
+    .await
+}
 
 #[actix_web::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -46,3 +56,133 @@ async fn redirect_to_admin_dashboard_after_successful_login() {
     // then we see the welcome message
     assert!(html_page.contains(r#"Welcome"#));
 }
+
+#[actix_web::test]
+async fn a_username_is_locked_out_after_too_many_failures() {
+    // given
+    let app = spawn_app_with(|c| {
+        c.rate_limit.login_failures_before_delay = 100;
+        c.rate_limit.login_failures_per_username = 3;
+    })
+    .await;
+    let username = app.test_user.username.clone();
+    for _ in 0..3 {
+        login_with_password(&app, &username, "wrong-password").await;
+    }
+    app.get_login_html().await;
+
+    // when
+    let response = login_with_password(&app, &username, &app.test_user.password).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[actix_web::test]
+async fn an_address_is_locked_out_after_too_many_failures() {
+    // given
+    let app = spawn_app_with(|c| {
+        c.rate_limit.login_failures_before_delay = 100;
+        c.rate_limit.login_failures_per_ip = 3;
+    })
+    .await;
+    for i in 0..3 {
+        login_with_password(&app, &format!("random-username-{}", i), "random-password").await;
+    }
+
+    // when
+    let response =
+        login_with_password(&app, &app.test_user.username, &app.test_user.password).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
+}
+
+#[actix_web::test]
+async fn a_successful_login_forgets_the_failures_of_the_username() {
+    // given
+    let app = spawn_app_with(|c| {
+        c.rate_limit.login_failures_before_delay = 100;
+        c.rate_limit.login_failures_per_username = 3;
+    })
+    .await;
+    let username = app.test_user.username.clone();
+    for _ in 0..2 {
+        login_with_password(&app, &username, "wrong-password").await;
+    }
+    app.login_test_user().await.unwrap();
+    app.post_logout().await;
+
+    // when
+    for _ in 0..2 {
+        login_with_password(&app, &username, "wrong-password").await;
+    }
+    let response = login_with_password(&app, &username, &app.test_user.password).await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+async fn repeated_failures_slow_down_further_attempts() {
+    // given
+    let app = spawn_app_with(|c| c.rate_limit.login_failures_before_delay = 1).await;
+    let username = app.test_user.username.clone();
+    login_with_password(&app, &username, "wrong-password").await;
+
+    // when
+    let started = Instant::now();
+    let response = login_with_password(&app, &username, &app.test_user.password).await;
+
+    // then
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+async fn concurrent_attempts_cannot_get_past_the_lockout() {
+    // given
+    let app = spawn_app_with(|c| {
+        c.rate_limit.login_failures_before_delay = 100;
+        c.rate_limit.login_failures_per_username = 3;
+    })
+    .await;
+    let username = app.test_user.username.clone();
+
+    // when
+    let responses = futures_util::future::join_all(
+        (0..10).map(|_| login_with_password(&app, &username, "wrong-password")),
+    )
+    .await;
+
+    // then only the attempts under the limit were checked and counted
+    for response in responses {
+        assert_is_redirect_to(&response, "/login");
+    }
+    let key = format!("login:username:{}", username);
+    assert_eq!(app.rate_limit_count(&key).await, 3);
+}
+
+#[actix_web::test]
+async fn attempts_that_could_not_be_checked_are_not_counted() {
+    // given
+    let app = spawn_app().await;
+    let username = app.test_user.username.clone();
+    sqlx::query!("ALTER TABLE users DROP COLUMN password_hash;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = login_with_password(&app, &username, &app.test_user.password).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    let key = format!("login:username:{}", username);
+    assert_eq!(app.rate_limit_count(&key).await, 0);
+}
@@ -1,8 +1,19 @@
 mod admin_dashboard;
+mod admin_lists;
+mod admin_subscribers;
+mod admin_suppressions;
+mod admin_users;
 mod change_password;
+mod email_webhook;
 mod health_check;
 mod helpers;
 mod login;
 mod newsletter;
+mod newsletter_drafts;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_unsubscribe;
+mod two_factor;
+mod user_invitations;
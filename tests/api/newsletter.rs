@@ -1,65 +1,11 @@
 use std::time::Duration;
 
-use fake::{
-    faker::{internet::en::SafeEmail, name::en::Name},
-    Fake,
-};
-use wiremock::{
-    matchers::{any, method, path},
-    Mock, MockBuilder, ResponseTemplate,
-};
-
-use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
-
-// Helpers below
-
-fn when_sending_an_email() -> MockBuilder {
-    Mock::given(path("/email")).and(method("POST"))
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_links = create_unconfirmed_subscriber(app).await;
-
-    reqwest::get(confirmation_links.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
-
-async fn create_unconfirmed_subscriber(app: &TestApp) -> crate::helpers::ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let json = &serde_json::json!({
-        "name": name,
-        "email": email,
-    });
-    let body = serde_urlencoded::to_string(json).unwrap();
-    tracing::debug!("body = {}", &body);
-
-    let _mock_guard = when_sending_an_email()
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-
-    app.post_subscriptions(body)
-        .await
-        .error_for_status()
-        .unwrap();
-
-    let email_request = &app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
-    app.get_confirmation_links(email_request)
-}
+use wiremock::{matchers::any, Mock, ResponseTemplate};
 
-// Tests below
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_confirmed_subscriber_in_list,
+    create_unconfirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
 
 #[actix_web::test]
 async fn concurrent_form_submission_is_handled_gracefully() {
@@ -233,3 +179,775 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
 
     // Mock verifies we sent the newsletter
 }
+
+#[actix_web::test]
+async fn failed_deliveries_are_retried_later() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    });
+
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // The task is still queued, scheduled for a later attempt
+    let task = sqlx::query!(
+        "SELECT n_retries, execute_after > now() AS \"scheduled_later!\" FROM issue_delivery_queue"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch the queued task.");
+
+    assert_eq!(task.n_retries, 1);
+    assert!(task.scheduled_later);
+
+    let delivery = sqlx::query!("SELECT status_code, error FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the delivery attempt.");
+    assert_eq!(delivery.status_code, Some(500));
+    assert!(delivery.error.is_some());
+}
+
+#[actix_web::test]
+async fn deliveries_are_dead_lettered_after_the_retry_ceiling() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    create_confirmed_subscriber(&app).await;
+
+    let max_retries = app.issue_delivery.max_retries;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .expect(u64::from(max_retries) + 1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    });
+
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    for _ in 0..=max_retries {
+        app.dispatch_all_pending_emails().await;
+        // Skip the backoff
+        sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+    }
+
+    let queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, 0);
+
+    let failure = sqlx::query!("SELECT n_retries, last_error FROM issue_delivery_failures")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the dead-lettered task.");
+    assert_eq!(i32::from(failure.n_retries), i32::from(max_retries));
+    assert!(!failure.last_error.is_empty());
+}
+
+#[actix_web::test]
+async fn permanently_rejected_deliveries_are_dead_lettered_at_once() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(400))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    });
+
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, 0);
+
+    let failure = sqlx::query!("SELECT n_retries, last_error FROM issue_delivery_failures")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the dead-lettered task.");
+    assert_eq!(failure.n_retries, 0);
+    assert!(failure.last_error.contains("400"));
+}
+
+#[actix_web::test]
+async fn delivery_attempts_are_recorded() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(
+            ResponseTemplate::new(201)
+                .set_body_json(serde_json::json!({ "messageId": "<id@provider>" })),
+        )
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    });
+
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let delivery =
+        sqlx::query!("SELECT status_code, provider_message_id, error FROM newsletter_deliveries")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch the delivery attempt.");
+    assert_eq!(delivery.status_code, Some(201));
+    assert_eq!(
+        delivery.provider_message_id.as_deref(),
+        Some("<id@provider>")
+    );
+    assert_eq!(delivery.error, None);
+}
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_see_an_issue_report() {
+    let app = spawn_app().await;
+
+    let response = app.get_newsletter_issue_report(&uuid::Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn issue_report_for_an_unknown_issue_is_404() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    let response = app.get_newsletter_issue_report(&uuid::Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn issue_report_shows_sent_failed_and_pending_counts() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    });
+    app.post_newsletter(&newsletter_request_body).await;
+
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app
+        .get_newsletter_issue_report_html(&issue.newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<tr><th>Sent</th><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><th>Pending</th><td>1</td></tr>"));
+
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app
+        .get_newsletter_issue_report_html(&issue.newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Failed</th><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><th>Pending</th><td>0</td></tr>"));
+}
+
+#[actix_web::test]
+async fn issue_titles_and_list_names_are_escaped() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    sqlx::query!("UPDATE lists SET name = '<b>Newsletter</b>'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_newsletter(&serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "<script>alert(1)</script>",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    }))
+    .await;
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let report_html = app
+        .get_newsletter_issue_report_html(&issue.newsletter_issue_id)
+        .await;
+    let issues_html = app.get_newsletter_html().await;
+
+    // then
+    for html_page in [report_html.as_str(), issues_html.as_str()] {
+        assert!(!html_page.contains("<script>"));
+        assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    }
+    assert!(report_html.contains("<p>List: &lt;b&gt;Newsletter&lt;/b&gt;</p>"));
+}
+
+fn scheduled_newsletter_request_body(
+    scheduled_for: chrono::DateTime<chrono::Utc>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+        "scheduled_for": scheduled_for.format("%Y-%m-%dT%H:%M").to_string(),
+    })
+}
+
+async fn make_scheduled_issue_due(app: &TestApp) {
+    sqlx::query!("UPDATE newsletter_issues SET scheduled_for = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn scheduled_newsletters_are_not_delivered_before_their_send_time() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let body = scheduled_newsletter_request_body(chrono::Utc::now() + chrono::Duration::days(1));
+    let response = app.post_newsletter(&body).await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been scheduled for"));
+    assert_eq!(app.publish_scheduled_issues().await, 0);
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!("SELECT status, published_at FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "scheduled");
+    assert!(issue.published_at.is_none());
+}
+
+#[actix_web::test]
+async fn scheduled_newsletters_are_delivered_once_due() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = scheduled_newsletter_request_body(chrono::Utc::now() + chrono::Duration::days(1));
+    app.post_newsletter(&body).await;
+
+    // when
+    make_scheduled_issue_due(&app).await;
+    assert_eq!(app.publish_scheduled_issues().await, 1);
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let issue = sqlx::query!("SELECT status, published_at FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "sent");
+    assert!(issue.published_at.is_some());
+    // Publishing again is a no-op
+    assert_eq!(app.publish_scheduled_issues().await, 0);
+}
+
+#[actix_web::test]
+async fn scheduled_newsletters_can_be_rescheduled() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    let body = scheduled_newsletter_request_body(chrono::Utc::now() + chrono::Duration::days(1));
+    app.post_newsletter(&body).await;
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let new_send_time = chrono::Utc::now() + chrono::Duration::days(7);
+    let response = app
+        .post_reschedule_newsletter_issue(
+            &issue.newsletter_issue_id,
+            &serde_json::json!({
+                "scheduled_for": new_send_time.format("%Y-%m-%dT%H:%M").to_string()
+            }),
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", issue.newsletter_issue_id),
+    );
+    let html_page = app
+        .get_newsletter_issue_report_html(&issue.newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("The newsletter issue has been rescheduled for"));
+
+    let issue = sqlx::query!("SELECT scheduled_for FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issue.scheduled_for.unwrap() > chrono::Utc::now() + chrono::Duration::days(6));
+}
+
+#[actix_web::test]
+async fn cancelled_newsletters_are_never_delivered() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let body = scheduled_newsletter_request_body(chrono::Utc::now() + chrono::Duration::days(1));
+    app.post_newsletter(&body).await;
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = app
+        .post_cancel_newsletter_issue(&issue.newsletter_issue_id)
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", issue.newsletter_issue_id),
+    );
+
+    // then
+    make_scheduled_issue_due(&app).await;
+    assert_eq!(app.publish_scheduled_issues().await, 0);
+    app.dispatch_all_pending_emails().await;
+
+    // Cancelling twice is reported as an error
+    app.post_cancel_newsletter_issue(&issue.newsletter_issue_id)
+        .await;
+    let html_page = app
+        .get_newsletter_issue_report_html(&issue.newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("Only scheduled issues can be cancelled."));
+}
+
+#[actix_web::test]
+async fn invalid_send_time_is_rejected() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+            "scheduled_for": "next tuesday",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn send_times_in_the_past_are_rejected() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let body = scheduled_newsletter_request_body(chrono::Utc::now() - chrono::Duration::hours(1));
+    let response = app.post_newsletter(&body).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+    let issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.count, 0);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn a_retry_is_answered_even_once_the_send_time_has_passed() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let mut body =
+        scheduled_newsletter_request_body(chrono::Utc::now() + chrono::Duration::hours(1));
+    let response = app.post_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // when the same request comes in again, too late for its send time
+    body["scheduled_for"] = (chrono::Utc::now() - chrono::Duration::hours(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+        .into();
+    let response = app.post_newsletter(&body).await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.count, 1);
+}
+
+#[actix_web::test]
+async fn merge_fields_are_rendered_for_each_subscriber() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT s.id, s.name, s.email, m.list_id
+        FROM subscriptions s
+        JOIN list_subscriptions m ON m.subscriber_id = s.id
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_newsletter(&serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "News for {{ name }}",
+        "text_content": "Hi {{name}}, this was sent to {{ email }}. Leave: {{ unsubscribe_url }}",
+        "html_content": "<p>Sent to {{ email }}</p>",
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let unsubscribe_link = zero2prod::routes::unsubscribe_link(
+        &app.base_url,
+        &subscriber.id,
+        &subscriber.list_id,
+        &app.hmac_secret,
+    );
+    assert_eq!(body["to"][0]["name"], subscriber.name);
+    assert_eq!(body["subject"], format!("News for {}", subscriber.name));
+    assert!(body["textContent"].as_str().unwrap().starts_with(&format!(
+        "Hi {}, this was sent to {}. Leave: {}",
+        subscriber.name, subscriber.email, unsubscribe_link
+    )));
+    assert!(body["htmlContent"]
+        .as_str()
+        .unwrap()
+        .starts_with(&format!("<p>Sent to {}</p>", subscriber.email)));
+}
+
+#[actix_web::test]
+async fn unknown_merge_fields_are_rejected_at_publish_time() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Hi {{ first_name }}",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_issues, 0);
+}
+
+#[actix_web::test]
+async fn newsletters_are_only_delivered_to_the_subscribers_of_their_list() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    app.create_list("rust-weekly").await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber_in_list(&app, "rust-weekly").await;
+    let rust_weekly_subscriber = sqlx::query!(
+        r#"
+        SELECT s.email
+        FROM subscriptions s
+        JOIN list_subscriptions m ON m.subscriber_id = s.id
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE l.slug = 'rust-weekly'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_newsletter(&serde_json::json!({
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+        "list": "rust-weekly",
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], rust_weekly_subscriber.email);
+}
+
+#[actix_web::test]
+async fn publishing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+            "list": "unknown",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn tag_subscriber(app: &TestApp, email: &str, tag: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT id, $2 FROM subscriptions WHERE email = $1
+        "#,
+        email,
+        tag
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+async fn segmented_newsletters_are_only_delivered_to_matching_subscribers() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let vip = sqlx::query!("SELECT email FROM subscriptions LIMIT 1")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    tag_subscriber(&app, &vip, "vip").await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+            "segment": "tag:vip",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], vip);
+}
+
+#[actix_web::test]
+async fn recipients_matched_are_counted_before_sending() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    let emails: Vec<_> = sqlx::query!("SELECT email FROM subscriptions WHERE status = 'confirmed'")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.email)
+        .collect();
+    tag_subscriber(&app, &emails[0], "vip").await;
+    sqlx::query!("UPDATE subscriptions SET locale = 'it'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when / then
+    let test_cases = vec![
+        ("", 2),
+        ("tag:vip", 1),
+        ("tag:vip locale:it", 1),
+        ("locale:en", 0),
+        ("subscribed_before:2000-01-01", 0),
+        ("subscribed_after:2000-01-01", 2),
+    ];
+    for (segment, expected) in test_cases {
+        let html_page = app
+            .get_newsletter_recipients_html("newsletter", segment)
+            .await;
+        assert!(
+            html_page.contains(&format!("Recipients matched: {}", expected)),
+            "Unexpected recipient count for segment {:?}",
+            segment
+        );
+    }
+}
+
+#[actix_web::test]
+async fn invalid_segments_are_rejected() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+            "segment": "country:it",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,346 @@
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+
+fn draft_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Draft title",
+        "text_content": "Draft body as plain text.",
+        "html_content": "<p>Draft body as HTML.</p>",
+    })
+}
+
+async fn save_draft(app: &TestApp) -> Uuid {
+    let response = app.post_draft(&draft_request_body()).await;
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}/edit", issue.newsletter_issue_id),
+    );
+    issue.newsletter_issue_id
+}
+
+async fn issue_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+#[actix_web::test]
+async fn you_must_be_logged_in_to_save_a_draft() {
+    let app = spawn_app().await;
+
+    let response = app.post_draft(&draft_request_body()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn saved_drafts_are_listed_but_not_delivered() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let newsletter_issue_id = save_draft(&app).await;
+
+    // then
+    let html_page = app.get_edit_draft_html(&newsletter_issue_id).await;
+    assert!(html_page.contains("The draft has been saved."));
+    assert!(html_page.contains("&lt;p&gt;Draft body as HTML.&lt;/p&gt;"));
+    assert!(html_page.contains("Recipients matched: 1"));
+
+    let html_page = app.get_drafts_html().await;
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/newsletters/{}/edit">Draft title</a>"#,
+        newsletter_issue_id
+    )));
+
+    assert_eq!(issue_status(&app).await, "draft");
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn drafts_can_be_edited() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+
+    // when
+    let response = app
+        .post_edit_draft(
+            &newsletter_issue_id,
+            &serde_json::json!({
+                "title": "New title",
+                "text_content": "New text.",
+                "html_content": "<p>New HTML.</p>",
+            }),
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}/edit", newsletter_issue_id),
+    );
+    let issue = sqlx::query!("SELECT title, text_content, html_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.title, "New title");
+    assert_eq!(issue.text_content, "New text.");
+    assert_eq!(issue.html_content, "<p>New HTML.</p>");
+}
+
+#[actix_web::test]
+async fn preview_renders_the_html_content() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+
+    let response = app.get_preview(&newsletter_issue_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), "<p>Draft body as HTML.</p>");
+}
+
+#[actix_web::test]
+async fn preview_is_sandboxed() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+
+    let response = app.get_preview(&newsletter_issue_id).await;
+
+    assert_eq!(
+        response.headers().get("Content-Security-Policy").unwrap(),
+        "sandbox"
+    );
+}
+
+#[actix_web::test]
+async fn test_send_delivers_one_copy_without_touching_the_queue() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    let newsletter_issue_id = save_draft(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_test_send(
+            &newsletter_issue_id,
+            &serde_json::json!({ "recipient": "editor@example.com" }),
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}/edit", newsletter_issue_id),
+    );
+    let html_page = app.get_edit_draft_html(&newsletter_issue_id).await;
+    assert!(html_page.contains("A test email has been sent to editor@example.com."));
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], "editor@example.com");
+    assert_eq!(body["subject"], "[TEST] Draft title");
+
+    let n_pending = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_pending, 0);
+    assert_eq!(issue_status(&app).await, "draft");
+}
+
+#[actix_web::test]
+async fn test_send_rejects_invalid_addresses() {
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_test_send(
+        &newsletter_issue_id,
+        &serde_json::json!({ "recipient": "not-an-email" }),
+    )
+    .await;
+
+    let html_page = app.get_edit_draft_html(&newsletter_issue_id).await;
+    assert!(html_page.contains("not-an-email is not a valid email"));
+}
+
+#[actix_web::test]
+async fn published_drafts_are_delivered_and_marked_as_sent() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    create_confirmed_subscriber(&app).await;
+    let newsletter_issue_id = save_draft(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_publish_draft(
+            &newsletter_issue_id,
+            &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+    assert_eq!(issue_status(&app).await, "queued");
+
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(issue_status(&app).await, "sent");
+}
+
+#[actix_web::test]
+async fn published_issues_can_no_longer_be_edited() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+    app.post_publish_draft(
+        &newsletter_issue_id,
+        &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+    )
+    .await;
+    // No confirmed subscribers: the issue is sent straight away
+    assert_eq!(issue_status(&app).await, "sent");
+
+    // when
+    let response = app.get_edit_draft(&newsletter_issue_id).await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+    app.post_edit_draft(
+        &newsletter_issue_id,
+        &serde_json::json!({
+            "title": "New title",
+            "text_content": "New text.",
+            "html_content": "<p>New HTML.</p>",
+        }),
+    )
+    .await;
+
+    // then
+    let html_page = app
+        .get_newsletter_issue_report_html(&newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("Only drafts can be edited."));
+    let title = sqlx::query!("SELECT title FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .title;
+    assert_eq!(title, "Draft title");
+}
+
+#[actix_web::test]
+async fn drafts_with_unknown_merge_fields_cannot_be_published() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+    app.post_edit_draft(
+        &newsletter_issue_id,
+        &serde_json::json!({
+            "title": "Hi {{ nickname }}",
+            "text_content": "New text.",
+            "html_content": "<p>New HTML.</p>",
+        }),
+    )
+    .await;
+
+    // when
+    let response = app
+        .post_publish_draft(
+            &newsletter_issue_id,
+            &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}/edit", newsletter_issue_id),
+    );
+    let html_page = app.get_edit_draft_html(&newsletter_issue_id).await;
+    assert!(html_page.contains("{{ nickname }} is not a known merge field"));
+    assert_eq!(issue_status(&app).await, "draft");
+}
+
+#[actix_web::test]
+async fn drafts_cannot_be_scheduled_in_the_past() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let newsletter_issue_id = save_draft(&app).await;
+    let scheduled_for = chrono::Utc::now() - chrono::Duration::hours(1);
+
+    // when
+    let response = app
+        .post_publish_draft(
+            &newsletter_issue_id,
+            &serde_json::json!({
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "scheduled_for": scheduled_for.format("%Y-%m-%dT%H:%M").to_string(),
+            }),
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}/edit", newsletter_issue_id),
+    );
+    let html_page = app.get_edit_draft_html(&newsletter_issue_id).await;
+    assert!(html_page.contains("The send time must be in the future."));
+    assert_eq!(issue_status(&app).await, "draft");
+}
@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use wiremock::{Request, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, spawn_app, spawn_app_with, when_sending_an_email, TestApp,
+};
+
+/// The reset email is sent in the background.
+async fn wait_for_email(app: &TestApp) -> Request {
+    for _ in 0..50 {
+        if let Some(request) = app.email_server.received_requests().await.unwrap().pop() {
+            return request;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("No email was sent.");
+}
+
+/// Requests a reset for the test user and returns the link from the email.
+async fn request_reset_link(app: &TestApp) -> reqwest::Url {
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "username": app.test_user.username }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let email_request = wait_for_email(app).await;
+    app.get_confirmation_links(&email_request).html
+}
+
+/// The form fields of a reset link, with the new password.
+fn reset_body(link: &reqwest::Url, new_password: &str) -> serde_json::Value {
+    let mut body: serde_json::Map<String, serde_json::Value> = link
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned().into()))
+        .collect();
+    body.insert("new_password".into(), new_password.into());
+    body.insert("new_password_check".into(), new_password.into());
+    body.into()
+}
+
+#[actix_web::test]
+async fn the_login_form_links_to_the_password_reset() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<a href="/password-reset">Forgot your password?</a>"#));
+}
+
+#[actix_web::test]
+async fn requests_for_unknown_users_look_the_same_and_send_nothing() {
+    // given
+    let app = spawn_app().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "username": "nobody" }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "If this user has an email address, a link to reset the password was sent to it."
+    ));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+}
+
+#[actix_web::test]
+async fn a_reset_link_lets_the_user_choose_a_new_password() {
+    // given
+    let app = spawn_app().await;
+    let link = request_reset_link(&app).await;
+
+    // when
+    let form = app.api_client.get(link.clone()).send().await.unwrap();
+    let response = app
+        .post_password_reset(&reset_body(&link, "a-brand-new-password"))
+        .await;
+
+    // then
+    assert_eq!(form.status().as_u16(), 200);
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your password has been reset, you can log in now."));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": "a-brand-new-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+async fn resetting_the_password_logs_the_user_out_everywhere() {
+    // given
+    let app = spawn_app().await;
+    let other_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    other_client
+        .post(format!("{}/login", app.address))
+        .form(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+    let dashboard = || {
+        other_client
+            .get(format!("{}/admin/dashboard", app.address))
+            .send()
+    };
+    assert_eq!(dashboard().await.unwrap().status().as_u16(), 200);
+    let link = request_reset_link(&app).await;
+
+    // when
+    app.post_password_reset(&reset_body(&link, "a-brand-new-password"))
+        .await;
+
+    // then
+    assert_is_redirect_to(&dashboard().await.unwrap(), "/login");
+}
+
+#[actix_web::test]
+async fn a_reset_link_only_works_once() {
+    // given
+    let app = spawn_app().await;
+    let link = request_reset_link(&app).await;
+    app.post_password_reset(&reset_body(&link, "a-brand-new-password"))
+        .await;
+
+    // when
+    let response = app
+        .post_password_reset(&reset_body(&link, "yet-another-password"))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn expired_or_tampered_links_are_rejected() {
+    // given
+    let app = spawn_app().await;
+    let link = request_reset_link(&app).await;
+    let mut body = reset_body(&link, "a-brand-new-password");
+
+    // when
+    body["expires_at"] = "1".into();
+    let expired = app.post_password_reset(&body).await;
+    body["expires_at"] = "99999999999".into();
+    let tampered = app.post_password_reset(&body).await;
+
+    // then
+    assert_eq!(expired.status().as_u16(), 403);
+    assert_eq!(tampered.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn the_new_password_must_follow_the_usual_rules() {
+    // given
+    let app = spawn_app().await;
+    let link = request_reset_link(&app).await;
+
+    // when
+    let response = app.post_password_reset(&reset_body(&link, "short")).await;
+
+    // then
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(location.starts_with("/password-reset/new?"));
+    let html_page = app
+        .api_client
+        .get(link)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Your new password should be at least 12 characters in length!"));
+}
+
+#[actix_web::test]
+async fn reset_requests_are_rate_limited_per_ip() {
+    // setup
+    let app = spawn_app_with(|c| c.rate_limit.password_resets_per_ip_per_hour = 2).await;
+
+    // given
+    for username in ["nobody", "somebody"] {
+        let response = app
+            .post_password_reset_request(&serde_json::json!({ "username": username }))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // when
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "username": "anybody" }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = app
+        .api_client
+        .get(format!("{}/password-reset", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Too many password reset requests, try again later."));
+}
+
+#[actix_web::test]
+async fn reset_emails_are_rate_limited_per_address() {
+    // setup
+    let app = spawn_app_with(|c| c.rate_limit.password_resets_per_email_per_hour = 1).await;
+
+    // given
+    request_reset_link(&app).await;
+
+    // when
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "username": app.test_user.username }))
+        .await;
+
+    // then the answer is the same, but no second email goes out
+    assert_is_redirect_to(&response, "/login");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
@@ -1,8 +1,9 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
+use zero2prod::domain::EmailDomainBlocklist;
 
 #[actix_web::test]
 async fn subscriber_returns_a_200_for_valid_form_data() {
@@ -10,7 +11,7 @@ async fn subscriber_returns_a_200_for_valid_form_data() {
     let app = spawn_app().await;
 
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -31,7 +32,7 @@ async fn subscribe_persists_the_new_subscriber() {
     let app = spawn_app().await;
 
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -60,12 +61,12 @@ async fn subscriber_returns_a_400_when_data_is_missing() {
 
     // given
     let test_cases = vec![
-        ("{\"name\":\"le guin\"}", "missing field `email`"),
+        ("name=le%20guin", "missing field `email`"),
         (
-            "{\"email\":\"ursula_le_guin@gmail.com\"}",
+            "email=ursula_le_guin%40gmail.com",
             "missing field `name`",
         ),
-        ("{}", "missing field"),
+        ("", "missing field"),
     ];
 
     for (invalid_body, error_message) in test_cases {
@@ -92,15 +93,15 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
     // given
     let test_cases = vec![
         (
-            "{\"name\":\"\",\"email\":\"ursula_le_guin@gmail.com\"}",
+            "name=&email=ursula_le_guin%40gmail.com",
             "empty field `email`",
         ),
         (
-            "{\"name\":\"Ursula\",\"email\":\"\"}",
+            "name=Ursula&email=",
             "empty field `email`",
         ),
         (
-            "{\"name\":\"Ursula\",\"email\":\"not an@-email\"}",
+            "name=Ursula&email=not%20an%40-email",
             "invalid `email`",
         ),
     ];
@@ -128,7 +129,7 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
 async fn subscribe_sends_a_confirmation_email_for_valid_data() {
     let app = spawn_app().await;
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -143,7 +144,7 @@ async fn subscribe_sends_a_confirmation_email_for_valid_data() {
 async fn subscribe_sends_a_confirmation_email_with_a_link() {
     let app = spawn_app().await;
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -163,10 +164,10 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
 }
 
 #[actix_web::test]
-async fn re_subscribe_sends_same_confirmation_email() {
+async fn re_subscribe_rotates_the_confirmation_token() {
     let app = spawn_app().await;
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -196,7 +197,67 @@ async fn re_subscribe_sends_same_confirmation_email() {
     let second_email_request = &app.email_server.received_requests().await.unwrap()[0];
     let second_confirmation_links = app.get_confirmation_links(second_email_request);
 
-    assert_eq!(first_confirmation_links.plain_text, second_confirmation_links.plain_text);
+    assert_ne!(
+        first_confirmation_links.plain_text,
+        second_confirmation_links.plain_text
+    );
+
+    // The first link no longer works, the second one does
+    let response = reqwest::get(first_confirmation_links.plain_text)
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+    let response = reqwest::get(second_confirmation_links.plain_text)
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn confirmation_emails_to_a_pending_address_are_throttled() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..3 {
+        let response = app.post_subscriptions(body.into()).await;
+        assert_eq!(200, response.status().as_u16());
+    }
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[actix_web::test]
+async fn re_subscribing_a_confirmed_subscriber_does_not_send_a_confirmation_email() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(200, response.status().as_u16());
 }
 
 #[actix_web::test]
@@ -204,7 +265,7 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     let app = spawn_app().await;
 
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;",)
         .execute(&app.db_pool)
@@ -215,3 +276,443 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
 
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[actix_web::test]
+async fn subscribe_adds_the_subscriber_to_the_default_list() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let lists = sqlx::query!(
+        r#"
+        SELECT l.slug
+        FROM list_subscriptions m
+        JOIN lists l ON l.list_id = m.list_id
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0].slug, "newsletter");
+}
+
+#[actix_web::test]
+async fn subscribing_to_another_list_keeps_a_single_subscriber() {
+    // given
+    let app = spawn_app().await;
+    app.create_list("rust-weekly").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&list=rust-weekly".into(),
+        )
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 1);
+    let n_memberships = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM list_subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_memberships, 2);
+}
+
+#[actix_web::test]
+async fn a_confirmed_subscriber_must_confirm_each_new_list() {
+    // given
+    let app = spawn_app().await;
+    app.create_list("rust-weekly").await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let response = app
+        .post_subscriptions(format!("{}&list=rust-weekly", body))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let membership = sqlx::query!(
+        r#"
+        SELECT m.status
+        FROM list_subscriptions m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE l.slug = 'rust-weekly'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(membership.status, "pending_confirmation");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let statuses: Vec<_> = sqlx::query!("SELECT status FROM list_subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.status)
+        .collect();
+    assert_eq!(statuses, ["confirmed", "confirmed"]);
+}
+
+#[actix_web::test]
+async fn subscribe_returns_a_400_for_an_unknown_list() {
+    let app = spawn_app().await;
+
+    let test_cases = vec![
+        (
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&list=unknown",
+            "unknown list",
+        ),
+        (
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&list=Not%20A%20Slug",
+            "invalid list",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_subscriptions(body.into()).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[actix_web::test]
+async fn subscribe_stores_the_locale_and_tags() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&locale=en&tags=vip%2Cbeta";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.locale.as_deref(), Some("en"));
+    let tags: Vec<_> = sqlx::query!("SELECT tag FROM subscriber_tags ORDER BY tag")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.tag)
+        .collect();
+    assert_eq!(tags, vec!["beta", "vip"]);
+}
+
+#[actix_web::test]
+async fn subscribe_returns_a_400_for_invalid_attributes() {
+    let app = spawn_app().await;
+
+    let test_cases = vec![
+        (
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&locale=English",
+            "invalid locale",
+        ),
+        (
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&tags=Big%20Spender",
+            "invalid tag",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_subscriptions(body.into()).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[actix_web::test]
+async fn subscribe_is_rate_limited_per_ip() {
+    // setup
+    let app = spawn_app_with(|c| c.rate_limit.subscriptions_per_ip_per_hour = 2).await;
+
+    // given
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    for email in ["ursula%40gmail.com", "le_guin%40gmail.com"] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={}", email))
+            .await;
+        assert_eq!(200, response.status().as_u16());
+    }
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[actix_web::test]
+async fn subscribe_is_rate_limited_per_email() {
+    // setup
+    let app = spawn_app_with(|c| c.rate_limit.subscriptions_per_email_per_hour = 1).await;
+
+    // given
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=Ursula_Le_Guin%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[actix_web::test]
+async fn subscribe_ignores_requests_filling_in_the_honeypot() {
+    // setup
+    let app = spawn_app().await;
+
+    // given
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&website=http%3A%2F%2Fspam.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app.post_subscriptions(body.into()).await;
+
+    // then
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[actix_web::test]
+async fn subscribe_returns_a_400_for_a_blocked_email_domain() {
+    // setup
+    let app = spawn_app_with(|c| {
+        c.subscriptions.blocked_email_domains = EmailDomainBlocklist::parse_list("mailinator.com")
+    })
+    .await;
+
+    // given
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40mailinator.com".into())
+        .await;
+
+    // then
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[actix_web::test]
+async fn subscribe_accepts_a_json_body() {
+    // setup
+    let app = spawn_app().await;
+
+    // given
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+
+    // then
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "accepted");
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+}
+
+#[actix_web::test]
+async fn the_json_body_does_not_tell_whether_an_address_is_confirmed() {
+    // given
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+    });
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let first: serde_json::Value = app
+        .post_subscriptions_json(&body)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let second: serde_json::Value = app
+        .post_subscriptions_json(&body)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(first, second);
+}
+
+#[actix_web::test]
+async fn subscribe_returns_structured_json_errors() {
+    // setup
+    let app = spawn_app().await;
+
+    // given
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "", "email": "ursula_le_guin@gmail.com"}),
+            "name",
+            "invalid",
+        ),
+        (
+            serde_json::json!({"name": "Ursula", "email": "definitely-not-an-email"}),
+            "email",
+            "invalid",
+        ),
+        (
+            serde_json::json!({"name": "Ursula", "email": "ursula@mailinator.com"}),
+            "email",
+            "blocked_domain",
+        ),
+        (
+            serde_json::json!({"name": "Ursula", "email": "ursula_le_guin@gmail.com", "list": "nope"}),
+            "list",
+            "unknown_list",
+        ),
+    ];
+
+    for (body, field, code) in test_cases {
+        // when
+        let response = app.post_subscriptions_json(&body).await;
+
+        // then
+        assert_eq!(400, response.status().as_u16(), "Payload: {}", body);
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["field"], field, "Payload: {}", body);
+        assert_eq!(error["code"], code, "Payload: {}", body);
+        assert!(!error["message"].as_str().unwrap().is_empty());
+    }
+}
+
+#[actix_web::test]
+async fn subscribe_returns_a_json_400_for_a_malformed_json_body() {
+    // setup
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({"name": "le guin"}))
+        .await;
+
+    // then
+    assert_eq!(400, response.status().as_u16());
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "invalid_body");
+    assert!(error["message"].as_str().unwrap().contains("email"));
+}
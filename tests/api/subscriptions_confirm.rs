@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
@@ -24,7 +24,7 @@ async fn link_returned_by_subscribe_returns_a_200_if_called() {
     let app = spawn_app().await;
 
     // given
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -49,7 +49,7 @@ async fn link_returned_by_subscribe_returns_a_200_if_called() {
 async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     // given
     let app = spawn_app().await;
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -95,7 +95,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
 async fn clicking_again_on_the_confirmation_link_returns_gone() {
     // given
     let app = spawn_app().await;
-    let body = "{\"name\":\"le guin\",\"email\":\"ursula_le_guin@gmail.com\"}";
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -121,3 +121,202 @@ async fn clicking_again_on_the_confirmation_link_returns_gone() {
 
     assert_eq!(second_response.status(), 410);
 }
+
+#[actix_web::test]
+async fn expired_confirmation_links_are_rejected() {
+    // given
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response.text().await.unwrap().contains("expired"));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[actix_web::test]
+async fn confirmation_returns_json_when_asked_for() {
+    // setup
+    let app = spawn_app().await;
+
+    // given
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // when
+    let response = app
+        .api_client
+        .get(confirmation_links.html)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "confirmed");
+}
+
+#[actix_web::test]
+async fn confirmation_errors_are_structured_json_when_asked_for() {
+    // setup
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=doesnotexist",
+            app.address
+        ))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["field"], "subscription_token");
+    assert_eq!(error["code"], "unknown_token");
+}
+
+async fn subscribe_and_get_confirmation_link(app: &TestApp) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_links(email_request).html
+}
+
+#[actix_web::test]
+async fn confirmation_renders_an_html_page() {
+    // given
+    let app = spawn_app().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    // when
+    let response = reqwest::get(confirmation_link.as_ref()).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Thank you le guin"));
+
+    // when
+    let page = reqwest::get(confirmation_link)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // then
+    assert!(page.contains("already confirmed"));
+}
+
+#[actix_web::test]
+async fn unknown_tokens_render_the_invalid_link_page() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=doesnotexist",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.text().await.unwrap().contains("not valid"));
+}
+
+#[actix_web::test]
+async fn confirmation_pages_can_be_overridden_with_templates() {
+    // given
+    let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir(&directory).unwrap();
+    std::fs::write(
+        directory.join("confirmed.html"),
+        "<h1>Welcome to ACME, {{ name }}!</h1>",
+    )
+    .unwrap();
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_templates_directory = Some(directory.clone())
+    })
+    .await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    // when
+    let page = reqwest::get(confirmation_link)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(page, "<h1>Welcome to ACME, le guin!</h1>");
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[actix_web::test]
+async fn confirmation_redirects_to_the_thank_you_url_when_configured() {
+    // given
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_redirect_url = Some("https://example.com/thanks".into())
+    })
+    .await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    // when
+    let response = app.api_client.get(confirmation_link).send().await.unwrap();
+
+    // then
+    assert_is_redirect_to(&response, "https://example.com/thanks");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
@@ -0,0 +1,370 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+use wiremock::{Request, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+
+/// Sends a newsletter to the only subscriber and returns the query string of
+/// the unsubscribe link it contained.
+async fn deliver_newsletter(app: &TestApp) -> String {
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text.",
+            "html_content": "<p>Newsletter body as HTML.</p>",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_links = app.get_unsubscribe_links(&email_request);
+    unsubscribe_links.html.query().unwrap().to_string()
+}
+
+/// The data access email is sent in the background.
+async fn wait_for_emails(app: &TestApp, n_emails: usize) -> Request {
+    for _ in 0..50 {
+        let mut requests = app.email_server.received_requests().await.unwrap();
+        if requests.len() >= n_emails {
+            return requests.pop().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("No email was sent.");
+}
+
+/// Asks for a link to the data of the only subscriber and returns its token.
+async fn request_data_access_token(app: &TestApp) -> String {
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    let n_emails = app.email_server.received_requests().await.unwrap().len();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = app.post_data_access_request(&email).await;
+    assert_is_redirect_to(&response, "/subscriptions/data/request");
+
+    let email_request = wait_for_emails(app, n_emails + 1).await;
+    let link = app.get_confirmation_links(&email_request).html;
+    assert_eq!(link.path(), "/subscriptions/data");
+    link.query_pairs()
+        .find(|(k, _)| k == "data_access_token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+#[actix_web::test]
+async fn a_forged_token_is_rejected_with_401() {
+    let app = spawn_app().await;
+    let token = "a".repeat(40);
+
+    assert_eq!(app.get_data_access(&token).await.status().as_u16(), 401);
+    assert_eq!(
+        app.post_subscriber_data(&token).await.status().as_u16(),
+        401
+    );
+    assert_eq!(
+        app.post_erase_subscriber(&token).await.status().as_u16(),
+        401
+    );
+}
+
+#[actix_web::test]
+async fn a_malformed_token_is_rejected_with_401() {
+    let app = spawn_app().await;
+    let token = "not-a-token";
+
+    assert_eq!(app.get_data_access(token).await.status().as_u16(), 401);
+    assert_eq!(app.post_subscriber_data(token).await.status().as_u16(), 401);
+    assert_eq!(
+        app.post_erase_subscriber(token).await.status().as_u16(),
+        401
+    );
+}
+
+#[actix_web::test]
+async fn the_unsubscribe_link_does_not_give_access_to_the_data() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+    let query = deliver_newsletter(&app).await;
+    let unsubscribe_token = url::form_urlencoded::parse(query.as_bytes())
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    // when
+    let response = app.post_subscriber_data(&unsubscribe_token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn requests_for_unknown_addresses_look_the_same_and_send_nothing() {
+    // given
+    let app = spawn_app().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app.post_data_access_request("nobody@example.com").await;
+
+    // then
+    assert_is_redirect_to(&response, "/subscriptions/data/request");
+    let html_page = app
+        .api_client
+        .get(format!("{}/subscriptions/data/request", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("If we know this address, a link to your data was sent to it."));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+}
+
+#[actix_web::test]
+async fn subscribers_can_download_everything_we_store_about_them() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+    deliver_newsletter(&app).await;
+    let token = request_data_access_token(&app).await;
+
+    // when
+    let response = app.post_subscriber_data(&token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let data: serde_json::Value = response.json().await.unwrap();
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(data["subscription"]["email"], saved.email);
+    assert_eq!(data["subscription"]["status"], "confirmed");
+    assert_eq!(data["lists"][0]["slug"], "newsletter");
+    assert!(data.get("subscription_tokens").is_none());
+    assert_eq!(
+        data["deliveries"][0]["newsletter_issue"],
+        "Newsletter title"
+    );
+    assert_eq!(data["deliveries"][0]["status_code"], 200);
+}
+
+#[actix_web::test]
+async fn opening_the_data_access_link_does_not_use_it_up() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = request_data_access_token(&app).await;
+
+    // when
+    let response = app.get_data_access(&token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<form method="post" action="/subscriptions/data">"#));
+    assert!(html_page.contains(r#"<form method="post" action="/subscriptions/erase">"#));
+    assert_eq!(
+        app.post_subscriber_data(&token).await.status().as_u16(),
+        200
+    );
+}
+
+#[actix_web::test]
+async fn a_data_access_link_works_only_once() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = request_data_access_token(&app).await;
+    app.post_subscriber_data(&token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let response = app.post_erase_subscriber(&token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 410);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 1);
+}
+
+#[actix_web::test]
+async fn a_failed_export_or_erasure_does_not_use_up_the_link() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = request_data_access_token(&app).await;
+    // Both the export and the erasure read this table.
+    sqlx::query!("ALTER TABLE issue_delivery_failures RENAME TO unavailable")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let export = app.post_subscriber_data(&token).await;
+    let erasure = app.post_erase_subscriber(&token).await;
+
+    // then
+    assert_eq!(export.status().as_u16(), 500);
+    assert_eq!(erasure.status().as_u16(), 500);
+    sqlx::query!("ALTER TABLE unavailable RENAME TO issue_delivery_failures")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        app.post_erase_subscriber(&token).await.status().as_u16(),
+        200
+    );
+}
+
+#[actix_web::test]
+async fn an_expired_data_access_link_is_rejected_with_403() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = request_data_access_token(&app).await;
+    sqlx::query!("UPDATE data_access_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = app.post_subscriber_data(&token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[actix_web::test]
+async fn erasure_leaves_only_a_hash_of_the_address() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+    deliver_newsletter(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, suppressed_at)
+        VALUES (lower($1), 'complaint', now())
+        "#,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let token = request_data_access_token(&app).await;
+
+    // when
+    let response = app.post_erase_subscriber(&token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let n_rows = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM subscriptions) +
+            (SELECT COUNT(*) FROM subscription_tokens) +
+            (SELECT COUNT(*) FROM data_access_tokens) +
+            (SELECT COUNT(*) FROM list_subscriptions) +
+            (SELECT COUNT(*) FROM issue_delivery_queue) +
+            (SELECT COUNT(*) FROM newsletter_deliveries) +
+            (SELECT COUNT(*) FROM suppressions) AS "n!"
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .n;
+    assert_eq!(n_rows, 0);
+    let erased = sqlx::query!("SELECT email_hash FROM erased_subscribers")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!erased.email_hash.contains(&email));
+    assert_eq!(
+        app.post_subscriber_data(&token).await.status().as_u16(),
+        401
+    );
+}
+
+#[actix_web::test]
+async fn erased_subscribers_are_not_brought_back_by_an_import() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    let token = request_data_access_token(&app).await;
+    app.post_erase_subscriber(&token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let csv = format!(
+        "email,name,status\n{},Someone,confirmed\n",
+        email.to_uppercase()
+    );
+    let response = app
+        .post_import_subscribers(&serde_json::json!({ "csv": csv }))
+        .await;
+
+    // then
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<td>Erased at the subscriber&#x27;s request</td>"));
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+}
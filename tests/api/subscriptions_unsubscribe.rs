@@ -0,0 +1,321 @@
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+use wiremock::matchers::{method, path};
+
+async fn publish_newsletter(app: &TestApp) {
+    let newsletter_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text.",
+        "html_content": "<p>Newsletter body as HTML.</p>",
+    });
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[actix_web::test]
+async fn unsubscribe_without_token_is_rejected_with_400() {
+    let app = spawn_app().await;
+
+    let response = app
+        .get_unsubscribe(&format!("subscriber_id={}", Uuid::new_v4()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn unsubscribe_with_a_forged_token_is_rejected_with_401() {
+    let app = spawn_app().await;
+
+    let query = format!("subscriber_id={}&token={}", Uuid::new_v4(), "a".repeat(64));
+
+    assert_eq!(app.get_unsubscribe(&query).await.status().as_u16(), 401);
+    assert_eq!(app.post_unsubscribe(&query).await.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn newsletters_contain_an_unsubscribe_link() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_links = app.get_unsubscribe_links(&email_request);
+
+    assert_eq!(unsubscribe_links.html, unsubscribe_links.plain_text);
+    assert_eq!(unsubscribe_links.html.path(), "/subscriptions/unsubscribe");
+}
+
+#[actix_web::test]
+async fn submitting_the_unsubscribe_form_unsubscribes_a_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+
+    let mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_links = app.get_unsubscribe_links(&email_request);
+    drop(mock_guard);
+
+    // when
+    let html_page = reqwest::get(unsubscribe_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let response = app
+        .post_unsubscribe(unsubscribe_links.html.query().unwrap())
+        .await;
+
+    // then
+    let form_action = format!(
+        r#"action="/subscriptions/unsubscribe?{}""#,
+        unsubscribe_links
+            .html
+            .query()
+            .unwrap()
+            .replace('&', "&amp;")
+    );
+    assert!(html_page.contains(&form_action));
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Mock verifies we didn't send the newsletter
+}
+
+#[actix_web::test]
+async fn newsletters_carry_one_click_unsubscribe_headers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_links = app.get_unsubscribe_links(&email_request);
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+
+    let list_unsubscribe = body["headers"]["List-Unsubscribe"].as_str().unwrap();
+    let list_unsubscribe = list_unsubscribe
+        .strip_prefix('<')
+        .and_then(|s| s.strip_suffix('>'))
+        .unwrap();
+    let mut list_unsubscribe = reqwest::Url::parse(list_unsubscribe).unwrap();
+    list_unsubscribe.set_port(Some(app.port)).unwrap();
+    assert_eq!(list_unsubscribe, unsubscribe_links.html);
+    assert_eq!(
+        body["headers"]["List-Unsubscribe-Post"],
+        "List-Unsubscribe=One-Click"
+    );
+}
+
+#[actix_web::test]
+async fn one_click_post_to_the_unsubscribe_link_unsubscribes_a_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_links = app.get_unsubscribe_links(&email_request);
+
+    // when
+    reqwest::Client::new()
+        .post(unsubscribe_links.html)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // then
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[actix_web::test]
+async fn opening_the_unsubscribe_link_does_not_unsubscribe() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_test_user().await.unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_links = app.get_unsubscribe_links(&email_request);
+
+    // when
+    let response = reqwest::get(unsubscribe_links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_web::test]
+async fn unsubscribing_from_a_list_keeps_the_other_lists() {
+    // given
+    let app = spawn_app().await;
+    app.create_list("rust-weekly").await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for body in [body.to_string(), format!("{}&list=rust-weekly", body)] {
+        app.post_subscriptions(body)
+            .await
+            .error_for_status()
+            .unwrap();
+        let email_request = app
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        reqwest::get(app.get_confirmation_links(&email_request).html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    let membership = sqlx::query!(
+        r#"
+        SELECT m.subscriber_id, m.list_id
+        FROM list_subscriptions m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE l.slug = 'newsletter'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    let unsubscribe_link = zero2prod::routes::unsubscribe_link(
+        &app.address,
+        &membership.subscriber_id,
+        &membership.list_id,
+        &app.hmac_secret,
+    );
+
+    // when
+    let query = reqwest::Url::parse(&unsubscribe_link).unwrap();
+    let response = app.post_unsubscribe(query.query().unwrap()).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let statuses = sqlx::query!(
+        r#"
+        SELECT l.slug, m.status
+        FROM list_subscriptions m
+        JOIN lists l ON l.list_id = m.list_id
+        ORDER BY l.slug
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(statuses[0].slug, "newsletter");
+    assert_eq!(statuses[0].status, "unsubscribed");
+    assert_eq!(statuses[1].slug, "rust-weekly");
+    assert_eq!(statuses[1].status, "confirmed");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
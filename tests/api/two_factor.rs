@@ -0,0 +1,277 @@
+use chrono::Utc;
+use zero2prod::authentication::TotpSecret;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+/// The key and the recovery codes shown while enrolling the test user.
+///
+/// The enrolment code is the previous one, leaving the current and the next
+/// ones for the test to log in with.
+async fn enable_two_factor(app: &TestApp) -> (TotpSecret, Vec<String>) {
+    let html_page = app.get_two_factor_html().await;
+    let key = html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|s| s.split("</code>").next())
+        .unwrap();
+    let secret = TotpSecret::parse_base32(key).unwrap();
+
+    let response = app
+        .post_two_factor(&serde_json::json!({
+            "code": secret.generate_code(Utc::now().timestamp() - 30),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s.split("</code>").next().unwrap().to_string())
+        .collect();
+    (secret, recovery_codes)
+}
+
+async fn login_with_password(app: &TestApp) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password,
+    }))
+    .await
+}
+
+fn current_code(secret: &TotpSecret) -> String {
+    secret.generate_code(Utc::now().timestamp())
+}
+
+#[actix_web::test]
+async fn enrolled_users_need_a_code_after_their_password() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let (secret, recovery_codes) = enable_two_factor(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+    app.post_logout().await;
+
+    // when
+    let response = login_with_password(&app).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login/two-factor");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+    assert_eq!(app.get_login_two_factor().await.status().as_u16(), 200);
+
+    // when
+    let response = app.post_login_two_factor(&current_code(&secret)).await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn the_second_step_needs_the_password_first() {
+    let app = spawn_app().await;
+
+    assert_is_redirect_to(&app.get_login_two_factor().await, "/login");
+    assert_is_redirect_to(&app.post_login_two_factor("123456").await, "/login");
+}
+
+#[actix_web::test]
+async fn wrong_codes_are_rejected_and_eventually_start_over() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    enable_two_factor(&app).await;
+    app.post_logout().await;
+    login_with_password(&app).await;
+
+    // when
+    let response = app.post_login_two_factor("000000x").await;
+
+    // then
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html_page = app.get_login_two_factor().await.text().await.unwrap();
+    assert!(html_page.contains("Authentication failed"));
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+
+    // when
+    for _ in 0..3 {
+        app.post_login_two_factor("000000x").await;
+    }
+    let response = app.post_login_two_factor("000000x").await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_login_two_factor().await, "/login");
+}
+
+#[actix_web::test]
+async fn codes_cannot_be_replayed() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let (secret, _) = enable_two_factor(&app).await;
+    app.post_logout().await;
+    let code = current_code(&secret);
+    login_with_password(&app).await;
+    app.post_login_two_factor(&code).await;
+    app.post_logout().await;
+
+    // when
+    login_with_password(&app).await;
+    let response = app.post_login_two_factor(&code).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[actix_web::test]
+async fn recovery_codes_work_once() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+    app.post_logout().await;
+
+    // when
+    login_with_password(&app).await;
+    let response = app
+        .post_login_two_factor(&recovery_codes[0].to_uppercase())
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("you have 9 recovery codes left"));
+
+    // when
+    app.post_logout().await;
+    login_with_password(&app).await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[actix_web::test]
+async fn enrolment_needs_a_valid_code() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    app.get_two_factor_html().await;
+
+    // when
+    let response = app
+        .post_two_factor(&serde_json::json!({ "code": "123456x" }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("The code is not valid"));
+    assert!(html_page.contains("Two-factor authentication is disabled."));
+    app.post_logout().await;
+    assert_is_redirect_to(&login_with_password(&app).await, "/admin/dashboard");
+}
+
+#[actix_web::test]
+async fn the_secret_is_stored_encrypted() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let (secret, recovery_codes) = enable_two_factor(&app).await;
+
+    // then
+    let user = sqlx::query!(
+        r#"SELECT totp_secret AS "totp_secret!" FROM users WHERE user_id = $1"#,
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    let key = secret.to_base32();
+    assert!(!String::from_utf8_lossy(&user.totp_secret).contains(&key));
+    assert_ne!(user.totp_secret.len(), 20);
+    let stored_codes = sqlx::query!("SELECT code_hash FROM user_recovery_codes")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(stored_codes
+        .iter()
+        .all(|r| !recovery_codes.contains(&r.code_hash)));
+}
+
+#[actix_web::test]
+async fn two_factor_authentication_can_be_disabled() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let (secret, _) = enable_two_factor(&app).await;
+
+    // when
+    let response = app
+        .post_disable_two_factor(&serde_json::json!({ "code": current_code(&secret) }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("Two-factor authentication is disabled."));
+    app.post_logout().await;
+    assert_is_redirect_to(&login_with_password(&app).await, "/admin/dashboard");
+}
+
+#[actix_web::test]
+async fn too_many_wrong_codes_lock_the_second_step_out() {
+    // given
+    let app = spawn_app_with(|c| {
+        c.rate_limit.login_failures_before_delay = 100;
+        c.rate_limit.login_failures_per_username = 2;
+    })
+    .await;
+    app.login_test_user().await.unwrap();
+    let (secret, _) = enable_two_factor(&app).await;
+    app.post_logout().await;
+    login_with_password(&app).await;
+    for _ in 0..2 {
+        app.post_login_two_factor("000000x").await;
+    }
+
+    // when
+    let response = app.post_login_two_factor(&current_code(&secret)).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+    login_with_password(&app).await;
+    let response = app.post_login_two_factor(&current_code(&secret)).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn enrolment_is_refused_without_an_encryption_key() {
+    // given
+    let app = spawn_app_with(|c| c.application.totp_encryption_key = None).await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let html_page = app.get_two_factor_html().await;
+    let response = app
+        .post_two_factor(&serde_json::json!({ "code": "123456" }))
+        .await;
+
+    // then
+    assert!(html_page.contains("Two-factor authentication is not available"));
+    assert!(!html_page.contains("<code>"));
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let user = sqlx::query!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(user.totp_secret.is_none());
+}
@@ -0,0 +1,258 @@
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, when_sending_an_email, TestApp, TestUser};
+
+/// Invites `email` as `role` and returns the link from the invitation email.
+async fn invite(app: &TestApp, email: &str, role: &str) -> reqwest::Url {
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = app
+        .post_invitation(&serde_json::json!({ "email": email, "role": role }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_invitation_link(&email_request)
+}
+
+fn invitation_token(link: &reqwest::Url) -> String {
+    link.query_pairs()
+        .find(|(k, _)| k == "invitation_token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+#[actix_web::test]
+async fn invitees_choose_their_own_password() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+
+    // when
+    let link = invite(&app, "ursula@gmail.com", "editor").await;
+
+    // then
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("An invitation has been sent to ursula@gmail.com."));
+    assert!(html_page.contains("<td>ursula@gmail.com</td><td>editor</td>"));
+
+    // when the invitee follows the link
+    app.post_logout().await;
+    let response = app.get_accept_invitation(&link).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("You have been invited as editor"));
+
+    // when
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "invitation_token": invitation_token(&link),
+            "username": "ursula",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your account is ready, you can log in now."));
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "ursula",
+            "password": "a-long-enough-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let dashboard = app.get_admin_dashboard_html().await;
+    assert!(dashboard.contains("You are signed in as editor."));
+}
+
+#[actix_web::test]
+async fn invitations_can_only_be_used_once() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let link = invite(&app, "ursula@gmail.com", "viewer").await;
+    let body = |username: &str| {
+        serde_json::json!({
+            "invitation_token": invitation_token(&link),
+            "username": username,
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        })
+    };
+    app.post_accept_invitation(&body("ursula")).await;
+
+    // when
+    let form = app.get_accept_invitation(&link).await;
+    let response = app.post_accept_invitation(&body("le_guin")).await;
+
+    // then
+    assert_eq!(form.status().as_u16(), 410);
+    assert_eq!(response.status().as_u16(), 410);
+    let users = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM users WHERE username = 'le_guin'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(users.count, 0);
+}
+
+#[actix_web::test]
+async fn expired_invitations_are_rejected() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let link = invite(&app, "ursula@gmail.com", "viewer").await;
+    sqlx::query!("UPDATE user_invitations SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = app.get_accept_invitation(&link).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(!app
+        .get_users_html()
+        .await
+        .contains("<td>ursula@gmail.com</td>"));
+}
+
+#[actix_web::test]
+async fn a_new_invitation_replaces_the_previous_one() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let first_link = invite(&app, "ursula@gmail.com", "viewer").await;
+
+    // when
+    let second_link = invite(&app, "Ursula@gmail.com", "editor").await;
+
+    // then
+    let response = app.get_accept_invitation(&first_link).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_accept_invitation(&second_link).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn unknown_invitation_tokens_are_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "invitation_token": "a".repeat(40),
+            "username": "ursula",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn the_password_must_follow_the_usual_rules() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let link = invite(&app, "ursula@gmail.com", "viewer").await;
+
+    // when
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "invitation_token": invitation_token(&link),
+            "username": "ursula",
+            "new_password": "short",
+            "new_password_check": "short",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!(
+            "/invitations/accept?invitation_token={}",
+            invitation_token(&link)
+        ),
+    );
+    let html_page = app.get_accept_invitation(&link).await.text().await.unwrap();
+    assert!(html_page.contains("Your new password should be at least 12 characters in length!"));
+}
+
+#[actix_web::test]
+async fn only_owners_can_invite() {
+    // given
+    let app = spawn_app().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login(&editor).await.unwrap();
+
+    // when
+    let response = app
+        .post_invitation(&serde_json::json!({
+            "email": "ursula@gmail.com",
+            "role": "owner",
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[actix_web::test]
+async fn usernames_are_limited_to_a_safe_charset() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await.unwrap();
+    let link = invite(&app, "ursula@gmail.com", "viewer").await;
+
+    // when
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "invitation_token": invitation_token(&link),
+            "username": "<script>alert(1)</script>",
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!(
+            "/invitations/accept?invitation_token={}",
+            invitation_token(&link)
+        ),
+    );
+    let html_page = app.get_accept_invitation(&link).await.text().await.unwrap();
+    assert!(html_page.contains("A username can only contain letters, digits"));
+    assert!(!html_page.contains("<script>"));
+    let users =
+        sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM users WHERE username LIKE '%script%'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(users.count, 0);
+}